@@ -0,0 +1,158 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::{stream, StreamExt};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::time::sleep;
+
+use super::{AccountSource, AccountUpdate};
+use crate::{
+    error::Error,
+    utils::{deser::AccountEncoding, websocket_client::Backoff},
+};
+
+/// Keys RPC nodes accept in a single `getMultipleAccounts` request
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Outcome of an account of a batched fetch, `None` if the account does not exist
+/// and `Error::FetchChunkFailed` with the error of its chunk if it still failed after
+/// the retries
+pub type FetchedAccount = Result<Option<AccountUpdate>, Error>;
+
+/// Fetches any number of accounts in chunks of at most `MAX_MULTIPLE_ACCOUNTS`,
+/// running `concurrency` chunks at a time and retrying transient errors
+#[derive(Clone, Copy, Debug)]
+pub struct BatchedFetch {
+    chunk_size: usize,
+    concurrency: usize,
+    retries: u32,
+    backoff: Backoff,
+}
+
+impl Default for BatchedFetch {
+    fn default() -> Self {
+        Self {
+            chunk_size: MAX_MULTIPLE_ACCOUNTS,
+            concurrency: 4,
+            retries: 3,
+            backoff: Backoff {
+                initial: Duration::from_millis(250),
+                max: Duration::from_secs(2),
+            },
+        }
+    }
+}
+
+impl BatchedFetch {
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.clamp(1, MAX_MULTIPLE_ACCOUNTS);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Accounts in the order of `addresses`, each with the context slot of its chunk
+    pub async fn get_multiple_accounts(
+        &self,
+        source: &dyn AccountSource,
+        addresses: &[Pubkey],
+        encoding: &AccountEncoding,
+        min_context_slot: Option<u64>,
+    ) -> Vec<FetchedAccount> {
+        let chunks = stream::iter(addresses.chunks(self.chunk_size))
+            .map(|chunk| self.fetch_chunk(source, chunk, encoding, min_context_slot))
+            .buffered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        addresses
+            .chunks(self.chunk_size)
+            .zip(chunks)
+            .flat_map(|(chunk, res)| match res {
+                Ok((slot, ais)) => ais
+                    .into_iter()
+                    .map(|ai| Ok(ai.map(|account| AccountUpdate { slot, account })))
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    let e = Arc::new(e);
+                    chunk
+                        .iter()
+                        .map(|_| Err(Error::FetchChunkFailed(e.clone())))
+                        .collect()
+                }
+            })
+            .collect()
+    }
+
+    async fn fetch_chunk(
+        &self,
+        source: &dyn AccountSource,
+        chunk: &[Pubkey],
+        encoding: &AccountEncoding,
+        min_context_slot: Option<u64>,
+    ) -> Result<(u64, Vec<Option<Account>>), Error> {
+        let mut attempt = 0;
+
+        loop {
+            match source
+                .get_multiple_accounts(chunk, encoding, min_context_slot)
+                .await
+            {
+                Ok((slot, ais)) if ais.len() == chunk.len() => return Ok((slot, ais)),
+                Ok((_, ais)) => {
+                    println!(
+                        "Fetched {} accounts instead of {}, starting at {}",
+                        ais.len(),
+                        chunk.len(),
+                        chunk[0]
+                    );
+                    return Err(Error::UnableToFetchAccount);
+                }
+                Err(e) if is_transient(&e) && attempt < self.retries => {
+                    let delay = self.backoff.delay(attempt);
+                    println!(
+                        "Unable to fetch {} accounts starting at {}: {}, retrying in {}ms",
+                        chunk.len(),
+                        chunk[0],
+                        e.to_string(),
+                        delay.as_millis()
+                    );
+
+                    attempt += 1;
+                    sleep(delay).await;
+                }
+                Err(e) => {
+                    println!(
+                        "Unable to fetch {} accounts starting at {}: {}",
+                        chunk.len(),
+                        chunk[0],
+                        e.to_string()
+                    );
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Errors a later attempt can succeed on, ex: an unhealthy node or one behind
+/// `min_context_slot`. Rejected requests fail the same way when retried
+fn is_transient(e: &Error) -> bool {
+    match e {
+        Error::RpcError(kind) => kind.is_transient(),
+        Error::MinContextSlotNotReached => true,
+        _ => false,
+    }
+}
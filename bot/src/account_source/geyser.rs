@@ -0,0 +1,385 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::Account,
+    commitment_config::{CommitmentConfig, CommitmentLevel as RpcCommitmentLevel},
+    pubkey::Pubkey,
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedSender},
+    time::{sleep, timeout, Instant},
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{
+    metadata::AsciiMetadataValue,
+    service::{interceptor::InterceptedService, Interceptor},
+    transport::{Channel, Endpoint},
+    Request, Status, Streaming,
+};
+use yellowstone_grpc_proto::prelude::{
+    geyser_client::GeyserClient, subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+    SubscribeRequestAccountsDataSlice, SubscribeRequestFilterAccounts, SubscribeUpdate,
+    SubscribeUpdateAccount,
+};
+
+use super::{
+    rpc, subscribe_each, AccountSource, AccountSubscription, AccountUpdate, AccountUpdates,
+    TaggedAccountUpdates,
+};
+use crate::{
+    error::Error,
+    utils::{
+        deser::AccountEncoding,
+        websocket_client::{Backoff, Notification, STABLE_CONNECTION},
+    },
+};
+
+/// Name of the accounts filter of the subscribe requests, echoed by the server
+const ACCOUNTS_FILTER: &str = "accounts";
+
+/// Accounts streamed by a Geyser subscription, an account matches if it is listed
+/// in `accounts` or owned by one of `owners`
+#[derive(Clone, Debug, Default)]
+pub struct GeyserAccountFilter {
+    pub accounts: Vec<Pubkey>,
+    pub owners: Vec<Pubkey>,
+}
+
+pub type GeyserAccountUpdates = UnboundedReceiverStream<Notification<(Pubkey, AccountUpdate)>>;
+
+/// Adds the `x-token` header expected by authenticated Geyser endpoints
+#[derive(Clone)]
+struct XToken(Option<AsciiMetadataValue>);
+
+impl Interceptor for XToken {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(x_token) = &self.0 {
+            request.metadata_mut().insert("x-token", x_token.clone());
+        }
+        Ok(request)
+    }
+}
+
+type Client = GeyserClient<InterceptedService<Channel, XToken>>;
+
+/// Fetches accounts over RPC, updates are streamed by a Yellowstone Geyser gRPC
+/// subscription. Streams reconnect on their own and signal `Notification::Gap`
+pub struct GeyserAccountSource {
+    rpc_client: Arc<RpcClient>,
+    endpoint: String,
+    x_token: Option<String>,
+    backoff: Backoff,
+}
+
+impl GeyserAccountSource {
+    pub fn new(rpc_client: Arc<RpcClient>, endpoint: String) -> Self {
+        Self {
+            rpc_client,
+            endpoint,
+            x_token: None,
+            backoff: Backoff::default(),
+        }
+    }
+
+    pub fn with_x_token(mut self, x_token: String) -> Self {
+        self.x_token = Some(x_token);
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    async fn connect(endpoint: &str, x_token: &Option<String>) -> Result<Client, String> {
+        let x_token = x_token
+            .as_ref()
+            .map(|x_token| AsciiMetadataValue::try_from(x_token.as_str()))
+            .transpose()
+            .map_err(|_| "Invalid x-token".to_string())?;
+
+        let channel = Endpoint::from_shared(endpoint.to_string())
+            .map_err(|e| e.to_string())?
+            .connect()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(GeyserClient::with_interceptor(channel, XToken(x_token)))
+    }
+
+    fn subscribe_request(
+        filter: &GeyserAccountFilter,
+        encoding: &AccountEncoding,
+        commitment: CommitmentConfig,
+    ) -> SubscribeRequest {
+        let commitment = match commitment.commitment {
+            RpcCommitmentLevel::Processed => CommitmentLevel::Processed,
+            RpcCommitmentLevel::Finalized => CommitmentLevel::Finalized,
+            _ => CommitmentLevel::Confirmed,
+        };
+
+        SubscribeRequest {
+            accounts: HashMap::from([(
+                ACCOUNTS_FILTER.to_string(),
+                SubscribeRequestFilterAccounts {
+                    account: filter.accounts.iter().map(|a| a.to_string()).collect(),
+                    owner: filter.owners.iter().map(|o| o.to_string()).collect(),
+                    ..Default::default()
+                },
+            )]),
+            commitment: Some(commitment as i32),
+            accounts_data_slice: encoding
+                .data_slice
+                .iter()
+                .map(|data_slice| SubscribeRequestAccountsDataSlice {
+                    offset: data_slice.offset as u64,
+                    length: data_slice.length as u64,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Slot ordered updates of the accounts matching `filter`. Updates older than the
+    /// last one seen for an account, ex: replayed after a reconnect, are skipped.
+    /// Without any message for `silence_timeout`, pings included, the stream reconnects
+    pub fn subscribe_accounts(
+        &self,
+        filter: GeyserAccountFilter,
+        encoding: &AccountEncoding,
+        silence_timeout: Option<Duration>,
+    ) -> GeyserAccountUpdates {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let account_stream = AccountStream {
+            endpoint: self.endpoint.clone(),
+            x_token: self.x_token.clone(),
+            request: Self::subscribe_request(&filter, encoding, self.rpc_client.commitment()),
+            silence_timeout,
+            backoff: self.backoff,
+            sender,
+            last_seen: HashMap::new(),
+            subscribed_once: false,
+            subscribed_at: None,
+            attempt: 0,
+        };
+        tokio::spawn(account_stream.run());
+
+        UnboundedReceiverStream::new(receiver)
+    }
+}
+
+/// Task behind a `GeyserAccountUpdates` stream, ends when the stream is dropped
+struct AccountStream {
+    endpoint: String,
+    x_token: Option<String>,
+    request: SubscribeRequest,
+    silence_timeout: Option<Duration>,
+    backoff: Backoff,
+    sender: UnboundedSender<Notification<(Pubkey, AccountUpdate)>>,
+    /// Slot and write version of the last update of each account
+    last_seen: HashMap<Pubkey, (u64, u64)>,
+    subscribed_once: bool,
+    /// When the current subscription was established
+    subscribed_at: Option<Instant>,
+    /// Drops and failed attempts since the last stable subscription
+    attempt: u32,
+}
+
+impl AccountStream {
+    async fn run(mut self) {
+        while !self.sender.is_closed() {
+            match self.stream_updates().await {
+                Ok(()) => return,
+                Err(e) => {
+                    if self
+                        .subscribed_at
+                        .take()
+                        .is_some_and(|at| at.elapsed() >= STABLE_CONNECTION)
+                    {
+                        self.attempt = 0;
+                    }
+
+                    let delay = self.backoff.delay(self.attempt);
+                    println!(
+                        "Geyser stream {} failed: {}, reconnecting in {}ms",
+                        self.endpoint,
+                        e,
+                        delay.as_millis()
+                    );
+
+                    self.attempt = self.attempt.saturating_add(1);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    /// Streams updates until the subscription fails, `Ok` once the receiver is dropped.
+    /// The gap of a reconnect is signaled once the new subscription is established
+    async fn stream_updates(&mut self) -> Result<(), String> {
+        let mut client = GeyserAccountSource::connect(&self.endpoint, &self.x_token).await?;
+
+        // the request side stays open, servers may end subscriptions whose requests ended
+        let requests = stream::iter([self.request.clone()]).chain(stream::pending());
+        let mut updates = client
+            .subscribe(requests)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_inner();
+
+        self.subscribed_at = Some(Instant::now());
+        if self.subscribed_once && self.sender.send(Notification::Gap).is_err() {
+            return Ok(());
+        }
+        self.subscribed_once = true;
+
+        loop {
+            let message = tokio::select! {
+                _ = self.sender.closed() => return Ok(()),
+                message = next_update(&mut updates, self.silence_timeout) => message?,
+            };
+
+            let Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(info),
+                slot,
+                ..
+            })) = message.update_oneof
+            else {
+                // pings and other updates only count for the silence timeout
+                continue;
+            };
+
+            let (Ok(pubkey), Ok(owner)) = (
+                Pubkey::try_from(info.pubkey.as_slice()),
+                Pubkey::try_from(info.owner.as_slice()),
+            ) else {
+                println!("Invalid pubkey in geyser account update");
+                continue;
+            };
+
+            let version = (slot, info.write_version);
+            if self
+                .last_seen
+                .get(&pubkey)
+                .is_some_and(|seen| seen >= &version)
+            {
+                continue;
+            }
+            self.last_seen.insert(pubkey, version);
+
+            let account = Account {
+                lamports: info.lamports,
+                data: info.data,
+                owner,
+                executable: info.executable,
+                rent_epoch: info.rent_epoch,
+            };
+
+            if self
+                .sender
+                .send(Notification::Value((
+                    pubkey,
+                    AccountUpdate { slot, account },
+                )))
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn next_update(
+    updates: &mut Streaming<SubscribeUpdate>,
+    silence_timeout: Option<Duration>,
+) -> Result<SubscribeUpdate, String> {
+    let message = match silence_timeout {
+        Some(silence_timeout) => timeout(silence_timeout, updates.message())
+            .await
+            .map_err(|_| "Silence timeout".to_string())?,
+        None => updates.message().await,
+    };
+
+    message
+        .map_err(|e| e.to_string())?
+        .ok_or("Stream ended".to_string())
+}
+
+#[async_trait]
+impl AccountSource for GeyserAccountSource {
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+        encoding: &AccountEncoding,
+        min_context_slot: Option<u64>,
+    ) -> Result<(u64, Vec<Option<Account>>), Error> {
+        rpc::get_multiple_accounts(&self.rpc_client, addresses, encoding, min_context_slot).await
+    }
+
+    async fn subscribe(
+        &self,
+        address: Pubkey,
+        encoding: &AccountEncoding,
+        silence_timeout: Option<Duration>,
+    ) -> Result<AccountUpdates, Error> {
+        let filter = GeyserAccountFilter {
+            accounts: vec![address],
+            ..Default::default()
+        };
+
+        let updates = self
+            .subscribe_accounts(filter, encoding, silence_timeout)
+            .map(|notification| match notification {
+                Notification::Value((_, update)) => Notification::Value(update),
+                Notification::Gap => Notification::Gap,
+            });
+
+        Ok(updates.boxed())
+    }
+
+    /// Single Geyser subscription fanned out by account, a gap of the subscription is
+    /// a gap of every account. Accounts with different data slices are subscribed on
+    /// their own, the data slice applies to the whole subscription
+    async fn subscribe_many(
+        &self,
+        subscriptions: &[AccountSubscription],
+    ) -> Result<TaggedAccountUpdates, Error> {
+        let Some(first) = subscriptions.first() else {
+            return Ok(stream::empty().boxed());
+        };
+        if subscriptions
+            .iter()
+            .any(|subscription| subscription.encoding.data_slice != first.encoding.data_slice)
+        {
+            return subscribe_each(self, subscriptions).await;
+        }
+
+        let filter = GeyserAccountFilter {
+            accounts: subscriptions.iter().map(|s| s.address).collect(),
+            ..Default::default()
+        };
+        // accounts share the stream, the shortest silence applies to all of them
+        let silence_timeout = subscriptions.iter().filter_map(|s| s.silence_timeout).min();
+        let addresses = filter.accounts.clone();
+
+        let updates = self
+            .subscribe_accounts(filter, &first.encoding, silence_timeout)
+            .flat_map(move |notification| match notification {
+                Notification::Value((address, update)) => {
+                    stream::iter(vec![(address, Notification::Value(update))])
+                }
+                Notification::Gap => stream::iter(
+                    addresses
+                        .iter()
+                        .map(|address| (*address, Notification::Gap))
+                        .collect(),
+                ),
+            });
+
+        Ok(updates.boxed())
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::HashMap, fs, path::Path, str::FromStr, time::Duration};
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use solana_account_decoder::UiDataSliceConfig;
+use solana_rpc_client_api::response::RpcKeyedAccount;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+use super::{AccountSource, AccountUpdates};
+use crate::{error::Error, utils::deser::AccountEncoding};
+
+/// Serves a directory of `solana account <address> --output json` dumps as a static
+/// snapshot, accounts never update
+pub struct LocalAccountSource {
+    slot: u64,
+    accounts: HashMap<Pubkey, Account>,
+}
+
+impl LocalAccountSource {
+    /// Loads every `.json` file of `dir`, accounts are keyed by the dumped pubkey
+    pub fn load(dir: &Path) -> Result<Self, Error> {
+        let entries = fs::read_dir(dir).map_err(|e| {
+            println!("Unable to read accounts directory {}: {}", dir.display(), e);
+            Error::UnableToLoadAccountDump
+        })?;
+
+        let mut accounts = HashMap::new();
+
+        for entry in entries {
+            let path = entry.map_err(|_| Error::UnableToLoadAccountDump)?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+
+            let (pubkey, account) = Self::load_dump(&path).map_err(|e| {
+                println!("Unable to load account dump {}", path.display());
+                e
+            })?;
+            accounts.insert(pubkey, account);
+        }
+
+        println!("Loaded {} accounts from {}", accounts.len(), dir.display());
+
+        Ok(Self { slot: 0, accounts })
+    }
+
+    fn load_dump(path: &Path) -> Result<(Pubkey, Account), Error> {
+        let dump = fs::read_to_string(path).map_err(|_| Error::UnableToLoadAccountDump)?;
+        let keyed: RpcKeyedAccount =
+            serde_json::from_str(&dump).map_err(|_| Error::UnableToLoadAccountDump)?;
+
+        let pubkey = Pubkey::from_str(&keyed.pubkey).map_err(|_| Error::UnableToDecode)?;
+        let account = keyed.account.decode().ok_or(Error::UnableToDecode)?;
+
+        Ok((pubkey, account))
+    }
+
+    /// Serves raw account data, e.g. the accounts first recorded in an account log
+    pub fn from_data(accounts: HashMap<Pubkey, Vec<u8>>) -> Self {
+        let accounts = accounts
+            .into_iter()
+            .map(|(pubkey, data)| {
+                (
+                    pubkey,
+                    Account {
+                        data,
+                        ..Account::default()
+                    },
+                )
+            })
+            .collect();
+
+        Self { slot: 0, accounts }
+    }
+
+    /// Slot reported for every account, the dumps do not record it
+    pub fn with_slot(mut self, slot: u64) -> Self {
+        self.slot = slot;
+        self
+    }
+}
+
+fn slice_data(data: &[u8], data_slice: &UiDataSliceConfig) -> Vec<u8> {
+    let start = data_slice.offset.min(data.len());
+    let end = start.saturating_add(data_slice.length).min(data.len());
+    data[start..end].to_vec()
+}
+
+#[async_trait]
+impl AccountSource for LocalAccountSource {
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+        encoding: &AccountEncoding,
+        min_context_slot: Option<u64>,
+    ) -> Result<(u64, Vec<Option<Account>>), Error> {
+        if min_context_slot.is_some_and(|min_context_slot| self.slot < min_context_slot) {
+            return Err(Error::MinContextSlotNotReached);
+        }
+
+        let accounts = addresses
+            .iter()
+            .map(|address| {
+                let mut account = self.accounts.get(address)?.clone();
+                if let Some(data_slice) = &encoding.data_slice {
+                    account.data = slice_data(&account.data, data_slice);
+                }
+                Some(account)
+            })
+            .collect();
+
+        Ok((self.slot, accounts))
+    }
+
+    async fn subscribe(
+        &self,
+        _address: Pubkey,
+        _encoding: &AccountEncoding,
+        _silence_timeout: Option<Duration>,
+    ) -> Result<AccountUpdates, Error> {
+        Ok(stream::pending().boxed())
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::{
+    stream::{select_all, BoxStream},
+    StreamExt,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+use crate::{
+    error::Error,
+    utils::{deser::AccountEncoding, websocket_client::Notification},
+};
+
+pub mod batch;
+pub mod geyser;
+pub mod local;
+pub mod rpc;
+pub mod websocket;
+
+pub use batch::BatchedFetch;
+pub use geyser::GeyserAccountSource;
+pub use local::LocalAccountSource;
+pub use rpc::RpcAccountSource;
+pub use websocket::WebsocketAccountSource;
+
+/// Account and the slot it was observed at
+#[derive(Clone, Debug)]
+pub struct AccountUpdate {
+    pub slot: u64,
+    pub account: Account,
+}
+
+pub type AccountUpdates = BoxStream<'static, Notification<AccountUpdate>>;
+
+/// Updates of several accounts, each tagged with the address it belongs to
+pub type TaggedAccountUpdates = BoxStream<'static, (Pubkey, Notification<AccountUpdate>)>;
+
+/// Account subscription of a `subscribe_many` call
+#[derive(Clone, Debug)]
+pub struct AccountSubscription {
+    pub address: Pubkey,
+    pub encoding: AccountEncoding,
+    pub silence_timeout: Option<Duration>,
+}
+
+/// Subscribes to each account on its own and merges the streams
+pub async fn subscribe_each(
+    source: &(impl AccountSource + ?Sized),
+    subscriptions: &[AccountSubscription],
+) -> Result<TaggedAccountUpdates, Error> {
+    let mut streams = vec![];
+
+    for subscription in subscriptions {
+        let address = subscription.address;
+        let stream = source
+            .subscribe(
+                address,
+                &subscription.encoding,
+                subscription.silence_timeout,
+            )
+            .await?;
+
+        streams.push(
+            stream
+                .map(move |notification| (address, notification))
+                .boxed(),
+        );
+    }
+
+    Ok(select_all(streams).boxed())
+}
+
+/// Where `State` reads accounts from, a live cluster or captured account dumps
+#[async_trait]
+pub trait AccountSource: Send + Sync {
+    /// Accounts in the order of `addresses` and the slot they were read at, which is
+    /// never older than `min_context_slot`
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+        encoding: &AccountEncoding,
+        min_context_slot: Option<u64>,
+    ) -> Result<(u64, Vec<Option<Account>>), Error>;
+
+    /// Updates of the account, `Notification::Gap` when updates may have been missed
+    /// and the account should be fetched again. `silence_timeout` only applies to
+    /// sources with server side subscriptions
+    async fn subscribe(
+        &self,
+        address: Pubkey,
+        encoding: &AccountEncoding,
+        silence_timeout: Option<Duration>,
+    ) -> Result<AccountUpdates, Error>;
+
+    /// Updates of all the subscribed accounts in a single stream. Sources able to
+    /// filter several accounts server side share one subscription between them
+    async fn subscribe_many(
+        &self,
+        subscriptions: &[AccountSubscription],
+    ) -> Result<TaggedAccountUpdates, Error> {
+        subscribe_each(self, subscriptions).await
+    }
+}
@@ -0,0 +1,101 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::time::sleep;
+
+use super::{AccountSource, AccountUpdate, AccountUpdates};
+use crate::{
+    error::Error,
+    utils::{deser::AccountEncoding, websocket_client::Notification},
+};
+
+pub(crate) async fn get_multiple_accounts(
+    rpc_client: &RpcClient,
+    addresses: &[Pubkey],
+    encoding: &AccountEncoding,
+    min_context_slot: Option<u64>,
+) -> Result<(u64, Vec<Option<Account>>), Error> {
+    let config = RpcAccountInfoConfig {
+        min_context_slot,
+        ..encoding.account_info_config(rpc_client.commitment())
+    };
+    let response = rpc_client
+        .get_multiple_accounts_with_config(addresses, config)
+        .await?;
+
+    Ok((response.context.slot, response.value))
+}
+
+/// Polls accounts over RPC, for endpoints without websocket support
+pub struct RpcAccountSource {
+    rpc_client: Arc<RpcClient>,
+    poll_interval: Duration,
+}
+
+impl RpcAccountSource {
+    pub fn new(rpc_client: Arc<RpcClient>, poll_interval: Duration) -> Self {
+        Self {
+            rpc_client,
+            poll_interval,
+        }
+    }
+}
+
+#[async_trait]
+impl AccountSource for RpcAccountSource {
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+        encoding: &AccountEncoding,
+        min_context_slot: Option<u64>,
+    ) -> Result<(u64, Vec<Option<Account>>), Error> {
+        get_multiple_accounts(&self.rpc_client, addresses, encoding, min_context_slot).await
+    }
+
+    /// Emits the account whenever its data changed since the previous poll
+    async fn subscribe(
+        &self,
+        address: Pubkey,
+        encoding: &AccountEncoding,
+        _silence_timeout: Option<Duration>,
+    ) -> Result<AccountUpdates, Error> {
+        let rpc_client = self.rpc_client.clone();
+        let poll_interval = self.poll_interval;
+        let config = encoding.account_info_config(rpc_client.commitment());
+
+        let updates = stream::unfold(None::<Account>, move |last| {
+            let rpc_client = rpc_client.clone();
+            let config = config.clone();
+
+            async move {
+                loop {
+                    sleep(poll_interval).await;
+
+                    match rpc_client
+                        .get_account_with_config(&address, config.clone())
+                        .await
+                    {
+                        Ok(response) => match response.value {
+                            Some(account) if last.as_ref() != Some(&account) => {
+                                let update = AccountUpdate {
+                                    slot: response.context.slot,
+                                    account: account.clone(),
+                                };
+                                return Some((Notification::Value(update), Some(account)));
+                            }
+                            _ => (),
+                        },
+                        Err(e) => {
+                            println!("Unable to poll account {}: {}", address, e.to_string());
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(updates.boxed())
+    }
+}
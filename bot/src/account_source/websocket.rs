@@ -0,0 +1,88 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::time::sleep;
+
+use super::{rpc, AccountSource, AccountUpdate, AccountUpdates};
+use crate::{
+    error::Error,
+    utils::{
+        deser::AccountEncoding,
+        websocket_client::{Notification, WebsocketClient, WebsocketError},
+    },
+};
+
+/// Delay between subscription attempts while the websocket is not connected yet
+const SUBSCRIBE_RETRY_MILLIS: u64 = 500;
+
+/// Fetches accounts over RPC, updates are pushed by websocket account subscriptions
+pub struct WebsocketAccountSource {
+    rpc_client: Arc<RpcClient>,
+    ws_client: Arc<WebsocketClient>,
+}
+
+impl WebsocketAccountSource {
+    pub fn new(rpc_client: Arc<RpcClient>, ws_client: Arc<WebsocketClient>) -> Self {
+        Self {
+            rpc_client,
+            ws_client,
+        }
+    }
+}
+
+#[async_trait]
+impl AccountSource for WebsocketAccountSource {
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+        encoding: &AccountEncoding,
+        min_context_slot: Option<u64>,
+    ) -> Result<(u64, Vec<Option<Account>>), Error> {
+        rpc::get_multiple_accounts(&self.rpc_client, addresses, encoding, min_context_slot).await
+    }
+
+    async fn subscribe(
+        &self,
+        address: Pubkey,
+        encoding: &AccountEncoding,
+        silence_timeout: Option<Duration>,
+    ) -> Result<AccountUpdates, Error> {
+        let config = encoding.account_info_config(self.rpc_client.commitment());
+
+        let (_, stream) = loop {
+            match self
+                .ws_client
+                .account_subscribe(address, config.clone(), silence_timeout)
+                .await
+            {
+                Err(
+                    WebsocketError::NotConnected
+                    | WebsocketError::ConnectionLost
+                    | WebsocketError::RequestTimeout,
+                ) => sleep(Duration::from_millis(SUBSCRIBE_RETRY_MILLIS)).await,
+                res => break res?,
+            }
+        };
+
+        let updates = stream.filter_map(move |notification| async move {
+            match notification {
+                Notification::Value(response) => match response.value.decode::<Account>() {
+                    Some(account) => Some(Notification::Value(AccountUpdate {
+                        slot: response.context.slot,
+                        account,
+                    })),
+                    None => {
+                        println!("Unable to decode account notification {}", address);
+                        None
+                    }
+                },
+                Notification::Gap => Some(Notification::Gap),
+            }
+        });
+
+        Ok(updates.boxed())
+    }
+}
@@ -1,5 +1,5 @@
 use drift::accounts::PerpMarket as DriftPerpMarket;
-use funding_program::state::Exchange;
+use funding_program::state::{Exchange, FundingHistoryLoader};
 use mango::{accounts::PerpMarket as MangoPerpMarket, types::Side};
 use solana_sdk::pubkey::Pubkey;
 
@@ -7,6 +7,7 @@ use crate::constants;
 
 pub struct FundingAccountMeta {
     pub address: Pubkey,
+    pub history_address: Pubkey,
     pub market_index: u16,
     pub market: Pubkey,
     pub exchange: Exchange,
@@ -87,6 +88,7 @@ impl StaticAddresses {
             .0;
             self.funding_accounts.push(FundingAccountMeta {
                 address: funding_account,
+                history_address: FundingHistoryLoader::pda(&funding_account).0,
                 market_index: market.perp_market_index,
                 market: *market_address,
                 exchange,
@@ -108,6 +110,7 @@ impl StaticAddresses {
             .0;
             self.funding_accounts.push(FundingAccountMeta {
                 address: funding_account,
+                history_address: FundingHistoryLoader::pda(&funding_account).0,
                 market_index: market.market_index,
                 market: *market_address,
                 exchange,
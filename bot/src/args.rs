@@ -1,13 +1,33 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
-use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use clap::{Parser, Subcommand, ValueEnum};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::{Signer, SignerError},
+};
+
+use crate::state::DEFAULT_SNAPSHOT_SLOT_WINDOW;
 
 pub struct Wallet {
     pub keypair: Keypair,
     pub pubkey: Pubkey,
 }
 
+impl Signer for Wallet {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        self.keypair.try_sign_message(message)
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
 const NAMESPACE: &'static str = "[DOTENV_ERROR]:";
 
 pub fn load_arg(key: &str) -> String {
@@ -68,6 +88,18 @@ pub fn parse_drift_markets_into_ids(markets: &Vec<String>) -> Result<Vec<u16>, P
         .collect()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AccountSourceKind {
+    /// RPC fetches and websocket account subscriptions
+    Websocket,
+    /// RPC fetches and polling
+    Rpc,
+    /// RPC fetches and Yellowstone Geyser gRPC account streams, see `--geyser-url`
+    Geyser,
+    /// `solana account --output json` dumps, see `--accounts-dir`
+    Local,
+}
+
 #[derive(Debug, Parser)]
 pub struct CliArgs {
     #[command(subcommand)]
@@ -79,10 +111,45 @@ pub enum Commands {
     FindFundingAccounts {
         #[arg(long)]
         output_dir: PathBuf,
+        /// Defaults to the wallet authority
+        #[arg(long)]
+        authority: Option<Pubkey>,
     },
 
     FundingClient {
         markets: Vec<String>,
+        #[arg(long, value_enum, default_value_t = AccountSourceKind::Websocket)]
+        account_source: AccountSourceKind,
+        /// Directory of account dumps for the local account source
+        #[arg(long, required_if_eq("account_source", "local"))]
+        accounts_dir: Option<PathBuf>,
+        /// Geyser gRPC endpoint, the `GEYSER_X_TOKEN` env var is sent as `x-token`
+        #[arg(long, required_if_eq("account_source", "geyser"))]
+        geyser_url: Option<String>,
+        /// Poll interval of the rpc account source
+        #[arg(long, default_value_t = 1000)]
+        poll_interval_ms: u64,
+        /// Writes ingested accounts and relayer decisions to this log, replacing any
+        /// previous one, see `Replay`
+        #[arg(long)]
+        record: Option<PathBuf>,
+        /// Funding snapshots whose accounts were read further apart are rejected
+        #[arg(long, default_value_t = DEFAULT_SNAPSHOT_SLOT_WINDOW)]
+        snapshot_slot_window: u64,
+        /// Logs due funding updates instead of initializing accounts and sending
+        /// transactions. Implied by the local account source, which then runs offline
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Replays a log written by `FundingClient --record` and compares the
+    /// published funding rates with the replayed ones
+    Replay {
+        log: PathBuf,
+        markets: Vec<String>,
+        /// Should match the window of the recorded run
+        #[arg(long, default_value_t = DEFAULT_SNAPSHOT_SLOT_WINDOW)]
+        snapshot_slot_window: u64,
     },
 
     Bot {
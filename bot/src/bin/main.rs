@@ -1,20 +1,41 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    future::pending,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use bot::{
+    account_source::{
+        AccountSource, GeyserAccountSource, LocalAccountSource, RpcAccountSource,
+        WebsocketAccountSource,
+    },
     addresses::StaticAddresses,
-    args::{self, CliArgs, Commands, Wallet},
+    args::{self, AccountSourceKind, CliArgs, Commands, Wallet},
     error::Error,
-    services::funding_relayer::{initialize_funding_accounts_if_needed, start_funding_relayer},
+    services::{
+        cluster_clock::ClusterClock,
+        funding_relayer::{
+            initialize_funding_accounts_if_needed, replay_funding, start_funding_relayer,
+            RelayerConfig,
+        },
+    },
     state::{fetch_markets, State},
-    utils::websocket_client::{create_persisted_websocket_connection, WebsocketClient},
+    utils::{
+        account_log::{first_recorded_accounts, read_log, Recorder},
+        deser::AccountEncoding,
+        websocket_client::{
+            create_persisted_websocket_connection, WebsocketClient, WebsocketError,
+        },
+    },
 };
 use clap::Parser;
 use drift::accounts::PerpMarket as DriftPerpMarket;
-use funding_program::{client::state::load_funding_account, state::Exchange};
+use funding_program::{client::rpc::FundingProgramClient, state::Exchange};
 use mango::accounts::PerpMarket as MangoPerpMarket;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    account::from_account, clock::Clock, commitment_config::CommitmentConfig, signature::Keypair,
+    signer::Signer, sysvar,
 };
 use tokio::{
     fs::{self, remove_file, File},
@@ -32,7 +53,18 @@ async fn main() -> Result<(), Error> {
             CommitmentConfig::confirmed(),
         )))
     });
-    let ws_client = args::load_and_parse("WS_URL", |url| Ok(Arc::new(WebsocketClient::new(url))));
+    // comma separated, endpoints after the first one are failovers
+    let ws_client = args::load_and_parse("WS_URL", |urls| {
+        let urls = urls
+            .split(",")
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect::<Vec<String>>();
+        if urls.is_empty() {
+            return Err("No websocket url".to_string());
+        }
+        Ok(Arc::new(WebsocketClient::new(urls)))
+    });
     let wallet = args::load_and_parse("PRIVATE_KEY", |pk_str| {
         let bytes = pk_str
             .split(",")
@@ -49,16 +81,19 @@ async fn main() -> Result<(), Error> {
 async fn start(
     cli_args: CliArgs,
     rpc_client: Arc<RpcClient>,
-    _ws_client: Arc<WebsocketClient>,
+    ws_client: Arc<WebsocketClient>,
     wallet: Arc<Wallet>,
 ) -> Result<(), Error> {
     match cli_args.commands {
-        // outputs funding accounts to a file
+        // outputs funding accounts of the authority registry to a file
         //
         // authority: #authority
         // ------------------------------------
         // #address : #id #market_index #exchange (#exchange_discriminant)
-        Commands::FindFundingAccounts { output_dir } => {
+        Commands::FindFundingAccounts {
+            output_dir,
+            authority,
+        } => {
             let filename = "funding_accounts.txt";
             let mut file_path = output_dir;
             file_path.push(filename);
@@ -77,46 +112,31 @@ async fn start(
                 })?
             };
 
-            let funding_accounts = rpc_client
-                .get_program_accounts(&funding_program::id())
-                .await?;
-            let mut accounts_by_authority: HashMap<Pubkey, String> = HashMap::new();
-
-            for (address, ai) in funding_accounts.iter() {
-                let account = load_funding_account(&ai.data).map_err(|_| {
-                    println!("Invalid funding account");
-                    Error::UnableToDeserialize
-                })?;
+            let authority = authority.unwrap_or(wallet.pubkey);
+            let funding_client = FundingProgramClient::new(rpc_client.clone(), wallet.clone());
+            let registry = funding_client.fetch_registry(&authority).await?;
 
-                let exchange = match account.exchange {
+            let mut meta = String::new();
+            for entry in registry.entries.iter() {
+                let exchange = match entry.exchange {
                     Exchange::Drift => "drift (0)",
                     Exchange::Mango => "mango (1)",
                 };
-                let meta = format!(
+                meta.push_str(&format!(
                     "{}: {} {} {}\n",
-                    address.to_string(),
-                    account.id,
-                    account.market_index,
+                    entry.address.to_string(),
+                    entry.id,
+                    entry.market_index,
                     exchange
-                );
-                match accounts_by_authority.get_mut(&account.authority) {
-                    Some(accounts) => {
-                        accounts.push_str(&meta);
-                    }
-                    None => {
-                        accounts_by_authority.insert(account.authority, meta);
-                    }
-                }
+                ));
             }
 
             let mut output =
                 "<account_address>: <id> <market_index> <exchange> (<exchange_discriminant>)\n"
                     .to_string();
-            accounts_by_authority.iter().for_each(|(authority, meta)| {
-                output.push_str(&format!("\nAuthority: {}\n", authority.to_string()));
-                output.push_str("--------------------------------\n");
-                output.push_str(&format!("{meta}\n"));
-            });
+            output.push_str(&format!("\nAuthority: {}\n", authority.to_string()));
+            output.push_str("--------------------------------\n");
+            output.push_str(&format!("{meta}\n"));
 
             match out_file.write_all(output.as_bytes()).await {
                 Ok(_) => {
@@ -125,7 +145,7 @@ async fn start(
                         Error::UnableToSaveOutputFile
                     })?;
 
-                    println!("Found {} funding accounts", funding_accounts.len());
+                    println!("Found {} funding accounts", registry.entries.len());
                 }
                 Err(e) => {
                     println!("Could not save funding accounts: {}", e.to_string());
@@ -133,7 +153,43 @@ async fn start(
                 }
             }
         }
-        Commands::FundingClient { markets } => {
+        Commands::FundingClient {
+            markets,
+            account_source,
+            accounts_dir,
+            geyser_url,
+            poll_interval_ms,
+            record,
+            snapshot_slot_window,
+            dry_run,
+        } => {
+            // the local source runs offline, nothing may reach the cluster
+            let offline = account_source == AccountSourceKind::Local;
+            let dry_run = dry_run || offline;
+
+            let source: Arc<dyn AccountSource> = match account_source {
+                AccountSourceKind::Websocket => Arc::new(WebsocketAccountSource::new(
+                    rpc_client.clone(),
+                    ws_client.clone(),
+                )),
+                AccountSourceKind::Rpc => Arc::new(RpcAccountSource::new(
+                    rpc_client.clone(),
+                    Duration::from_millis(poll_interval_ms),
+                )),
+                // required by the cli for the geyser source
+                AccountSourceKind::Geyser => {
+                    let source = GeyserAccountSource::new(rpc_client.clone(), geyser_url.unwrap());
+                    match std::env::var("GEYSER_X_TOKEN") {
+                        Ok(x_token) => Arc::new(source.with_x_token(x_token)),
+                        Err(_) => Arc::new(source),
+                    }
+                }
+                // required by the cli for the local source
+                AccountSourceKind::Local => {
+                    Arc::new(LocalAccountSource::load(&accounts_dir.unwrap())?)
+                }
+            };
+
             let mango_markets_addresses = StaticAddresses::get_mango_markets_from_ids(
                 &args::parse_mango_markets_into_ids(&markets)?,
             );
@@ -144,29 +200,64 @@ async fn start(
             let mut static_addresses = StaticAddresses::new();
 
             let mango_markets =
-                fetch_markets::<MangoPerpMarket>(&rpc_client, &mango_markets_addresses).await?;
+                fetch_markets::<MangoPerpMarket>(source.as_ref(), &mango_markets_addresses).await?;
             let drift_markets =
-                fetch_markets::<DriftPerpMarket>(&rpc_client, &drift_markets_addresses).await?;
+                fetch_markets::<DriftPerpMarket>(source.as_ref(), &drift_markets_addresses).await?;
 
             static_addresses.set_mango_markets(&mango_markets);
             static_addresses.set_drift_markets(&drift_markets);
 
-            initialize_funding_accounts_if_needed(
-                &rpc_client,
-                &wallet,
-                &static_addresses.funding_accounts,
-            )
-            .await?;
+            if !dry_run {
+                initialize_funding_accounts_if_needed(
+                    &rpc_client,
+                    &wallet,
+                    &static_addresses.funding_accounts,
+                )
+                .await?;
+            }
 
-            let state = State::new(rpc_client.clone(), static_addresses);
+            let mut state = State::new(source, static_addresses)
+                .with_snapshot_slot_window(snapshot_slot_window);
+            if let Some(path) = record {
+                state = state.with_recorder(Arc::new(Recorder::create(&path)?));
+            }
+            let state = Arc::new(state);
 
-            *state.mango_markets.write().await = mango_markets;
-            *state.drift_markets.write().await = drift_markets;
+            let websocket_handle = match account_source {
+                AccountSourceKind::Websocket => {
+                    create_persisted_websocket_connection(ws_client.clone()).await?
+                }
+                // nothing else uses the websocket
+                _ => tokio::spawn(pending::<Result<(), WebsocketError>>()),
+            };
+            let subscriptions_handle = State::subscribe_to_accounts(state.clone());
 
-            let (relayer_cache_handle, relayer_handle) =
-                start_funding_relayer(rpc_client, wallet, Arc::new(state)).await?;
+            let clock = if offline {
+                Arc::new(offline_clock(state.source()).await)
+            } else {
+                Arc::new(ClusterClock::new(rpc_client.clone()).await?)
+            };
+            let clock_handle = ClusterClock::start(clock.clone());
+
+            let (relayer_cache_handle, relayer_handle) = start_funding_relayer(
+                rpc_client,
+                wallet,
+                state,
+                clock,
+                RelayerConfig::default().with_dry_run(dry_run),
+            )
+            .await?;
 
             let program_result = tokio::select! {
+                websocket_res = websocket_handle => {
+                    websocket_res.map(|r| r.map_err(|e| e.into()))
+                }
+                subscriptions_res = subscriptions_handle => {
+                    subscriptions_res
+                }
+                clock_res = clock_handle => {
+                    clock_res
+                }
                 relayer_cache_res = relayer_cache_handle => {
                     relayer_cache_res
                 }
@@ -186,6 +277,65 @@ async fn start(
 
             return Err(Error::ServiceShutdownUnexpectedly);
         }
+        Commands::Replay {
+            log,
+            markets,
+            snapshot_slot_window,
+        } => {
+            let records = read_log(&log)?;
+            println!("Replaying {} records of {}", records.len(), log.display());
+
+            let source: Arc<dyn AccountSource> = Arc::new(LocalAccountSource::from_data(
+                first_recorded_accounts(&records)?,
+            ));
+
+            let mango_markets_addresses = StaticAddresses::get_mango_markets_from_ids(
+                &args::parse_mango_markets_into_ids(&markets)?,
+            );
+            let drift_markets_addresses = StaticAddresses::get_drift_markets_from_ids(
+                &args::parse_drift_markets_into_ids(&markets)?,
+            );
+
+            let mut static_addresses = StaticAddresses::new();
+
+            let mango_markets =
+                fetch_markets::<MangoPerpMarket>(source.as_ref(), &mango_markets_addresses).await?;
+            let drift_markets =
+                fetch_markets::<DriftPerpMarket>(source.as_ref(), &drift_markets_addresses).await?;
+
+            static_addresses.set_mango_markets(&mango_markets);
+            static_addresses.set_drift_markets(&drift_markets);
+
+            let state = Arc::new(
+                State::new(source, static_addresses)
+                    .with_snapshot_slot_window(snapshot_slot_window),
+            );
+            State::fetch_all(&state).await?;
+
+            let publishes = replay_funding(&state, &records).await?;
+            let mismatches = publishes.iter().filter(|p| !p.matches()).count();
+
+            for publish in publishes.iter() {
+                println!(
+                    "{} {} - recorded: {} (confidence: {:?}), replayed: {}",
+                    if publish.matches() { "OK  " } else { "DIFF" },
+                    publish.funding_account,
+                    publish.recorded.0,
+                    publish.recorded.1,
+                    match publish.replayed {
+                        Some((funding_rate, confidence)) =>
+                            format!("{} (confidence: {:?})", funding_rate, confidence),
+                        None => "not due".to_string(),
+                    },
+                );
+            }
+
+            println!(
+                "Replayed {} publishes, {} mismatches",
+                publishes.len(),
+                mismatches
+            );
+        }
         Commands::Bot { markets } => {
             // let websocket_handle = create_persisted_websocket_connection(ws_client.clone()).await?;
 
@@ -262,3 +412,29 @@ async fn start(
 
     Ok(())
 }
+
+/// Clock sysvar of the account source, the local time if it does not hold one
+async fn offline_clock(source: &dyn AccountSource) -> ClusterClock {
+    let clock = source
+        .get_multiple_accounts(&[sysvar::clock::id()], &AccountEncoding::default(), None)
+        .await
+        .ok()
+        .and_then(|(_, mut ais)| ais.pop().flatten())
+        .and_then(|ai| from_account::<Clock, _>(&ai));
+
+    match clock {
+        Some(clock) => ClusterClock::offline(clock),
+        None => {
+            println!("No clock sysvar in the account source, using the local time");
+            let unix_timestamp = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64;
+
+            ClusterClock::offline(Clock {
+                unix_timestamp,
+                ..Clock::default()
+            })
+        }
+    }
+}
@@ -1,4 +1,13 @@
-use solana_client::client_error::ClientError;
+use std::sync::Arc;
+
+use funding_program::client::rpc::FundingClientError;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_rpc_client_api::{
+    custom_error::{
+        JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    },
+    request::RpcError as RpcRequestError,
+};
 
 use crate::{
     args::ParseMarketsError,
@@ -20,20 +29,73 @@ pub enum Error {
     UnableToDecode,
     UnableToDeserialize,
     UnableToFetchAccount,
+    /// Error of the `getMultipleAccounts` chunk, shared by every account of the chunk
+    FetchChunkFailed(Arc<Error>),
+    MinContextSlotNotReached,
+    SnapshotSlotWindowExceeded,
+    UnableToLoadAccountDump,
+    UnableToWriteLog,
+    UnableToReadLog,
 
     InvalidOraclePriceData,
 
     TransactionError,
 
-    RpcError,
+    RpcError(RpcErrorKind),
     WebsocketClientError(WebsocketError),
     TransactionErrorClient(TransactionErrorClient),
     ParseMarketsError(ParseMarketsError),
+    FundingClientError(FundingClientError),
+}
+
+/// What an RPC request failed on, enough to tell errors worth retrying apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcErrorKind {
+    /// The request did not get an answer, ex: refused connection or timeout
+    Transport,
+    /// HTTP 5xx status
+    ServerError,
+    /// JSON-RPC error -32005, the node is behind
+    NodeUnhealthy,
+    /// JSON-RPC error -32016, the node did not reach `minContextSlot` yet
+    MinContextSlotNotReached,
+    /// Any other error, retrying the same request fails the same way
+    Other,
+}
+
+impl RpcErrorKind {
+    /// A later attempt, possibly on another node, can succeed
+    pub fn is_transient(self) -> bool {
+        !matches!(self, Self::Other)
+    }
+}
+
+impl From<&ClientError> for RpcErrorKind {
+    fn from(value: &ClientError) -> Self {
+        match value.kind() {
+            ClientErrorKind::Io(_) => Self::Transport,
+            ClientErrorKind::Reqwest(e) => match e.status() {
+                Some(status) if status.is_server_error() => Self::ServerError,
+                Some(_) => Self::Other,
+                None => Self::Transport,
+            },
+            ClientErrorKind::RpcError(RpcRequestError::RpcResponseError { code, .. }) => {
+                match *code {
+                    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY => Self::NodeUnhealthy,
+                    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED => {
+                        Self::MinContextSlotNotReached
+                    }
+                    _ => Self::Other,
+                }
+            }
+            _ => Self::Other,
+        }
+    }
 }
 
 impl From<ClientError> for Error {
-    fn from(_: ClientError) -> Self {
-        Self::RpcError
+    fn from(value: ClientError) -> Self {
+        Self::RpcError(RpcErrorKind::from(&value))
     }
 }
 
@@ -55,6 +117,12 @@ impl From<ParseMarketsError> for Error {
     }
 }
 
+impl From<FundingClientError> for Error {
+    fn from(value: FundingClientError) -> Self {
+        Self::FundingClientError(value)
+    }
+}
+
 impl ToString for Error {
     fn to_string(&self) -> String {
         match self {
@@ -68,12 +136,19 @@ impl ToString for Error {
             Self::UnableToDecode => "UnableToDecode".to_string(),
             Self::UnableToDeserialize => "UnableToDeserialize".to_string(),
             Self::UnableToFetchAccount => "UnableToFetchAccount".to_string(),
+            Self::FetchChunkFailed(e) => format!("FetchChunkFailed: {}", e.to_string()),
+            Self::MinContextSlotNotReached => "MinContextSlotNotReached".to_string(),
+            Self::SnapshotSlotWindowExceeded => "SnapshotSlotWindowExceeded".to_string(),
+            Self::UnableToLoadAccountDump => "UnableToLoadAccountDump".to_string(),
+            Self::UnableToWriteLog => "UnableToWriteLog".to_string(),
+            Self::UnableToReadLog => "UnableToReadLog".to_string(),
             Self::InvalidOraclePriceData => "InvalidOraclePriceData".to_string(),
             Self::TransactionError => "TransactionError".to_string(),
-            Self::RpcError => "RpcError".to_string(),
+            Self::RpcError(kind) => format!("RpcError: {:?}", kind),
             Self::WebsocketClientError(e) => format!("WebsocketClientError: {}", e.to_string()),
             Self::TransactionErrorClient(e) => format!("TransactionErrorClient: {}", e.to_string()),
             Self::ParseMarketsError(e) => format!("ParseMarketsError: {}", e.to_string()),
+            Self::FundingClientError(e) => format!("FundingClientError: {}", e.to_string()),
         }
     }
 }
@@ -1,3 +1,4 @@
+pub mod account_source;
 pub mod addresses;
 pub mod args;
 pub mod constants;
@@ -0,0 +1,123 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::from_account,
+    clock::{Clock, DEFAULT_MS_PER_SLOT},
+    sysvar,
+};
+use tokio::{sync::RwLock, task::JoinHandle, time::sleep};
+
+use crate::error::Error;
+
+const CLOCK_REFRESH_SECS: u64 = 10;
+/// Local clock drift logged as a warning above this
+const MAX_LOCAL_DRIFT_SECS: i64 = 5;
+
+#[derive(Clone, Copy, Debug)]
+struct ClockObservation {
+    slot: u64,
+    unix_timestamp: i64,
+    /// Cluster time minus local time when the clock sysvar was fetched
+    local_drift_secs: i64,
+    observed_at: Instant,
+}
+
+/// Tracks the cluster `Clock` sysvar so funding computations and staleness checks
+/// use the same time as the funding program instead of the local system time
+pub struct ClusterClock {
+    /// `None` for offline clocks, which are never refreshed
+    rpc_client: Option<Arc<RpcClient>>,
+    observation: RwLock<ClockObservation>,
+}
+
+impl ClusterClock {
+    pub async fn new(rpc_client: Arc<RpcClient>) -> Result<Self, Error> {
+        let observation = Self::observe(&rpc_client).await?;
+
+        Ok(Self {
+            rpc_client: Some(rpc_client),
+            observation: RwLock::new(observation),
+        })
+    }
+
+    /// Extrapolated from `clock` without ever reaching the cluster, ex: the clock
+    /// sysvar of an account dump
+    pub fn offline(clock: Clock) -> Self {
+        Self {
+            rpc_client: None,
+            observation: RwLock::new(Self::observation(&clock)),
+        }
+    }
+
+    async fn observe(rpc_client: &RpcClient) -> Result<ClockObservation, Error> {
+        let ai = rpc_client.get_account(&sysvar::clock::id()).await?;
+        let clock: Clock = from_account(&ai).ok_or(Error::UnableToDeserialize)?;
+
+        Ok(Self::observation(&clock))
+    }
+
+    fn observation(clock: &Clock) -> ClockObservation {
+        let local_ts = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        ClockObservation {
+            slot: clock.slot,
+            unix_timestamp: clock.unix_timestamp,
+            local_drift_secs: clock.unix_timestamp - local_ts,
+            observed_at: Instant::now(),
+        }
+    }
+
+    pub async fn refresh(&self) -> Result<(), Error> {
+        let Some(rpc_client) = &self.rpc_client else {
+            return Ok(());
+        };
+        let observation = Self::observe(rpc_client).await?;
+
+        if observation.local_drift_secs.abs() > MAX_LOCAL_DRIFT_SECS {
+            println!(
+                "Local clock is {}s off the cluster clock",
+                -observation.local_drift_secs
+            );
+        }
+
+        *self.observation.write().await = observation;
+        Ok(())
+    }
+
+    /// Cluster unix timestamp extrapolated from the last observed clock sysvar
+    pub async fn now_ts(&self) -> i64 {
+        let observation = self.observation.read().await;
+        observation.unix_timestamp + observation.observed_at.elapsed().as_secs() as i64
+    }
+
+    /// Slot extrapolated from the last observed clock sysvar
+    pub async fn estimated_slot(&self) -> u64 {
+        let observation = self.observation.read().await;
+        observation.slot
+            + observation.observed_at.elapsed().as_millis() as u64 / DEFAULT_MS_PER_SLOT
+    }
+
+    /// Cluster time minus local time in seconds
+    pub async fn local_drift_secs(&self) -> i64 {
+        self.observation.read().await.local_drift_secs
+    }
+
+    pub fn start(clock: Arc<ClusterClock>) -> JoinHandle<Result<(), Error>> {
+        tokio::spawn(async move {
+            loop {
+                sleep(Duration::from_secs(CLOCK_REFRESH_SECS)).await;
+
+                if let Err(e) = clock.refresh().await {
+                    println!("Unable to refresh cluster clock: {}", e.to_string());
+                }
+            }
+        })
+    }
+}
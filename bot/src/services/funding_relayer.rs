@@ -1,31 +1,38 @@
-use std::{
-    sync::Arc,
-    time::{Duration, Instant, SystemTime},
-};
+use std::{sync::Arc, time::Duration};
 
 use funding_program::{
     client::{
         instructions::{
-            initialize_funding_account, update_funding_account, InitializeFundingAccountAccounts,
-            UpdateFundingAccountAccounts,
+            initialize_funding_account, initialize_funding_history, register_funding_account,
+            update_funding_data, InitializeFundingAccountAccounts,
+            InitializeFundingHistoryAccounts, RegisterFundingAccountAccounts,
+            UpdateFundingDataAccounts,
         },
-        state::load_funding_account,
+        rpc::decode_transaction_error,
+        state::{load_funding_account, load_funding_registry},
     },
-    state::Exchange,
+    state::{Exchange, FundingRegistryLoader},
 };
+use funding_rate::{Apr, FundingRate};
 use futures_util::lock::Mutex;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
 use tokio::{task::JoinHandle, time::sleep};
 
 use crate::{
+    account_source::{AccountUpdate, BatchedFetch, RpcAccountSource},
     addresses::FundingAccountMeta,
     args::Wallet,
     error::Error,
+    services::cluster_clock::ClusterClock,
     state::State,
-    utils::transaction::{
-        build_signed_transaction, force_send_transaction, send_and_confirm_transaction,
-        TransactionResult,
+    utils::{
+        account_log::{first_recorded_accounts, LogRecord},
+        deser::AccountEncoding,
+        transaction::{
+            build_signed_transaction, force_send_transaction, send_and_confirm_transaction,
+            TransactionResult,
+        },
     },
 };
 
@@ -34,78 +41,173 @@ pub async fn initialize_funding_accounts_if_needed(
     wallet: &Arc<Wallet>,
     funding_accounts: &Vec<FundingAccountMeta>,
 ) -> Result<(), Error> {
-    let ais = rpc_client
-        .get_multiple_accounts(
-            &funding_accounts
-                .iter()
-                .map(|meta| meta.address)
-                .collect::<Vec<Pubkey>>(),
-        )
-        .await?;
-    let mut uninitialized = vec![];
-
-    for (i, ai) in ais.iter().enumerate() {
-        let meta = &funding_accounts[i];
-
-        match ai {
-            None => uninitialized.push(meta),
-            Some(ai) => {
-                if ai.data.len() == 0 {
-                    uninitialized.push(meta);
-                }
-            }
+    // only fetches, nothing is polled
+    let source = RpcAccountSource::new(rpc_client.clone(), Duration::default());
+    let batched_fetch = BatchedFetch::default();
+    let encoding = AccountEncoding::default();
+
+    let addresses = funding_accounts
+        .iter()
+        .map(|meta| meta.address)
+        .collect::<Vec<Pubkey>>();
+    let history_addresses = funding_accounts
+        .iter()
+        .map(|meta| meta.history_address)
+        .collect::<Vec<Pubkey>>();
+    let ais = batched_fetch
+        .get_multiple_accounts(&source, &addresses, &encoding, None)
+        .await;
+    let history_ais = batched_fetch
+        .get_multiple_accounts(&source, &history_addresses, &encoding, None)
+        .await;
+    let funding_registry = FundingRegistryLoader::pda(&wallet.pubkey).0;
+    // `None` if the registry could not be fetched, registering is skipped then
+    let registered: Option<Vec<Pubkey>> = batched_fetch
+        .get_multiple_accounts(&source, &[funding_registry], &encoding, None)
+        .await
+        .pop()
+        .and_then(|ai| ai.ok())
+        .map(|ai| match ai {
+            Some(ai) if !ai.account.data.is_empty() => load_funding_registry(&ai.account.data)
+                .map(|registry| registry.entries.iter().map(|e| e.address).collect())
+                .unwrap_or_default(),
+            _ => vec![],
+        });
+    let mut ixs = vec![];
+
+    for ((meta, ai), history_ai) in funding_accounts.iter().zip(ais).zip(history_ais) {
+        // an account that failed to fetch may exist, initializing it would fail
+        let (Ok(ai), Ok(history_ai)) = (ai, history_ai) else {
+            println!(
+                "Unable to check funding accounts of market {}, skipping",
+                meta.market_index
+            );
+            continue;
+        };
+
+        let is_uninitialized = |ai: &Option<AccountUpdate>| match ai {
+            None => true,
+            Some(ai) => ai.account.data.len() == 0,
+        };
+
+        if is_uninitialized(&ai) {
+            ixs.push(initialize_funding_account(
+                InitializeFundingAccountAccounts {
+                    authority: wallet.pubkey,
+                    funding_account: meta.address,
+                    funding_registry,
+                },
+                0,
+                meta.exchange,
+                meta.market_index,
+                120,
+                600,
+                5,
+                30,
+            ));
+        } else if registered
+            .as_ref()
+            .is_some_and(|registered| !registered.contains(&meta.address))
+        {
+            // created before the registry existed
+            ixs.push(register_funding_account(RegisterFundingAccountAccounts {
+                authority: wallet.pubkey,
+                funding_account: meta.address,
+                funding_registry,
+            }));
         }
-    }
 
-    if uninitialized.len() > 0 {
-        for metas in uninitialized.chunks(10) {
-            let ixs = metas
-                .iter()
-                .map(|meta| {
-                    initialize_funding_account(
-                        InitializeFundingAccountAccounts {
-                            authority: wallet.pubkey,
-                            funding_account: meta.address,
-                        },
-                        0,
-                        meta.exchange,
-                        meta.market_index,
-                        120,
-                        600,
-                        5,
-                        30,
-                    )
-                })
-                .collect::<Vec<Instruction>>();
-
-            force_send_transaction(rpc_client, wallet, ixs, &vec![]).await?;
+        if is_uninitialized(&history_ai) {
+            ixs.push(initialize_funding_history(
+                InitializeFundingHistoryAccounts {
+                    authority: wallet.pubkey,
+                    funding_account: meta.address,
+                    funding_history: meta.history_address,
+                },
+            ));
         }
     }
 
+    for ixs in ixs.chunks(10) {
+        force_send_transaction(rpc_client, wallet, ixs.to_vec(), &vec![]).await?;
+    }
+
     Ok(())
 }
 
 const SNAPSHOT_TIMEOUT_SECS: u64 = 30;
 const RELAYER_SEND_FREQUENCY_SECS: u64 = 10;
+/// Lets the account subscriptions settle before the first snapshot
+const RELAYER_START_DELAY_SECS: u64 = 5;
+
+/// Intervals of the relayer tasks. Cache sizes are derived from the default
+/// snapshot interval, shorter intervals only speed the relayer up, ex: in tests
+#[derive(Clone, Copy, Debug)]
+pub struct RelayerConfig {
+    pub start_delay: Duration,
+    pub snapshot_interval: Duration,
+    pub send_interval: Duration,
+    /// Due updates are logged and marked published without sending any transaction,
+    /// the relayer then only reads from the account source
+    pub dry_run: bool,
+}
+
+impl Default for RelayerConfig {
+    fn default() -> Self {
+        Self {
+            start_delay: Duration::from_secs(RELAYER_START_DELAY_SECS),
+            snapshot_interval: Duration::from_secs(SNAPSHOT_TIMEOUT_SECS),
+            send_interval: Duration::from_secs(RELAYER_SEND_FREQUENCY_SECS),
+            dry_run: false,
+        }
+    }
+}
+
+impl RelayerConfig {
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Snapshots are skipped for oracles that did not update for longer than this
+const MAX_ORACLE_STALENESS_SECS: i64 = 60;
 
-struct MarketFundingCache {
+pub(crate) struct MarketFundingCache {
     pub address: Pubkey,
+    pub history_address: Pubkey,
     pub market: Pubkey,
     pub market_index: u16,
     pub exchange: Exchange,
     pub update_frequency_secs: u64,
 
-    pub funding_snapshots: Vec<i64>,
+    pub funding_snapshots: Vec<FundingRate<Apr>>,
 
-    pub last_account_update_at: Instant,
+    /// Cluster timestamp of the last funding account update
+    pub last_updated_ts: i64,
 }
 
 impl MarketFundingCache {
+    fn new(meta: &FundingAccountMeta, funding_account_data: &Vec<u8>) -> Option<Self> {
+        let funding_account = load_funding_account(funding_account_data).ok()?;
+
+        Some(Self {
+            address: meta.address,
+            history_address: meta.history_address,
+            market: meta.market,
+            market_index: meta.market_index,
+            exchange: funding_account.exchange,
+            update_frequency_secs: funding_account.config.update_frequency_secs,
+            funding_snapshots: vec![],
+            last_updated_ts: funding_account.last_updated_ts,
+        })
+    }
+
     fn cache_funding_rates(&self) -> usize {
         (self.update_frequency_secs / SNAPSHOT_TIMEOUT_SECS) as usize
     }
 
-    pub fn insert_funding_rate(&mut self, funding_rate: i64) {
+    pub fn insert_funding_rate(&mut self, funding_rate: FundingRate<Apr>) {
         if self.funding_snapshots.len() == self.cache_funding_rates() {
             self.funding_snapshots.remove(0);
         }
@@ -113,148 +215,249 @@ impl MarketFundingCache {
         self.funding_snapshots.push(funding_rate);
     }
 
-    pub fn get_average_funding_rate(&self) -> Option<i64> {
+    pub fn get_average_funding_rate(&self) -> Option<FundingRate<Apr>> {
         let len = self.cache_funding_rates();
         if self.funding_snapshots.len() == len {
-            let sum = self.funding_snapshots.iter().sum::<i64>();
-            Some(sum / (len as i64))
+            let sum = self
+                .funding_snapshots
+                .iter()
+                .copied()
+                .sum::<FundingRate<Apr>>();
+            Some(FundingRate::from_raw(sum.raw() / (len as i64)))
         } else {
             None
         }
     }
+
+    /// Standard deviation of the cached snapshots around the average funding rate
+    pub fn get_funding_rate_std_dev(&self, average: FundingRate<Apr>) -> Option<u64> {
+        let len = self.funding_snapshots.len();
+        if len == 0 {
+            return None;
+        }
+
+        let sum_squared_diffs = self
+            .funding_snapshots
+            .iter()
+            .map(|fr| {
+                let diff = (*fr - average).raw() as i128;
+                diff * diff
+            })
+            .sum::<i128>();
+        let variance = sum_squared_diffs / (len as i128);
+
+        Some((variance as f64).sqrt() as u64)
+    }
+
+    /// Average funding rate and its confidence once the funding account is due for an update
+    pub fn due_update(&self, now_ts: i64) -> Option<(FundingRate<Apr>, Option<u64>)> {
+        if now_ts - self.last_updated_ts < self.update_frequency_secs as i64 {
+            return None;
+        }
+
+        let funding_rate = self.get_average_funding_rate()?;
+        Some((funding_rate, self.get_funding_rate_std_dev(funding_rate)))
+    }
+}
+
+/// Inserts a funding rate snapshot of the market at `now_ts` into the cache
+async fn take_snapshot(state: &State, market_cache: &mut MarketFundingCache, now_ts: i64) {
+    match market_cache.exchange {
+        Exchange::Drift => {
+            let (perp_market, oracle) =
+                match state.get_drift_market_and_oracle(market_cache.market).await {
+                    Ok(snapshot) => snapshot.value,
+                    Err(e) => {
+                        println!(
+                            "Unable to take snapshot of drift market {}: {}",
+                            market_cache.market,
+                            e.to_string()
+                        );
+                        return;
+                    }
+                };
+
+            let Ok(price) = oracle.get_drift_price() else {
+                println!(
+                    "Invalid oracle price data for drift market: {} - {}",
+                    perp_market.market_index, perp_market.amm.oracle
+                );
+                return;
+            };
+
+            if oracle.is_stale(now_ts, MAX_ORACLE_STALENESS_SECS) {
+                println!(
+                    "Stale oracle for drift market: {} - {}",
+                    perp_market.market_index, perp_market.amm.oracle
+                );
+                return;
+            }
+
+            let funding_rate = perp_market.calculate_funding_rate(price, oracle.confidence, now_ts);
+
+            match funding_rate {
+                Ok(fr) => {
+                    market_cache.insert_funding_rate(fr);
+                }
+                Err(e) => {
+                    println!(
+                        "Unable to calculate drift funding rate for market: {}, oracle: {:?} - error: {}",
+                        perp_market.market_index,
+                        oracle,
+                        e.to_string(),
+                    );
+                }
+            }
+        }
+        Exchange::Mango => {
+            let (perp_market, bids, asks, oracle) = match state
+                .get_mango_market_with_components(market_cache.market)
+                .await
+            {
+                Ok(snapshot) => snapshot.value,
+                Err(e) => {
+                    println!(
+                        "Unable to take snapshot of mango market {}: {}",
+                        market_cache.market,
+                        e.to_string()
+                    );
+                    return;
+                }
+            };
+
+            if oracle.is_stale(now_ts, MAX_ORACLE_STALENESS_SECS) {
+                println!(
+                    "Stale oracle for mango market: {} - {}",
+                    perp_market.perp_market_index, perp_market.oracle
+                );
+                return;
+            }
+
+            let price = oracle.get_mango_price(perp_market.base_decimals);
+            let funding_rate =
+                perp_market.calculate_funding_rate(&bids, &asks, price, now_ts as u64);
+
+            match funding_rate {
+                Ok(fr) => {
+                    market_cache.insert_funding_rate(fr);
+                }
+                Err(_) => {
+                    println!(
+                        "Unable to calculate mango funding rate for market: {}, oracle: {:?}",
+                        perp_market.perp_market_index, oracle,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Marks the funding accounts of `markets` updated at `updated_ts`
+async fn mark_published(
+    state: &State,
+    cache: &Mutex<Vec<MarketFundingCache>>,
+    markets: impl Iterator<Item = &Pubkey>,
+    updated_ts: i64,
+) -> Result<(), Error> {
+    let mut log = match state.recorder() {
+        Some(recorder) => Some(recorder.lock().await),
+        None => None,
+    };
+    let mut cache = cache.lock().await;
+
+    for market in markets {
+        let Some(market_cache) = cache.iter_mut().find(|c| &c.market == market) else {
+            continue;
+        };
+
+        market_cache.last_updated_ts = updated_ts;
+
+        if let Some(log) = log.as_mut() {
+            log.append(&LogRecord::Published {
+                ts: updated_ts,
+                funding_account: market_cache.address,
+            })?;
+        }
+    }
+
+    Ok(())
 }
 
 pub async fn start_funding_relayer(
     rpc_client: Arc<RpcClient>,
     wallet: Arc<Wallet>,
     state: Arc<State>,
+    clock: Arc<ClusterClock>,
+    config: RelayerConfig,
 ) -> Result<(JoinHandle<Result<(), Error>>, JoinHandle<Result<(), Error>>), Error> {
-    sleep(Duration::from_secs(5)).await;
+    sleep(config.start_delay).await;
     let cache: Arc<Mutex<Vec<MarketFundingCache>>> = Default::default();
 
     {
         let funding_accounts_metas = &state.static_addresses.funding_accounts;
-        let ais = rpc_client
+        let addresses = funding_accounts_metas
+            .iter()
+            .map(|m| m.address)
+            .collect::<Vec<Pubkey>>();
+        let fetched = BatchedFetch::default()
             .get_multiple_accounts(
-                &funding_accounts_metas
-                    .iter()
-                    .map(|m| m.address)
-                    .collect::<Vec<Pubkey>>(),
+                state.source(),
+                &addresses,
+                &AccountEncoding::default(),
+                None,
             )
-            .await?;
+            .await;
+
         let mut cache = cache.lock().await;
 
-        for (i, ai) in ais.iter().enumerate() {
-            let meta = &funding_accounts_metas[i];
-
-            if let Some(ai) = ai {
-                if let Ok(funding_account) = load_funding_account(&ai.data) {
-                    cache.push(MarketFundingCache {
-                        address: meta.address,
-                        market: meta.market,
-                        market_index: meta.market_index,
-                        exchange: funding_account.exchange,
-                        update_frequency_secs: funding_account.config.update_frequency_secs,
-                        funding_snapshots: vec![],
-                        last_account_update_at: Instant::now(),
-                    });
-                    continue;
+        for (meta, fetched) in funding_accounts_metas.iter().zip(fetched) {
+            let update = match fetched {
+                Ok(Some(update)) => update,
+                Ok(None) => {
+                    // Should be unreachable since accounts get initialized
+                    println!("Funding account does not exist: {}", meta.address);
+                    return Err(Error::UnableToFetchAccount);
                 }
-            }
-
-            // Should be unreachable since accounts get initialized
-            println!("Funding account does not exist: {}", meta.address);
-            return Err(Error::UnableToFetchAccount);
+                // logged by the fetch
+                Err(e) => return Err(e),
+            };
+
+            // a replay builds its funding caches from these
+            state
+                .record(update.slot, [(meta.address, &update.account.data[..])])
+                .await?;
+
+            let Some(market_cache) = MarketFundingCache::new(meta, &update.account.data) else {
+                println!("Unable to deserialize funding account: {}", meta.address);
+                return Err(Error::UnableToDeserialize);
+            };
+            cache.push(market_cache);
         }
     }
 
     let cache_handle: JoinHandle<Result<(), Error>> = tokio::spawn({
         let cache = cache.clone();
+        let clock = clock.clone();
+        let state = state.clone();
 
         async move {
             loop {
                 println!("Taking snapshot");
 
-                State::update_for_funding_snapshot(&state).await?;
+                let now_ts = clock.now_ts().await;
 
+                let log = state
+                    .append_record(&LogRecord::Snapshot { ts: now_ts })
+                    .await?;
                 let mut cache = cache.lock().await;
 
                 for market_cache in cache.iter_mut() {
-                    match market_cache.exchange {
-                        Exchange::Drift => {
-                            let Some((perp_market, oracle)) =
-                                state.get_drift_market_and_oracle(market_cache.market).await
-                            else {
-                                println!(
-                                    "Perp market for drift market {} does not exist",
-                                    market_cache.market
-                                );
-                                continue;
-                            };
-
-                            let Ok(price) = oracle.get_drift_price() else {
-                                println!(
-                                    "Invalid oracle price data for drift market: {} - {}",
-                                    perp_market.market_index, perp_market.amm.oracle
-                                );
-                                continue;
-                            };
-
-                            let funding_rate =
-                                perp_market.calculate_funding_rate(price, oracle.confidence, 0);
-
-                            match funding_rate {
-                                Ok(fr) => {
-                                    market_cache.insert_funding_rate(fr);
-                                }
-                                Err(e) => {
-                                    println!(
-                                        "Unable to calculate drift funding rate for market: {}, oracle: {:?} - error: {}",
-                                        perp_market.market_index,
-                                        oracle,
-                                        e.to_string(),
-                                    );
-                                }
-                            }
-                        }
-                        Exchange::Mango => {
-                            let Some((perp_market, bids, asks, oracle)) = state
-                                .get_mango_market_with_components(market_cache.market)
-                                .await
-                            else {
-                                println!(
-                                    "Perp market for mango market {} does not exist",
-                                    market_cache.market
-                                );
-                                continue;
-                            };
-
-                            let price = oracle.get_mango_price(perp_market.base_decimals);
-                            let now_ts = SystemTime::now()
-                                .duration_since(SystemTime::UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs();
-                            let funding_rate =
-                                perp_market.calculate_funding_rate(&bids, &asks, price, now_ts);
-
-                            match funding_rate {
-                                Ok(fr) => {
-                                    market_cache.insert_funding_rate(fr);
-                                }
-                                Err(_) => {
-                                    println!(
-                                        "Unable to calculate mango funding rate for market: {}, oracle: {:?}",
-                                        perp_market.perp_market_index,
-                                        oracle,
-                                    );
-                                }
-                            }
-                        }
-                    }
+                    take_snapshot(&state, market_cache, now_ts).await;
                 }
 
                 drop(cache);
-                sleep(Duration::from_secs(SNAPSHOT_TIMEOUT_SECS)).await;
+                drop(log);
+                sleep(config.snapshot_interval).await;
             }
         }
     });
@@ -264,6 +467,11 @@ pub async fn start_funding_relayer(
 
         async move {
             loop {
+                let now_ts = clock.now_ts().await;
+                let mut log = match state.recorder() {
+                    Some(recorder) => Some(recorder.lock().await),
+                    None => None,
+                };
                 let cache_lock = cache.lock().await;
                 let mut markets_with_instructions = vec![];
 
@@ -273,41 +481,63 @@ pub async fn start_funding_relayer(
                         Exchange::Mango => "mango",
                     };
 
-                    if market_cache.last_account_update_at.elapsed().as_secs()
-                        < market_cache.update_frequency_secs
-                    {
-                        continue;
-                    }
-
-                    let Some(funding_rate) = market_cache.get_average_funding_rate() else {
+                    let Some((funding_rate, confidence)) = market_cache.due_update(now_ts) else {
                         continue;
                     };
 
+                    if let Some(log) = log.as_mut() {
+                        log.append(&LogRecord::Publish {
+                            ts: now_ts,
+                            funding_account: market_cache.address,
+                            funding_rate: funding_rate.raw(),
+                            confidence,
+                        })?;
+                    }
+
                     println!(
-                        "{} - {}: {}",
-                        exchange_str, market_cache.market_index, funding_rate
+                        "{} - {}: {} (confidence: {:?})",
+                        exchange_str, market_cache.market_index, funding_rate, confidence
                     );
                     markets_with_instructions.push((
                         market_cache.market,
-                        update_funding_account(
-                            UpdateFundingAccountAccounts {
+                        update_funding_data(
+                            UpdateFundingDataAccounts {
                                 authority: wallet.pubkey,
                                 funding_account: market_cache.address,
+                                funding_history: Some(market_cache.history_address),
                             },
-                            funding_rate,
+                            funding_rate.raw(),
+                            confidence,
                         ),
                     ))
                 }
                 drop(cache_lock);
+                drop(log);
 
-                if markets_with_instructions.len() > 0 {
+                if config.dry_run && markets_with_instructions.len() > 0 {
+                    println!(
+                        "Dry run, not sending {} funding account updates",
+                        markets_with_instructions.len()
+                    );
+                    let markets = markets_with_instructions.iter().map(|(market, _)| market);
+                    mark_published(&state, &cache, markets, now_ts).await?;
+                } else if markets_with_instructions.len() > 0 {
                     for markets_with_instructions in markets_with_instructions.chunks(10) {
                         let ixs = markets_with_instructions
                             .iter()
                             .map(|(_, ix)| ix.clone())
                             .collect::<Vec<Instruction>>();
-                        let tx = build_signed_transaction(&rpc_client, &wallet, &ixs[..], &vec![])
-                            .await?;
+                        // the updates stay due, they are sent again on the next round
+                        let tx =
+                            match build_signed_transaction(&rpc_client, &wallet, &ixs[..], &vec![])
+                                .await
+                            {
+                                Ok(tx) => tx,
+                                Err(e) => {
+                                    println!("Unable to build transaction: {}", e.to_string());
+                                    continue;
+                                }
+                            };
 
                         let mut retries = 0;
                         loop {
@@ -316,29 +546,38 @@ pub async fn start_funding_relayer(
                                 break;
                             }
 
-                            match send_and_confirm_transaction(&rpc_client, &tx).await? {
+                            let res = match send_and_confirm_transaction(&rpc_client, &tx).await {
+                                Ok(res) => res,
+                                Err(e) => {
+                                    println!("Unable to send transaction: {}", e.to_string());
+                                    retries += 1;
+                                    continue;
+                                }
+                            };
+
+                            match res {
                                 TransactionResult::Timeout(_) => {
                                     retries += 1;
                                     continue;
                                 }
                                 TransactionResult::Error(sig, e) => {
-                                    println!(
-                                        "TransactionError: {} Funding accounts update error: {}",
-                                        sig, e
-                                    );
+                                    match decode_transaction_error(&e, &ixs) {
+                                        Some(error_code) => println!(
+                                            "TransactionError: {} Funding program error: {}",
+                                            sig, error_code
+                                        ),
+                                        None => println!(
+                                            "TransactionError: {} Funding accounts update error: {}",
+                                            sig, e
+                                        ),
+                                    }
                                     break;
                                 }
                                 TransactionResult::Success(sig, _) => {
-                                    let mut cache = cache.lock().await;
-
-                                    for (market, _) in markets_with_instructions.iter() {
-                                        cache.iter_mut().find(|c| &c.market == market).map(
-                                            |market_cache| {
-                                                market_cache.last_account_update_at =
-                                                    Instant::now();
-                                            },
-                                        );
-                                    }
+                                    let updated_ts = clock.now_ts().await;
+                                    let markets =
+                                        markets_with_instructions.iter().map(|(market, _)| market);
+                                    mark_published(&state, &cache, markets, updated_ts).await?;
 
                                     println!("Successfully updated funding accounts {}", sig);
                                     break;
@@ -348,10 +587,98 @@ pub async fn start_funding_relayer(
                     }
                 }
 
-                sleep(Duration::from_secs(RELAYER_SEND_FREQUENCY_SECS)).await;
+                sleep(config.send_interval).await;
             }
         }
     });
 
     Ok((cache_handle, relayer_handle))
 }
+
+/// Funding rate published by the relayer and the one replayed at the same point of the log
+#[derive(Debug)]
+pub struct ReplayedPublish {
+    pub ts: i64,
+    pub funding_account: Pubkey,
+    pub recorded: (FundingRate<Apr>, Option<u64>),
+    pub replayed: Option<(FundingRate<Apr>, Option<u64>)>,
+}
+
+impl ReplayedPublish {
+    pub fn matches(&self) -> bool {
+        self.replayed == Some(self.recorded)
+    }
+}
+
+/// Feeds an account log through `state` and the relayer funding caches, on the
+/// timestamps of the log instead of the cluster clock. `state` is expected to start
+/// from the accounts as first recorded
+pub async fn replay_funding(
+    state: &State,
+    records: &[LogRecord],
+) -> Result<Vec<ReplayedPublish>, Error> {
+    let first_recorded = first_recorded_accounts(records)?;
+    let mut cache = vec![];
+
+    for meta in state.static_addresses.funding_accounts.iter() {
+        let Some(market_cache) = first_recorded
+            .get(&meta.address)
+            .and_then(|data| MarketFundingCache::new(meta, data))
+        else {
+            println!("Funding account is not in the log: {}", meta.address);
+            return Err(Error::UnableToFetchAccount);
+        };
+
+        cache.push(market_cache);
+    }
+
+    let mut publishes = vec![];
+
+    for record in records {
+        match record {
+            // the subscriptions of the recorded run
+            LogRecord::Account { .. }
+            | LogRecord::Observed { .. }
+            | LogRecord::Gap { .. }
+            | LogRecord::GapCleared { .. } => {
+                if let Err(e) = state.ingest(record).await {
+                    println!("Unable to apply account log record: {}", e.to_string());
+                }
+            }
+            LogRecord::Snapshot { ts } => {
+                for market_cache in cache.iter_mut() {
+                    take_snapshot(state, market_cache, *ts).await;
+                }
+            }
+            LogRecord::Publish {
+                ts,
+                funding_account,
+                funding_rate,
+                confidence,
+            } => {
+                let Some(market_cache) = cache.iter().find(|c| &c.address == funding_account)
+                else {
+                    continue;
+                };
+
+                publishes.push(ReplayedPublish {
+                    ts: *ts,
+                    funding_account: *funding_account,
+                    recorded: (FundingRate::from_raw(*funding_rate), *confidence),
+                    replayed: market_cache.due_update(*ts),
+                });
+            }
+            LogRecord::Published {
+                ts,
+                funding_account,
+            } => {
+                if let Some(market_cache) = cache.iter_mut().find(|c| &c.address == funding_account)
+                {
+                    market_cache.last_updated_ts = *ts;
+                }
+            }
+        }
+    }
+
+    Ok(publishes)
+}
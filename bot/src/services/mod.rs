@@ -1,2 +1,3 @@
 pub mod bot;
+pub mod cluster_clock;
 pub mod funding_relayer;
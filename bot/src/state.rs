@@ -1,21 +1,35 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use anchor_lang::{AccountDeserialize, Discriminator};
 use drift::accounts::PerpMarket as DriftPerpMarket;
 use fixed::types::I80F48;
+use futures::{future::try_join_all, StreamExt};
 use mango::accounts::{BookSide, PerpMarket as MangoPerpMarket};
-use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
-use tokio::{sync::RwLock, time::Instant};
-
-use crate::{addresses::StaticAddresses, error::Error, utils::deser::AccountData};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::{
+    account_source::{AccountSource, AccountSubscription, AccountUpdate, BatchedFetch},
+    addresses::StaticAddresses,
+    error::Error,
+    utils::{
+        account_log::{LogRecord, Recorder, RecorderGuard},
+        deser::{AccountData, AccountEncoding},
+        websocket_client::Notification,
+    },
+};
 
 #[derive(Clone, Copy, Debug)]
 pub struct OraclePriceData {
     pub expo: i32,
     pub price: i64,
     pub updated_at_slot: u64,
-    pub updated_at_ts: Instant,
+    /// Cluster unix timestamp of the aggregate price
+    pub updated_at_ts: i64,
     pub confidence: u64,
 }
 
@@ -26,12 +40,16 @@ impl From<&pyth_sdk_solana::state::PriceAccount> for OraclePriceData {
             price: value.agg.price,
             updated_at_slot: value.last_slot,
             confidence: value.agg.conf,
-            updated_at_ts: Instant::now(),
+            updated_at_ts: value.timestamp,
         }
     }
 }
 
 impl OraclePriceData {
+    pub fn is_stale(&self, now_ts: i64, max_staleness_secs: i64) -> bool {
+        now_ts - self.updated_at_ts > max_staleness_secs
+    }
+
     pub fn get_drift_price(&self) -> Result<i64, Error> {
         use drift::constants::PRICE_PRECISION;
 
@@ -73,195 +91,635 @@ impl OraclePriceData {
     }
 }
 
+/// Markets that do not exist or fail to fetch or parse are logged and skipped,
+/// fails only if none of them could be read
 pub async fn fetch_markets<T: AccountDeserialize + Discriminator>(
-    rpc_client: &Arc<RpcClient>,
+    source: &dyn AccountSource,
     markets: &Vec<Pubkey>,
 ) -> Result<Vec<(Pubkey, T)>, Error> {
-    let ais = rpc_client.get_multiple_accounts(&markets).await?;
+    let fetched = BatchedFetch::default()
+        .get_multiple_accounts(source, markets, &AccountEncoding::default(), None)
+        .await;
     let mut parsed = vec![];
 
-    for (i, ai) in ais.iter().enumerate() {
-        let address = &markets[i];
-        if let Some(ai) = ai {
-            let market = AccountData::from(ai).parse().map_err(|e| {
-                println!("Unable to deserialize market account: {}", address);
-                e
-            })?;
-            parsed.push((*address, market));
-        } else {
-            println!("Perp market account does not exist: {}", address);
-            return Err(Error::UnableToFetchAccount);
+    for (address, fetched) in markets.iter().zip(fetched) {
+        match fetched {
+            Ok(Some(update)) => match AccountData::from(&update.account).parse() {
+                Ok(market) => parsed.push((*address, market)),
+                Err(_) => println!("Unable to deserialize market account: {}", address),
+            },
+            Ok(None) => println!("Perp market account does not exist: {}", address),
+            // logged by the fetch
+            Err(_) => (),
         }
     }
 
+    if parsed.is_empty() && !markets.is_empty() {
+        return Err(Error::UnableToFetchAccount);
+    }
+
     Ok(parsed)
 }
 
+/// Latest copy of an account and the slot it was updated at
+#[derive(Clone, Copy, Debug)]
+pub struct Slotted<T> {
+    pub slot: u64,
+    pub value: T,
+}
+
+/// Accounts by address, readers clone the `Arc` of the copy they need so updates
+/// never block on or change a snapshot being computed
+pub type SlottedAccounts<T> = RwLock<HashMap<Pubkey, Slotted<Arc<T>>>>;
+
+/// `false` if the update is older than the copy held, ex: a notification queued
+/// before the initial fetch or an RPC response racing a websocket notification
+async fn upsert<T>(accounts: &SlottedAccounts<T>, address: Pubkey, slot: u64, value: T) -> bool {
+    let mut accounts = accounts.write().await;
+
+    match accounts.get(&address) {
+        Some(account) if account.slot > slot => false,
+        _ => {
+            let value = Arc::new(value);
+            accounts.insert(address, Slotted { slot, value });
+            true
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SubscribedAccount {
+    DriftMarket,
+    MangoMarket,
+    BookSide,
+    Oracle,
+}
+
+impl SubscribedAccount {
+    const ALL: [Self; 4] = [
+        Self::DriftMarket,
+        Self::MangoMarket,
+        Self::BookSide,
+        Self::Oracle,
+    ];
+
+    /// Used for both the RPC fetch and the subscription of the account
+    fn encoding(self) -> AccountEncoding {
+        match self {
+            // mostly empty order tree nodes, compresses well
+            Self::BookSide => AccountEncoding::base64_zstd(),
+            _ => AccountEncoding::base64(),
+        }
+    }
+
+    /// Subscriptions without a notification for this long are resubscribed and the
+    /// account fetched again
+    fn silence_timeout(self) -> Duration {
+        match self {
+            // oracles update every few slots, silence means a dropped subscription
+            Self::Oracle => Duration::from_secs(ORACLE_SILENCE_TIMEOUT_SECS),
+            _ => Duration::from_secs(MARKET_SILENCE_TIMEOUT_SECS),
+        }
+    }
+}
+
+/// Oracle subscriptions without a notification for this long are resubscribed
+const ORACLE_SILENCE_TIMEOUT_SECS: u64 = 30;
+/// Markets and book sides change on every trade, quiet ones are refetched after this
+const MARKET_SILENCE_TIMEOUT_SECS: u64 = 60;
+/// Slots the components of a funding snapshot can be apart, about 8 seconds
+pub const DEFAULT_SNAPSHOT_SLOT_WINDOW: u64 = 20;
+
 pub struct State {
-    rpc_client: Arc<RpcClient>,
+    source: Arc<dyn AccountSource>,
+    recorder: Option<Arc<Recorder>>,
+    snapshot_slot_window: u64,
+    /// Newest notification slot of each account subscription. A subscription only
+    /// vouches for its own account, a quiet one falls out of the snapshot window
+    /// until it is notified or refetched after its silence timeout
+    observed_slots: RwLock<HashMap<Pubkey, u64>>,
+    /// Accounts whose subscription had a gap the refetch did not cover yet
+    gapped: RwLock<HashSet<Pubkey>>,
     pub static_addresses: StaticAddresses,
 
-    pub drift_markets: RwLock<Vec<(Pubkey, DriftPerpMarket)>>,
-    pub mango_markets: RwLock<Vec<(Pubkey, MangoPerpMarket)>>,
-    pub oracles: RwLock<Vec<(Pubkey, OraclePriceData)>>,
-    pub book_sides: RwLock<Vec<(Pubkey, BookSide)>>,
+    pub drift_markets: SlottedAccounts<DriftPerpMarket>,
+    pub mango_markets: SlottedAccounts<MangoPerpMarket>,
+    pub oracles: SlottedAccounts<OraclePriceData>,
+    pub book_sides: SlottedAccounts<BookSide>,
 }
 
 impl State {
-    pub fn new(rpc_client: Arc<RpcClient>, static_addresses: StaticAddresses) -> Self {
+    pub fn new(source: Arc<dyn AccountSource>, static_addresses: StaticAddresses) -> Self {
         Self {
-            rpc_client,
+            source,
+            recorder: None,
+            snapshot_slot_window: DEFAULT_SNAPSHOT_SLOT_WINDOW,
+            observed_slots: Default::default(),
+            gapped: Default::default(),
             static_addresses,
-            drift_markets: RwLock::new(vec![]),
-            mango_markets: RwLock::new(vec![]),
-            oracles: RwLock::new(vec![]),
-            book_sides: RwLock::new(vec![]),
+            drift_markets: Default::default(),
+            mango_markets: Default::default(),
+            oracles: Default::default(),
+            book_sides: Default::default(),
         }
     }
 
-    pub async fn update_drift_markets(state: Arc<State>) -> Result<(), Error> {
-        *state.drift_markets.write().await = fetch_markets::<DriftPerpMarket>(
-            &state.rpc_client,
-            &state.static_addresses.drift_markets,
-        )
-        .await?;
+    /// Every account update ingested is appended to the log of `recorder`
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
 
-        Ok(())
+    /// Snapshots whose components were read more than `slots` apart are rejected
+    pub fn with_snapshot_slot_window(mut self, slots: u64) -> Self {
+        self.snapshot_slot_window = slots;
+        self
     }
 
-    pub async fn update_mango_markets(state: Arc<State>) -> Result<(), Error> {
-        *state.mango_markets.write().await = fetch_markets::<MangoPerpMarket>(
-            &state.rpc_client,
-            &state.static_addresses.mango_markets,
-        )
-        .await?;
-        Ok(())
+    pub fn source(&self) -> &dyn AccountSource {
+        self.source.as_ref()
     }
 
-    pub async fn update_oracles(state: Arc<State>) -> Result<(), Error> {
-        let oracles = &state.static_addresses.oracles;
-        let ais = state.rpc_client.get_multiple_accounts(oracles).await?;
-        let mut parsed = vec![];
-
-        for (i, ai) in ais.iter().enumerate() {
-            let address = &oracles[i];
-            if let Some(ai) = ai {
-                match pyth_sdk_solana::state::load_price_account(&ai.data[..]) {
-                    Ok(price_account) => {
-                        parsed.push((*address, OraclePriceData::from(price_account)));
-                    }
-                    Err(e) => {
-                        println!(
-                            "Unable to parse oracle account {}: {}",
-                            address,
-                            e.to_string()
-                        )
-                    }
-                };
-            } else {
-                println!("Perp market account does not exist: {}", address);
-                return Err(Error::UnableToFetchAccount);
-            }
+    pub fn recorder(&self) -> Option<&Arc<Recorder>> {
+        self.recorder.as_ref()
+    }
+
+    /// Appends the accounts to the log if recording, the guard is held until the
+    /// accounts are written to `State` so the log keeps the order readers see
+    pub async fn record<'a>(
+        &self,
+        slot: u64,
+        accounts: impl IntoIterator<Item = (Pubkey, &'a [u8])>,
+    ) -> Result<Option<RecorderGuard<'_>>, Error> {
+        let Some(recorder) = &self.recorder else {
+            return Ok(None);
+        };
+
+        let mut guard = recorder.lock().await;
+        for (address, data) in accounts {
+            guard.append(&LogRecord::account(slot, address, data)?)?;
         }
 
-        *state.oracles.write().await = parsed;
-        Ok(())
+        Ok(Some(guard))
+    }
+
+    /// Appends `record` to the log if recording, the guard is held until the recorded
+    /// change is applied
+    pub async fn append_record(
+        &self,
+        record: &LogRecord,
+    ) -> Result<Option<RecorderGuard<'_>>, Error> {
+        let Some(recorder) = &self.recorder else {
+            return Ok(None);
+        };
+
+        let mut guard = recorder.lock().await;
+        guard.append(record)?;
+        Ok(Some(guard))
     }
 
-    pub async fn update_mango_book_sides(state: Arc<State>) -> Result<(), Error> {
-        let book_sides_addresses = &state.static_addresses.mango_book_sides;
-        let ais = state
-            .rpc_client
+    /// Fetches the accounts of `kind`, accounts that are missing or fail to fetch or
+    /// parse are logged and skipped. Returns the oldest slot the accounts were read at,
+    /// `None` if there is nothing to fetch
+    async fn update_accounts(
+        state: Arc<State>,
+        kind: SubscribedAccount,
+        min_context_slot: Option<u64>,
+    ) -> Result<Option<u64>, Error> {
+        let addresses = state
+            .subscribed_accounts()
+            .into_iter()
+            .filter(|(subscribed, _)| subscribed == &kind)
+            .map(|(_, address)| address)
+            .collect::<Vec<Pubkey>>();
+        if addresses.is_empty() {
+            return Ok(None);
+        }
+
+        let fetched = BatchedFetch::default()
             .get_multiple_accounts(
-                &book_sides_addresses
+                state.source.as_ref(),
+                &addresses,
+                &kind.encoding(),
+                min_context_slot,
+            )
+            .await;
+
+        let mut oldest_slot: Option<u64> = None;
+        for (address, fetched) in addresses.into_iter().zip(fetched) {
+            let update = match fetched {
+                Ok(Some(update)) => update,
+                Ok(None) => {
+                    println!("{:?} account does not exist: {}", kind, address);
+                    continue;
+                }
+                // logged by the fetch
+                Err(_) => continue,
+            };
+
+            let data = AccountData::from(&update.account);
+            if let Err(e) = state
+                .apply_account_update(kind, address, update.slot, data)
+                .await
+            {
+                println!(
+                    "Unable to apply {:?} account {}: {}",
+                    kind,
+                    address,
+                    e.to_string()
+                );
+                continue;
+            }
+
+            oldest_slot = Some(oldest_slot.map_or(update.slot, |slot| slot.min(update.slot)));
+        }
+
+        match oldest_slot {
+            Some(slot) => Ok(Some(slot)),
+            None => Err(Error::UnableToFetchAccount),
+        }
+    }
+
+    /// Newest slot of the accounts held, `None` before the first fetch
+    pub async fn newest_slot(&self) -> Option<u64> {
+        let drift_markets = self.drift_markets.read().await;
+        let mango_markets = self.mango_markets.read().await;
+        let book_sides = self.book_sides.read().await;
+        let oracles = self.oracles.read().await;
+
+        drift_markets
+            .values()
+            .map(|a| a.slot)
+            .chain(mango_markets.values().map(|a| a.slot))
+            .chain(book_sides.values().map(|a| a.slot))
+            .chain(oracles.values().map(|a| a.slot))
+            .max()
+    }
+
+    /// Fetches every account over RPC, account subscriptions keep them up to date afterwards.
+    /// Fetches are never older than the accounts already held, and groups read from a
+    /// slot outside the snapshot window of the newest group, ex: answered by a lagging
+    /// node, are fetched again at that slot. Returns the newest slot fetched
+    pub async fn fetch_all(state: &Arc<State>) -> Result<u64, Error> {
+        let min_context_slot = state.newest_slot().await;
+        let slots = try_join_all(
+            SubscribedAccount::ALL
+                .map(|kind| State::update_accounts(state.clone(), kind, min_context_slot)),
+        )
+        .await?;
+
+        let newest = slots.iter().flatten().copied().max().unwrap_or_default();
+        let lagging = SubscribedAccount::ALL
+            .into_iter()
+            .zip(slots)
+            .filter_map(|(kind, slot)| Some((kind, slot?)))
+            .filter(|(_, slot)| newest - slot > state.snapshot_slot_window)
+            .map(|(kind, slot)| {
+                println!(
+                    "{:?} accounts fetched at slot {}, behind slot {}, fetching again",
+                    kind, slot, newest
+                );
+                State::update_accounts(state.clone(), kind, Some(newest))
+            });
+        try_join_all(lagging).await?;
+
+        Ok(newest)
+    }
+
+    fn subscribed_accounts(&self) -> Vec<(SubscribedAccount, Pubkey)> {
+        let addresses = &self.static_addresses;
+
+        addresses
+            .drift_markets
+            .iter()
+            .map(|addr| (SubscribedAccount::DriftMarket, *addr))
+            .chain(
+                addresses
+                    .mango_markets
                     .iter()
-                    .map(|(_, addr, _)| *addr)
-                    .collect::<Vec<Pubkey>>(),
+                    .map(|addr| (SubscribedAccount::MangoMarket, *addr)),
             )
-            .await?;
+            .chain(
+                addresses
+                    .mango_book_sides
+                    .iter()
+                    .map(|(_, addr, _)| (SubscribedAccount::BookSide, *addr)),
+            )
+            .chain(
+                addresses
+                    .oracles
+                    .iter()
+                    .map(|addr| (SubscribedAccount::Oracle, *addr)),
+            )
+            .collect()
+    }
 
-        let mut book_sides = vec![];
+    async fn apply_account_update(
+        &self,
+        kind: SubscribedAccount,
+        address: Pubkey,
+        slot: u64,
+        data: AccountData<'_>,
+    ) -> Result<(), Error> {
+        let bytes = data.bytes()?.into_owned();
+        let data = AccountData::Serialized(&bytes);
+        let _record = self.record(slot, [(address, &bytes[..])]).await?;
+
+        let applied = match kind {
+            SubscribedAccount::DriftMarket => {
+                upsert(&self.drift_markets, address, slot, data.parse()?).await
+            }
+            SubscribedAccount::MangoMarket => {
+                upsert(&self.mango_markets, address, slot, data.parse()?).await
+            }
+            SubscribedAccount::BookSide => {
+                upsert(&self.book_sides, address, slot, data.parse()?).await
+            }
+            SubscribedAccount::Oracle => {
+                let price_account = pyth_sdk_solana::state::load_price_account(&bytes[..])
+                    .map_err(|_| Error::UnableToDeserialize)?;
 
-        for (i, ai) in ais.iter().enumerate() {
-            let address = book_sides_addresses[i].1;
-            if let Some(ai) = ai {
-                book_sides.push((
+                upsert(
+                    &self.oracles,
                     address,
-                    AccountData::from(ai).parse().map_err(|e| {
-                        println!("Unable to deserialize mango book side account: {}", address);
-                        e
-                    })?,
-                ))
-            } else {
-                println!("Mango book side does not exist: {}", address);
-                return Err(Error::UnableToFetchAccount);
+                    slot,
+                    OraclePriceData::from(price_account),
+                )
+                .await
+            }
+        };
+
+        if !applied {
+            println!(
+                "Skipped update of {} at slot {}, older than the copy held",
+                address, slot
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Applies a record of an account log, which stands for the subscriptions, ex: on
+    /// replay. Accounts `State` does not track and relayer records are ignored
+    pub async fn ingest(&self, record: &LogRecord) -> Result<(), Error> {
+        match record {
+            LogRecord::Account { slot, pubkey, data } => {
+                let Some((kind, _)) = self
+                    .subscribed_accounts()
+                    .into_iter()
+                    .find(|(_, subscribed)| subscribed == pubkey)
+                else {
+                    return Ok(());
+                };
+
+                let data = LogRecord::decompress(data)?;
+                self.apply_account_update(kind, *pubkey, *slot, AccountData::Serialized(&data))
+                    .await
             }
+            LogRecord::Observed { slot, pubkey } => self.observe_slot(*pubkey, *slot).await,
+            LogRecord::Gap { pubkey } => self.set_gapped(*pubkey, true).await,
+            LogRecord::GapCleared { pubkey } => self.set_gapped(*pubkey, false).await,
+            _ => Ok(()),
         }
+    }
 
-        *state.book_sides.write().await = book_sides;
+    async fn observe_slot(&self, address: Pubkey, slot: u64) -> Result<(), Error> {
+        let _record = self
+            .append_record(&LogRecord::Observed {
+                slot,
+                pubkey: address,
+            })
+            .await?;
 
+        let mut observed_slots = self.observed_slots.write().await;
+        let observed = observed_slots.entry(address).or_default();
+        *observed = (*observed).max(slot);
         Ok(())
     }
 
-    pub async fn update_for_funding_snapshot(state: &Arc<State>) -> Result<(), Error> {
-        let (r1, r2, r3, r4) = tokio::join!(
-            State::update_drift_markets(state.clone()),
-            State::update_mango_markets(state.clone()),
-            State::update_mango_book_sides(state.clone()),
-            State::update_oracles(state.clone()),
-        );
+    /// Gap changes are appended to the log if recording, snapshots of a replay are
+    /// then checked against the same gaps
+    async fn set_gapped(&self, address: Pubkey, gapped: bool) -> Result<(), Error> {
+        let mut log = match &self.recorder {
+            Some(recorder) => Some(recorder.lock().await),
+            None => None,
+        };
+        let mut gapped_accounts = self.gapped.write().await;
+
+        let changed = if gapped {
+            gapped_accounts.insert(address)
+        } else {
+            gapped_accounts.remove(&address)
+        };
 
-        r1?;
-        r2?;
-        r3?;
-        r4?;
+        if let (true, Some(log)) = (changed, log.as_mut()) {
+            let record = if gapped {
+                LogRecord::Gap { pubkey: address }
+            } else {
+                LogRecord::GapCleared { pubkey: address }
+            };
+            log.append(&record)?;
+        }
 
         Ok(())
     }
 
-    pub async fn get_drift_market_and_oracle(
+    /// Newest slot the copy of `address` is known to be current at, the last
+    /// notification of its subscription unless a gap of the account was not covered
+    async fn observed_slot<T>(&self, address: &Pubkey, account: &Slotted<T>) -> u64 {
+        if self.gapped.read().await.contains(address) {
+            return account.slot;
+        }
+
+        let observed = self.observed_slots.read().await.get(address).copied();
+        account.slot.max(observed.unwrap_or_default())
+    }
+
+    /// Fetches a single subscribed account over RPC, used to cover notifications
+    /// missed while the websocket was reconnecting
+    async fn refetch_account(&self, kind: SubscribedAccount, address: Pubkey) -> Result<(), Error> {
+        self.set_gapped(address, true).await?;
+
+        let (slot, ais) = self
+            .source
+            .get_multiple_accounts(&[address], &kind.encoding(), None)
+            .await?;
+        let Some(Some(account)) = ais.first() else {
+            return Err(Error::UnableToFetchAccount);
+        };
+
+        self.apply_account_update(kind, address, slot, AccountData::from(account))
+            .await?;
+        self.set_gapped(address, false).await
+    }
+
+    /// The account is current again at the slot of the notification, whether the
+    /// refetch of a gap failed or not
+    async fn apply_notification(
         &self,
-        market_address: Pubkey,
-    ) -> Option<(DriftPerpMarket, OraclePriceData)> {
-        let drift_markets = self.drift_markets.read().await;
-        let market = drift_markets
-            .iter()
-            .find(|(addr, _)| addr == &market_address);
+        kind: SubscribedAccount,
+        address: Pubkey,
+        update: &AccountUpdate,
+    ) -> Result<(), Error> {
+        self.observe_slot(address, update.slot).await?;
+        self.set_gapped(address, false).await?;
+        self.apply_account_update(
+            kind,
+            address,
+            update.slot,
+            AccountData::from(&update.account),
+        )
+        .await
+    }
+
+    /// Keeps markets, book sides and oracles up to date with the account source,
+    /// subscribed to all at once so sources can share a subscription. A gap in the updates of an account, ex: after a websocket reconnect, triggers
+    /// a refetch of the account to cover missed updates
+    pub fn subscribe_to_accounts(state: Arc<State>) -> JoinHandle<Result<(), Error>> {
+        tokio::spawn(async move {
+            let subscribed = state.subscribed_accounts();
+            let subscriptions = subscribed
+                .iter()
+                .map(|(kind, address)| AccountSubscription {
+                    address: *address,
+                    encoding: kind.encoding(),
+                    silence_timeout: Some(kind.silence_timeout()),
+                })
+                .collect::<Vec<AccountSubscription>>();
+            let kinds = subscribed
+                .into_iter()
+                .map(|(kind, address)| (address, kind))
+                .collect::<HashMap<Pubkey, SubscribedAccount>>();
+
+            let mut updates = state.source.subscribe_many(&subscriptions).await?;
+
+            // updates in between the fetch and the subscriptions are not missed, the
+            // fetched accounts are current at the slot they were read at
+            State::fetch_all(&state).await?;
+
+            while let Some((address, notification)) = updates.next().await {
+                let Some(kind) = kinds.get(&address).copied() else {
+                    continue;
+                };
 
-        if let Some((_, market)) = market {
-            let oracles = self.oracles.read().await;
-            if let Some((_, oracle)) = oracles.iter().find(|(addr, _)| addr == &market.amm.oracle) {
-                return Some((market.clone(), *oracle));
+                let res = match notification {
+                    Notification::Value(update) => {
+                        state.apply_notification(kind, address, &update).await
+                    }
+                    Notification::Gap => state.refetch_account(kind, address).await,
+                };
+
+                if let Err(e) = res {
+                    println!(
+                        "Unable to apply account update {}: {}",
+                        address,
+                        e.to_string()
+                    );
+                }
             }
+
+            println!("Account subscriptions closed");
+            Err(Error::ServiceShutdownUnexpectedly)
+        })
+    }
+
+    /// Newest observed slot of the components of a snapshot of `market`, components
+    /// observed further apart than the snapshot window are rejected
+    fn snapshot_slot(&self, market: &Pubkey, slots: &[u64]) -> Result<u64, Error> {
+        let newest = slots.iter().copied().max().unwrap_or_default();
+        let oldest = slots.iter().copied().min().unwrap_or_default();
+
+        if newest - oldest > self.snapshot_slot_window {
+            println!(
+                "Snapshot of market {} spans slots {} to {}, more than {} slots apart",
+                market, oldest, newest, self.snapshot_slot_window
+            );
+            return Err(Error::SnapshotSlotWindowExceeded);
         }
 
-        None
+        Ok(newest)
     }
 
-    pub async fn get_mango_market_with_components(
+    /// Market and oracle, tagged with the newest observed slot of the two
+    pub async fn get_drift_market_and_oracle(
         &self,
         market_address: Pubkey,
-    ) -> Option<(MangoPerpMarket, BookSide, BookSide, OraclePriceData)> {
-        let mango_markets = self.mango_markets.read().await;
-        let Some((_, market)) = mango_markets
-            .iter()
-            .find(|(addr, _)| addr == &market_address)
-        else {
-            return None;
-        };
+    ) -> Result<Slotted<(Arc<DriftPerpMarket>, Arc<OraclePriceData>)>, Error> {
+        let market = self
+            .drift_markets
+            .read()
+            .await
+            .get(&market_address)
+            .cloned()
+            .ok_or(Error::UnableToFetchAccount)?;
+        let oracle = self
+            .oracles
+            .read()
+            .await
+            .get(&market.value.amm.oracle)
+            .cloned()
+            .ok_or(Error::UnableToFetchAccount)?;
+
+        let slots = [
+            self.observed_slot(&market_address, &market).await,
+            self.observed_slot(&market.value.amm.oracle, &oracle).await,
+        ];
+        let slot = self.snapshot_slot(&market_address, &slots)?;
+
+        Ok(Slotted {
+            slot,
+            value: (market.value, oracle.value),
+        })
+    }
 
-        let oracles = self.oracles.read().await;
-        let Some((_, oracle)) = oracles.iter().find(|(addr, _)| addr == &market.oracle) else {
-            return None;
-        };
+    /// Market, bids, asks and oracle, tagged with the newest observed slot of the four
+    pub async fn get_mango_market_with_components(
+        &self,
+        market_address: Pubkey,
+    ) -> Result<
+        Slotted<(
+            Arc<MangoPerpMarket>,
+            Arc<BookSide>,
+            Arc<BookSide>,
+            Arc<OraclePriceData>,
+        )>,
+        Error,
+    > {
+        let market = self
+            .mango_markets
+            .read()
+            .await
+            .get(&market_address)
+            .cloned()
+            .ok_or(Error::UnableToFetchAccount)?;
+        let oracle = self
+            .oracles
+            .read()
+            .await
+            .get(&market.value.oracle)
+            .cloned()
+            .ok_or(Error::UnableToFetchAccount)?;
 
         let book_sides = self.book_sides.read().await;
-        let bids = book_sides.iter().find(|(addr, _)| addr == &market.bids);
-        let asks = book_sides.iter().find(|(addr, _)| addr == &market.asks);
-
-        match (bids, asks) {
-            (Some((_, bids)), Some((_, asks))) => Some((market.clone(), *bids, *asks, *oracle)),
-            _ => None,
-        }
+        let (Some(bids), Some(asks)) = (
+            book_sides.get(&market.value.bids).cloned(),
+            book_sides.get(&market.value.asks).cloned(),
+        ) else {
+            return Err(Error::UnableToFetchAccount);
+        };
+        drop(book_sides);
+
+        let slots = [
+            self.observed_slot(&market_address, &market).await,
+            self.observed_slot(&market.value.bids, &bids).await,
+            self.observed_slot(&market.value.asks, &asks).await,
+            self.observed_slot(&market.value.oracle, &oracle).await,
+        ];
+        let slot = self.snapshot_slot(&market_address, &slots)?;
+
+        Ok(Slotted {
+            slot,
+            value: (market.value, bids.value, asks.value, oracle.value),
+        })
     }
 }
@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anchor_lang::prelude::{borsh, AnchorDeserialize, AnchorSerialize};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::error::Error;
+
+/// Entry of the account log, relayer entries carry the cluster timestamp they
+/// were taken at so a replay runs on the same virtual time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum LogRecord {
+    /// Account data ingested by `State`, zstd compressed
+    Account {
+        slot: u64,
+        pubkey: Pubkey,
+        data: Vec<u8>,
+    },
+    /// Relayer took funding snapshots of every market
+    Snapshot { ts: i64 },
+    /// Relayer decided to publish a funding rate
+    Publish {
+        ts: i64,
+        funding_account: Pubkey,
+        funding_rate: i64,
+        confidence: Option<u64>,
+    },
+    /// Funding account update confirmed
+    Published { ts: i64, funding_account: Pubkey },
+    /// Subscription of the account notified at `slot`
+    Observed { slot: u64, pubkey: Pubkey },
+    /// Updates of the account may have been missed, its subscription no longer
+    /// vouches for it
+    Gap { pubkey: Pubkey },
+    /// A refetch or a notification covered the gap of the account
+    GapCleared { pubkey: Pubkey },
+}
+
+impl LogRecord {
+    pub fn account(slot: u64, pubkey: Pubkey, data: &[u8]) -> Result<Self, Error> {
+        Ok(Self::Account {
+            slot,
+            pubkey,
+            data: zstd::bulk::compress(data, 0).map_err(|_| Error::UnableToWriteLog)?,
+        })
+    }
+
+    pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+        zstd::stream::decode_all(data).map_err(|_| Error::UnableToDecode)
+    }
+}
+
+/// Appends records to the account log. Writers hold the lock for as long as the
+/// recorded change takes to become visible, so the log order is the order the
+/// relayer observed. A log holds a single session, replays start from its first
+/// accounts
+pub struct Recorder {
+    writer: Mutex<BufWriter<File>>,
+}
+
+pub struct RecorderGuard<'a>(MutexGuard<'a, BufWriter<File>>);
+
+impl Recorder {
+    /// Replaces the log at `path`, a previous session would be replayed from the
+    /// accounts it started with
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|e| {
+            println!("Unable to open account log {}: {}", path.display(), e);
+            Error::UnableToWriteLog
+        })?;
+
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub async fn lock(&self) -> RecorderGuard<'_> {
+        RecorderGuard(self.writer.lock().await)
+    }
+}
+
+impl<'a> RecorderGuard<'a> {
+    pub fn append(&mut self, record: &LogRecord) -> Result<(), Error> {
+        record
+            .serialize(&mut *self.0)
+            .and_then(|_| self.0.flush())
+            .map_err(|_| Error::UnableToWriteLog)
+    }
+}
+
+/// Data of every account as first recorded, the state the relayer started from
+pub fn first_recorded_accounts(records: &[LogRecord]) -> Result<HashMap<Pubkey, Vec<u8>>, Error> {
+    let mut accounts = HashMap::new();
+
+    for record in records {
+        if let LogRecord::Account { pubkey, data, .. } = record {
+            if !accounts.contains_key(pubkey) {
+                accounts.insert(*pubkey, LogRecord::decompress(data)?);
+            }
+        }
+    }
+
+    Ok(accounts)
+}
+
+/// Records of the account log, a record truncated by a crash ends the log
+pub fn read_log(path: &Path) -> Result<Vec<LogRecord>, Error> {
+    let bytes = fs::read(path).map_err(|e| {
+        println!("Unable to read account log {}: {}", path.display(), e);
+        Error::UnableToReadLog
+    })?;
+
+    let mut remaining = &bytes[..];
+    let mut records = vec![];
+
+    while !remaining.is_empty() {
+        match LogRecord::deserialize(&mut remaining) {
+            Ok(record) => records.push(record),
+            Err(_) => {
+                println!("Account log truncated after {} records", records.len());
+                break;
+            }
+        }
+    }
+
+    Ok(records)
+}
@@ -1,7 +1,10 @@
+use std::borrow::Cow;
+
 use anchor_lang::{AccountDeserialize, Discriminator};
 use base64::{engine::general_purpose, Engine};
-use solana_account_decoder::{UiAccount, UiAccountData, UiAccountEncoding};
-use solana_sdk::account::Account;
+use solana_account_decoder::{UiAccount, UiAccountData, UiAccountEncoding, UiDataSliceConfig};
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig};
 
 use crate::error::Error;
 
@@ -9,6 +12,70 @@ pub fn decode_base64_data(encoded: &String) -> Option<Vec<u8>> {
     general_purpose::STANDARD.decode(encoded).ok()
 }
 
+pub fn decode_base64_zstd_data(encoded: &String) -> Option<Vec<u8>> {
+    let compressed = decode_base64_data(encoded)?;
+    zstd::stream::decode_all(&compressed[..]).ok()
+}
+
+pub fn decode_base58_data(encoded: &String) -> Option<Vec<u8>> {
+    bs58::decode(encoded).into_vec().ok()
+}
+
+/// Encoding of account data requested from the RPC, for fetches and subscriptions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountEncoding {
+    pub encoding: UiAccountEncoding,
+    /// Only this window of the account data is sent
+    pub data_slice: Option<UiDataSliceConfig>,
+}
+
+impl Default for AccountEncoding {
+    fn default() -> Self {
+        Self::base64()
+    }
+}
+
+impl AccountEncoding {
+    pub fn base64() -> Self {
+        Self {
+            encoding: UiAccountEncoding::Base64,
+            data_slice: None,
+        }
+    }
+
+    /// Much smaller payloads for large sparse accounts like order books
+    pub fn base64_zstd() -> Self {
+        Self {
+            encoding: UiAccountEncoding::Base64Zstd,
+            data_slice: None,
+        }
+    }
+
+    /// Limited to 128 bytes of account data by the RPC
+    pub fn base58() -> Self {
+        Self {
+            encoding: UiAccountEncoding::Base58,
+            data_slice: None,
+        }
+    }
+
+    pub fn with_data_slice(self, offset: usize, length: usize) -> Self {
+        Self {
+            data_slice: Some(UiDataSliceConfig { offset, length }),
+            ..self
+        }
+    }
+
+    pub fn account_info_config(&self, commitment: CommitmentConfig) -> RpcAccountInfoConfig {
+        RpcAccountInfoConfig {
+            encoding: Some(self.encoding),
+            data_slice: self.data_slice,
+            commitment: Some(commitment),
+            min_context_slot: None,
+        }
+    }
+}
+
 pub enum AccountData<'a> {
     Serialized(&'a Vec<u8>),
     Encoded(&'a UiAccountData),
@@ -31,17 +98,47 @@ impl<'a> AccountData<'a> {
         let res = match encoded_data {
             UiAccountData::Binary(encoded_data, encoding) => match encoding {
                 UiAccountEncoding::Base64 => decode_base64_data(encoded_data),
+                UiAccountEncoding::Base64Zstd => decode_base64_zstd_data(encoded_data),
+                UiAccountEncoding::Base58 => decode_base58_data(encoded_data),
                 _ => None,
             },
-            _ => None,
+            UiAccountData::LegacyBinary(encoded_data) => decode_base58_data(encoded_data),
+            UiAccountData::Json(_) => None,
         };
         res.ok_or(Error::UnableToDecode)
     }
 
+    pub fn bytes(&self) -> Result<Cow<'a, [u8]>, Error> {
+        match self {
+            Self::Encoded(encoded) => Ok(Cow::Owned(Self::decode(encoded)?)),
+            Self::Serialized(bytes) => Ok(Cow::Borrowed(&bytes[..])),
+        }
+    }
+
+    /// `length` bytes at `offset` of the full account data, from data requested
+    /// with `data_slice`
+    pub fn read_at(
+        &self,
+        data_slice: &UiDataSliceConfig,
+        offset: usize,
+        length: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let start = offset
+            .checked_sub(data_slice.offset)
+            .ok_or(Error::UnableToDecode)?;
+
+        self.bytes()?
+            .get(start..start + length)
+            .map(|bytes| bytes.to_vec())
+            .ok_or(Error::UnableToDecode)
+    }
+
     pub fn deserialize<T: AccountDeserialize + Discriminator>(data: &Vec<u8>) -> Result<T, Error> {
         T::try_deserialize(&mut &data[..]).map_err(|_| Error::UnableToDeserialize)
     }
 
+    /// Data requested with a `data_slice` only parses when the slice starts at 0 and
+    /// covers the whole struct
     pub fn parse<T: AccountDeserialize + Discriminator>(&self) -> Result<T, Error> {
         match self {
             Self::Encoded(encoded) => {
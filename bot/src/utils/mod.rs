@@ -1,3 +1,4 @@
+pub mod account_log;
 pub mod deser;
 pub mod transaction;
 pub mod websocket_client;
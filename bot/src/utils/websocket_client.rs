@@ -1,19 +1,29 @@
-use std::{collections::HashMap, future::ready, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    future::ready,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use futures::{SinkExt, StreamExt};
 use futures_util::stream::BoxStream;
+use rand::Rng;
 use serde::de::DeserializeOwned;
 use serde_json::{json, Map, Value};
-use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_account_decoder::UiAccount;
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig,
+    RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
 use solana_rpc_client_api::{
     error_object::RpcErrorObject,
-    response::{Response, RpcKeyedAccount, SlotInfo},
+    response::{Response, RpcKeyedAccount, RpcLogsResponse, RpcSignatureResult, SlotInfo},
 };
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Signature};
 use tokio::{
-    sync::{broadcast, mpsc, Mutex},
+    sync::{broadcast, mpsc, watch},
     task::JoinHandle,
-    time::sleep,
+    time::{interval, sleep, timeout, MissedTickBehavior},
 };
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_tungstenite::{
@@ -28,8 +38,10 @@ use tokio_tungstenite::{
 pub enum WebsocketError {
     AlreadyConnected,
     NotConnected,
+    RequestTimeout,
+    /// The connection dropped before the request was confirmed
+    ConnectionLost,
     SubscriptionFailed(String),
-    ConnectionCouldNotBeEstablished(String),
 
     ConnectionError(tokio_tungstenite::tungstenite::Error),
     MessageParseError(serde_json::error::Error),
@@ -40,10 +52,9 @@ impl ToString for WebsocketError {
         match self {
             Self::AlreadyConnected => "AlreadyConnected".to_string(),
             Self::NotConnected => "NotConnected".to_string(),
+            Self::RequestTimeout => "RequestTimeout".to_string(),
+            Self::ConnectionLost => "ConnectionLost".to_string(),
             Self::SubscriptionFailed(msg) => format!("SubscriptionFailed: {}", msg),
-            Self::ConnectionCouldNotBeEstablished(msg) => {
-                format!("ConnectionCouldNotBeEstablished: {}", msg)
-            }
             Self::ConnectionError(e) => format!("SendError: {}", e.to_string()),
             Self::MessageParseError(e) => format!("MessageParseError: {}", e.to_string()),
         }
@@ -65,25 +76,62 @@ impl From<serde_json::error::Error> for WebsocketError {
 #[derive(Clone, PartialEq, Debug)]
 enum SubscribeParams {
     Slot,
+    Root,
     Program {
         program_id: Pubkey,
         config: RpcProgramAccountsConfig,
     },
+    Account {
+        pubkey: Pubkey,
+        config: RpcAccountInfoConfig,
+    },
+    Logs {
+        filter: RpcTransactionLogsFilter,
+        config: RpcTransactionLogsConfig,
+    },
+    Signature {
+        signature: Signature,
+        config: RpcSignatureSubscribeConfig,
+    },
 }
 
 impl SubscribeParams {
-    pub fn notification_into_unsub_method(method: String) -> &'static str {
-        match method.as_str() {
-            "slotNotification" => "slotUnsubscribe",
-            "programNotification" => "programUnsubscribe",
-            _ => unreachable!(),
+    fn method(&self) -> &'static str {
+        match self {
+            Self::Slot => "slotSubscribe",
+            Self::Root => "rootSubscribe",
+            Self::Program { .. } => "programSubscribe",
+            Self::Account { .. } => "accountSubscribe",
+            Self::Logs { .. } => "logsSubscribe",
+            Self::Signature { .. } => "signatureSubscribe",
+        }
+    }
+
+    /// Unsubscribe method matching a `<name>Subscribe` or `<name>Notification` method
+    pub fn into_unsub_method(method: &str) -> Option<&'static str> {
+        let name = method
+            .strip_suffix("Subscribe")
+            .or_else(|| method.strip_suffix("Notification"))?;
+
+        match name {
+            "slot" => Some("slotUnsubscribe"),
+            "root" => Some("rootUnsubscribe"),
+            "program" => Some("programUnsubscribe"),
+            "account" => Some("accountUnsubscribe"),
+            "logs" => Some("logsUnsubscribe"),
+            "signature" => Some("signatureUnsubscribe"),
+            _ => None,
         }
     }
 
-    pub fn build_unsubscribe_request(method: String, subscription_id: u64) -> String {
+    pub fn build_unsubscribe_request(
+        method: &str,
+        request_id: u64,
+        subscription_id: u64,
+    ) -> String {
         json!({
             "jsonrpc": "2.0",
-            "id": 1,
+            "id": request_id,
             "method": method,
             "params": [subscription_id]
         })
@@ -91,53 +139,81 @@ impl SubscribeParams {
     }
 
     pub fn build_subscribe_request_and_method(&self, request_id: u64) -> (String, String) {
-        let m: String;
-        let r = match self {
-            Self::Slot => {
-                m = "slotSubscribe".to_string();
-                json!({ "jsonrpc": "2.0", "id": request_id, "method": m })
-            }
-            Self::Program { program_id, config } => {
-                m = "programSubscribe".to_string();
-                json!({
-                    "jsonrpc": "2.0",
-                    "id": request_id,
-                    "method": m,
-                    "params": [
-                        program_id.to_string(),
-                        config,
-                    ],
-                })
-            }
+        let m = self.method().to_string();
+        let params = match self {
+            Self::Slot | Self::Root => None,
+            Self::Program { program_id, config } => Some(json!([program_id.to_string(), config])),
+            Self::Account { pubkey, config } => Some(json!([pubkey.to_string(), config])),
+            Self::Logs { filter, config } => Some(json!([filter, config])),
+            Self::Signature { signature, config } => Some(json!([signature.to_string(), config])),
+        };
+
+        let r = match params {
+            Some(params) => json!({
+                "jsonrpc": "2.0",
+                "id": request_id,
+                "method": m,
+                "params": params,
+            }),
+            None => json!({ "jsonrpc": "2.0", "id": request_id, "method": m }),
         }
         .to_string();
         (r, m)
     }
 }
 
-pub type NotificationSender = mpsc::UnboundedSender<Value>;
-pub type NotificationReceiver = mpsc::UnboundedReceiver<Value>;
+/// Item of a subscription stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification<T> {
+    Value(T),
+    /// The connection was lost and the subscription replayed on a new one, notifications
+    /// sent in between were missed so anything derived from the stream should be refetched
+    Gap,
+}
+
+pub type NotificationSender = mpsc::UnboundedSender<Notification<Value>>;
+pub type NotificationReceiver = mpsc::UnboundedReceiver<Notification<Value>>;
 pub type SubscriptionStatusSender =
     mpsc::Sender<Result<(u64, NotificationReceiver), WebsocketError>>;
 pub type UnsubscriptionStatusSender = mpsc::Sender<()>;
 
-type SubscribeRequest = (SubscribeParams, SubscriptionStatusSender);
+type SubscribeRequest = (SubscribeParams, Option<Duration>, SubscriptionStatusSender);
 pub type UnsubscribeRequest = (u64, UnsubscriptionStatusSender);
 
-pub type SubscribeResponse<'a, T> = (u64, BoxStream<'a, T>);
+/// Subscription id stays the same across reconnects
+pub type SubscribeResponse<'a, T> = (u64, BoxStream<'a, Notification<T>>);
 
-pub struct PendingSubscription {
-    method: String,
-    status_sender: SubscriptionStatusSender,
+enum PendingRequest {
+    Subscribe {
+        params: SubscribeParams,
+        silence_timeout: Option<Duration>,
+        status_sender: SubscriptionStatusSender,
+    },
+    /// Replay of an active subscription after a reconnect, `method` unsubscribes the
+    /// confirmed replay if the subscription is gone by then
+    Resubscribe {
+        subscription_id: u64,
+        method: &'static str,
+    },
+    Unsubscribe {
+        status_sender: UnsubscriptionStatusSender,
+    },
 }
 
 #[derive(Debug)]
 pub struct ActiveSubscription {
-    method: String,
+    params: SubscribeParams,
     notification_sender: NotificationSender,
+    /// Id assigned by the server on the current connection, `None` until (re)confirmed
+    server_id: Option<u64>,
+    /// Resubscribed when no notification is received for this long, for subscriptions
+    /// that are expected to be busy. A subscription the server silently dropped is
+    /// otherwise indistinguishable from a quiet one
+    silence_timeout: Option<Duration>,
+    last_notification_at: Instant,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum ConnectionStatus {
     Connected,
     Reconnecting,
@@ -151,9 +227,63 @@ impl Default for ConnectionStatus {
     }
 }
 
+/// Exponential delay between connection attempts, half of it is randomized so
+/// clients dropped at the same time do not reconnect in lockstep
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(250),
+            max: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Pings are sent every `interval`, the connection is considered dead and reconnected
+/// after `max_missed` pings without a pong
+#[derive(Clone, Copy, Debug)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub max_missed: u32,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            max_missed: 3,
+        }
+    }
+}
+
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Connections which stayed up for this long reset the backoff
+pub const STABLE_CONNECTION: Duration = Duration::from_secs(30);
+
+impl Backoff {
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max);
+
+        delay / 2 + delay.mul_f64(rand::thread_rng().gen_range(0.0..0.5))
+    }
+}
+
 pub struct WebsocketClient {
-    url: String,
-    connection_status: Mutex<ConnectionStatus>,
+    /// Endpoints are rotated when a connection attempt fails or a connection drops
+    urls: Vec<String>,
+    backoff: Backoff,
+    heartbeat: Heartbeat,
+    /// Applies to subscribe and unsubscribe requests
+    request_timeout: Duration,
+    connection_status: watch::Sender<ConnectionStatus>,
 
     unsubscribe_sender: broadcast::Sender<UnsubscribeRequest>,
     subscribe_sender: broadcast::Sender<SubscribeRequest>,
@@ -161,20 +291,45 @@ pub struct WebsocketClient {
 }
 
 impl WebsocketClient {
-    pub fn new(url: String) -> Self {
+    pub fn new(urls: Vec<String>) -> Self {
+        assert!(!urls.is_empty(), "At least one websocket url is required");
+
         let (subscribe_sender, _) = broadcast::channel(100);
         let (unsubscribe_sender, _) = broadcast::channel(100);
         let (reconnect_sender, _) = broadcast::channel(1);
+        let (connection_status, _) = watch::channel(ConnectionStatus::default());
 
         Self {
-            connection_status: Default::default(),
-            url,
+            connection_status,
+            urls,
+            backoff: Backoff::default(),
+            heartbeat: Heartbeat::default(),
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
             subscribe_sender,
             unsubscribe_sender,
             reconnect_sender,
         }
     }
 
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn connection_status(&self) -> watch::Receiver<ConnectionStatus> {
+        self.connection_status.subscribe()
+    }
+
     pub async fn reconnect(&self) -> Result<(), WebsocketError> {
         let (status_sender, mut status_receiver) = mpsc::channel(1);
 
@@ -191,20 +346,72 @@ impl WebsocketClient {
         &self,
         program_id: Pubkey,
         config: RpcProgramAccountsConfig,
-    ) -> Result<SubscribeResponse<Response<RpcKeyedAccount>>, WebsocketError> {
-        self.subscribe(SubscribeParams::Program { program_id, config })
+        silence_timeout: Option<Duration>,
+    ) -> Result<SubscribeResponse<'static, Response<RpcKeyedAccount>>, WebsocketError> {
+        self.subscribe(
+            SubscribeParams::Program { program_id, config },
+            silence_timeout,
+        )
+        .await
+    }
+
+    pub async fn account_subscribe(
+        &self,
+        pubkey: Pubkey,
+        config: RpcAccountInfoConfig,
+        silence_timeout: Option<Duration>,
+    ) -> Result<SubscribeResponse<'static, Response<UiAccount>>, WebsocketError> {
+        self.subscribe(SubscribeParams::Account { pubkey, config }, silence_timeout)
             .await
     }
 
-    pub async fn slot_subscribe(&self) -> Result<SubscribeResponse<SlotInfo>, WebsocketError> {
-        self.subscribe(SubscribeParams::Slot).await
+    pub async fn slot_subscribe(
+        &self,
+        silence_timeout: Option<Duration>,
+    ) -> Result<SubscribeResponse<'static, SlotInfo>, WebsocketError> {
+        self.subscribe(SubscribeParams::Slot, silence_timeout).await
     }
 
+    pub async fn root_subscribe(
+        &self,
+        silence_timeout: Option<Duration>,
+    ) -> Result<SubscribeResponse<'static, Slot>, WebsocketError> {
+        self.subscribe(SubscribeParams::Root, silence_timeout).await
+    }
+
+    pub async fn logs_subscribe(
+        &self,
+        filter: RpcTransactionLogsFilter,
+        config: RpcTransactionLogsConfig,
+        silence_timeout: Option<Duration>,
+    ) -> Result<SubscribeResponse<'static, Response<RpcLogsResponse>>, WebsocketError> {
+        self.subscribe(SubscribeParams::Logs { filter, config }, silence_timeout)
+            .await
+    }
+
+    /// The stream ends after the processed notification, the server closes the subscription
+    pub async fn signature_subscribe(
+        &self,
+        signature: &Signature,
+        config: RpcSignatureSubscribeConfig,
+    ) -> Result<SubscribeResponse<'static, Response<RpcSignatureResult>>, WebsocketError> {
+        self.subscribe(
+            SubscribeParams::Signature {
+                signature: *signature,
+                config,
+            },
+            None,
+        )
+        .await
+    }
+
+    /// `silence_timeout` resubscribes when no notification arrives for that long
     async fn subscribe<'a, T: DeserializeOwned + Send + 'a>(
         &self,
         params: SubscribeParams,
-    ) -> Result<(u64, BoxStream<'a, T>), WebsocketError> {
-        let status = self.connection_status.lock().await.clone();
+        silence_timeout: Option<Duration>,
+    ) -> Result<SubscribeResponse<'a, T>, WebsocketError> {
+        let status = *self.connection_status.borrow();
 
         let (subscription_id, receiver) = match status {
             ConnectionStatus::Disconnected => {
@@ -214,29 +421,38 @@ impl WebsocketClient {
                 let (status_sender, mut status_receiver) = mpsc::channel(1);
 
                 self.subscribe_sender
-                    .send((params, status_sender))
+                    .send((params, silence_timeout, status_sender))
                     .map_err(|_| WebsocketError::NotConnected)?;
 
-                if let Some(res) = status_receiver.recv().await {
-                    res?
-                } else {
-                    return Err(WebsocketError::NotConnected);
+                match timeout(self.request_timeout, status_receiver.recv()).await {
+                    Ok(Some(res)) => res?,
+                    Ok(None) => return Err(WebsocketError::NotConnected),
+                    Err(_) => return Err(WebsocketError::RequestTimeout),
                 }
             }
         };
 
         let stream = UnboundedReceiverStream::new(receiver)
-            .filter_map(|value| ready(serde_json::from_value::<T>(value).ok()))
+            .filter_map(|notification| {
+                ready(match notification {
+                    Notification::Value(value) => serde_json::from_value::<T>(value)
+                        .ok()
+                        .map(Notification::Value),
+                    Notification::Gap => Some(Notification::Gap),
+                })
+            })
             .boxed();
 
         Ok((subscription_id, stream))
     }
 
     pub async fn unsubscribe(&self, subscription_id: u64) {
-        let status = self.connection_status.lock().await.clone();
+        let status = *self.connection_status.borrow();
 
         match status {
-            ConnectionStatus::Connected => {
+            ConnectionStatus::Disconnected => (),
+            // also while reconnecting, the subscription would be replayed otherwise
+            _ => {
                 let (status_sender, mut status_receiver) = mpsc::channel(1);
 
                 self.unsubscribe_sender
@@ -244,9 +460,10 @@ impl WebsocketClient {
                     .ok();
 
                 // if channel is closed, subscription is closed too
-                status_receiver.recv().await;
+                timeout(self.request_timeout, status_receiver.recv())
+                    .await
+                    .ok();
             }
-            _ => (),
         }
     }
 }
@@ -254,194 +471,419 @@ impl WebsocketClient {
 pub async fn create_persisted_websocket_connection(
     client: Arc<WebsocketClient>,
 ) -> Result<JoinHandle<Result<(), WebsocketError>>, WebsocketError> {
-    let status = client.connection_status.lock().await;
-    if *status == ConnectionStatus::Connected || *status == ConnectionStatus::Reconnecting {
+    if *client.connection_status.borrow() != ConnectionStatus::Disconnected {
         return Err(WebsocketError::AlreadyConnected);
     }
-    drop(status);
 
     let handle: JoinHandle<Result<(), WebsocketError>> = tokio::spawn(async move {
         type RequestId = u64;
         type SubscriptionId = u64;
+        type ServerSubscriptionId = u64;
 
-        // TODO:
-        // Can potentially remain pending forever if websocket reconnects
-        // while the subscription is pending
-        let mut pending_subscriptions: HashMap<RequestId, PendingSubscription> = HashMap::new();
+        // Subscriptions outlive connections and are replayed after each reconnect,
+        // consumers only ever see the client side ids
+        let mut active_subscriptions: HashMap<SubscriptionId, ActiveSubscription> = HashMap::new();
+        let mut next_subscription_id: SubscriptionId = 1;
         let mut pending_reconnect: Option<mpsc::Sender<()>> = None;
 
         let mut subscribe_receiver = client.subscribe_sender.subscribe();
         let mut unsubscribe_receiver = client.unsubscribe_sender.subscribe();
         let mut reconnect_receiver = client.reconnect_sender.subscribe();
 
+        let mut endpoint = 0;
+        // kept across connections, servers closing right after accepting are backed off too
+        let mut attempt = 0;
+
         loop {
             let mut request_id: RequestId = 1;
 
-            println!("Connecting to ws");
-            let mut conn_status = client.connection_status.lock().await;
-            let (mut ws, _response) = connect_async(&client.url)
-                .await
-                .map_err(|e| WebsocketError::ConnectionCouldNotBeEstablished(e.to_string()))?;
-            *conn_status = ConnectionStatus::Connected;
-            drop(conn_status);
+            let (mut ws, _response) = loop {
+                let url = &client.urls[endpoint];
+
+                println!("Connecting to ws {}", url);
+                match connect_async(url).await {
+                    Ok(connection) => break connection,
+                    Err(e) => {
+                        let delay = client.backoff.delay(attempt);
+                        println!(
+                            "Unable to connect to ws {}: {}, retrying in {}ms",
+                            url,
+                            e.to_string(),
+                            delay.as_millis()
+                        );
+
+                        endpoint = (endpoint + 1) % client.urls.len();
+                        attempt = attempt.saturating_add(1);
+                        sleep(delay).await;
+                    }
+                }
+            };
+            client
+                .connection_status
+                .send_replace(ConnectionStatus::Connected);
+            let connected_at = Instant::now();
 
-            if let Some(status_sender) = pending_reconnect {
+            if let Some(status_sender) = pending_reconnect.take() {
                 status_sender.send(()).await.ok();
-                pending_reconnect = None;
             }
 
-            let mut active_subscriptions: HashMap<SubscriptionId, ActiveSubscription> =
+            let mut pending_requests: HashMap<RequestId, (Instant, PendingRequest)> =
                 HashMap::new();
-            let mut pending_unsubscriptions: HashMap<RequestId, UnsubscriptionStatusSender> =
-                HashMap::default();
-
-            loop {
-                tokio::select! {
-                    Ok(status_sender) = reconnect_receiver.recv() =>
-                    {
-                        #[allow(unused_assignments)]
+            let mut subscription_ids: HashMap<ServerSubscriptionId, SubscriptionId> =
+                HashMap::new();
+
+            // Errors only end the current connection, the client then reconnects
+            let res: Result<(), WebsocketError> = async {
+                for (subscription_id, subscription) in active_subscriptions.iter_mut() {
+                    subscription.server_id = None;
+
+                    let (req, method) = subscription
+                        .params
+                        .build_subscribe_request_and_method(request_id);
+                    ws.send(Message::Text(req)).await?;
+                    println!(
+                        "Resubscribing {} {}: {}",
+                        &method, subscription_id, request_id
+                    );
+                    pending_requests.insert(
+                        request_id,
+                        (
+                            Instant::now(),
+                            PendingRequest::Resubscribe {
+                                subscription_id: *subscription_id,
+                                method: subscription.params.method(),
+                            },
+                        ),
+                    );
+                    request_id += 1;
+                }
+
+                let mut heartbeat = interval(client.heartbeat.interval);
+                heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                let mut missed_heartbeats = 0;
+
+                loop {
+                    tokio::select! {
+                        Ok(status_sender) = reconnect_receiver.recv() =>
                         {
                             pending_reconnect = Some(status_sender);
+
+                            let frame = CloseFrame { code: CloseCode::Normal, reason: "".into() };
+                            ws.send(Message::Close(Some(frame))).await?;
+                            ws.flush().await?;
+
+                            break;
                         }
+                        Ok((subscription_id, status_sender)) = unsubscribe_receiver.recv() => {
+                            let Some(ActiveSubscription { params, server_id, .. }) = active_subscriptions.remove(&subscription_id) else {
+                                status_sender.send(()).await.ok();
+                                continue;
+                            };
 
-                        let frame = CloseFrame { code: CloseCode::Normal, reason: "".into() };
-                        ws.send(Message::Close(Some(frame))).await?;
-                        ws.flush().await?;
+                            // not confirmed on this connection, a pending replay is unsubscribed once confirmed
+                            let Some(server_id) = server_id else {
+                                status_sender.send(()).await.ok();
+                                continue;
+                            };
+                            subscription_ids.remove(&server_id);
 
-                        break;
-                    }
-                    Ok((subscription_id, status_sender)) = unsubscribe_receiver.recv() => {
-                        let Some(ActiveSubscription { method, .. }) = active_subscriptions.remove(&subscription_id) else {
-                            status_sender.send(()).await.ok();
-                            continue;
-                        };
+                            let Some(unsub_method) = SubscribeParams::into_unsub_method(params.method()) else {
+                                status_sender.send(()).await.ok();
+                                continue;
+                            };
 
-                        println!("Unsubcribing {}: rid {}", subscription_id, request_id);
+                            println!("Unsubcribing {}: rid {}", subscription_id, request_id);
 
-                        let req = SubscribeParams::build_unsubscribe_request(method, request_id);
-                        ws.send(Message::Text(req)).await?;
+                            let req = SubscribeParams::build_unsubscribe_request(unsub_method, request_id, server_id);
+                            ws.send(Message::Text(req)).await?;
+                            pending_requests.insert(request_id, (Instant::now(), PendingRequest::Unsubscribe { status_sender }));
 
-                        request_id += 1;
-                    }
-                    Ok((subscribe_params, status_sender)) = subscribe_receiver.recv() => {
-                        let (req, method) = subscribe_params.build_subscribe_request_and_method(request_id);
-                        ws.send(Message::Text(req)).await?;
-                        println!("Subscribing {}: {}", &method, request_id);
-                        pending_subscriptions.insert(request_id, PendingSubscription { method, status_sender });
-                        request_id += 1;
-                    }
-                    _ = sleep(Duration::from_secs(5)) => {
-                        ws.send(Message::Ping(vec![])).await?;
-                    }
-                    Some(msg) = ws.next() => {
-                        let Ok(msg) = msg else {
-                            println!("Websocket message error: {}", msg.err().unwrap().to_string());
-                            break;
-                        };
-                        let text = match msg {
-                            Message::Text(v) => v,
-                            Message::Ping(data) => {
-                                ws.send(Message::Pong(data)).await?;
-                                continue;
-                            }
-                            Message::Close(reason) => {
-                                dbg!(reason);
+                            request_id += 1;
+                        }
+                        Ok((params, silence_timeout, status_sender)) = subscribe_receiver.recv() => {
+                            let (req, method) = params.build_subscribe_request_and_method(request_id);
+                            ws.send(Message::Text(req)).await?;
+                            println!("Subscribing {}: {}", &method, request_id);
+                            pending_requests.insert(request_id, (Instant::now(), PendingRequest::Subscribe { params, silence_timeout, status_sender }));
+                            request_id += 1;
+                        }
+                        _ = heartbeat.tick() => {
+                            // half-open connections do not error, they just stop answering
+                            if missed_heartbeats >= client.heartbeat.max_missed {
+                                println!("Missed {} heartbeats, reconnecting", missed_heartbeats);
                                 break;
                             }
-                            _ => {
-                                continue;
-                            }
-                        };
-                        let response: Map<String, Value> = serde_json::from_str(&text)?;
-
-                        match response.get("id").map(|id| id.as_u64()).flatten() {
-                            Some(r_id) => {
-                                let err = response.get("error").map(|error_object| {
-                                    match serde_json::from_value::<RpcErrorObject>(error_object.clone()) {
-                                        Ok(rpc_error_object) => {
-                                            format!("{} ({})",  rpc_error_object.message, rpc_error_object.code)
-                                        }
-                                        Err(err) => format!(
-                                            "Failed to deserialize RPC error response: {} [{}]",
-                                            serde_json::to_string(error_object).unwrap(),
-                                            err
-                                        )
+                            ws.send(Message::Ping(vec![])).await?;
+                            missed_heartbeats += 1;
+
+                            let expired = pending_requests
+                                .iter()
+                                .filter(|(_, (sent_at, _))| sent_at.elapsed() > client.request_timeout)
+                                .map(|(r_id, _)| *r_id)
+                                .collect::<Vec<RequestId>>();
+
+                            for r_id in expired {
+                                match pending_requests.remove(&r_id).map(|(_, request)| request) {
+                                    Some(PendingRequest::Subscribe { status_sender, .. }) => {
+                                        status_sender.send(Err(WebsocketError::RequestTimeout)).await.ok();
                                     }
-                                });
+                                    Some(PendingRequest::Unsubscribe { status_sender }) => {
+                                        status_sender.send(()).await.ok();
+                                    }
+                                    Some(PendingRequest::Resubscribe { subscription_id, .. }) => {
+                                        println!("Resubscription {} timed out, reconnecting", subscription_id);
+                                        return Ok(());
+                                    }
+                                    None => (),
+                                }
+                            }
 
-                                if let Some(status_sender) = pending_unsubscriptions.remove(&r_id) {
-                                    println!("confirming unsub {}", r_id);
-                                    status_sender.send(()).await.ok();
+                            for (subscription_id, subscription) in active_subscriptions.iter_mut() {
+                                let is_silent = subscription
+                                    .silence_timeout
+                                    .is_some_and(|silence_timeout| subscription.last_notification_at.elapsed() > silence_timeout);
+                                let Some(server_id) = subscription.server_id.filter(|_| is_silent) else {
                                     continue;
+                                };
+
+                                println!("Subscription {} is silent, resubscribing", subscription_id);
+
+                                subscription_ids.remove(&server_id);
+                                subscription.server_id = None;
+                                subscription.last_notification_at = Instant::now();
+
+                                if let Some(unsub_method) = SubscribeParams::into_unsub_method(subscription.params.method()) {
+                                    let req = SubscribeParams::build_unsubscribe_request(unsub_method, request_id, server_id);
+                                    ws.send(Message::Text(req)).await?;
+                                    request_id += 1;
                                 }
 
-                                if let Some(PendingSubscription { method, status_sender }) = pending_subscriptions.remove(&r_id) {
-                                    match err {
-                                        Some(msg) => {
-                                            status_sender.send(Err(WebsocketError::SubscriptionFailed(format!("{}: {}", msg, text.clone())))).await.ok();
-                                            continue;
+                                let (req, _) = subscription.params.build_subscribe_request_and_method(request_id);
+                                ws.send(Message::Text(req)).await?;
+                                pending_requests.insert(
+                                    request_id,
+                                    (Instant::now(), PendingRequest::Resubscribe { subscription_id: *subscription_id, method: subscription.params.method() }),
+                                );
+                                request_id += 1;
+                            }
+                        }
+                        Some(msg) = ws.next() => {
+                            let Ok(msg) = msg else {
+                                println!("Websocket message error: {}", msg.err().unwrap().to_string());
+                                break;
+                            };
+                            let text = match msg {
+                                Message::Text(v) => v,
+                                Message::Ping(data) => {
+                                    ws.send(Message::Pong(data)).await?;
+                                    continue;
+                                }
+                                Message::Pong(_) => {
+                                    missed_heartbeats = 0;
+                                    continue;
+                                }
+                                Message::Close(reason) => {
+                                    dbg!(reason);
+                                    break;
+                                }
+                                _ => {
+                                    continue;
+                                }
+                            };
+                            let Ok(response) = serde_json::from_str::<Map<String, Value>>(&text) else {
+                                println!("Unable to parse ws message: {}", text);
+                                continue;
+                            };
+
+                            match response.get("id").map(|id| id.as_u64()).flatten() {
+                                Some(r_id) => {
+                                    let err = response.get("error").map(|error_object| {
+                                        match serde_json::from_value::<RpcErrorObject>(error_object.clone()) {
+                                            Ok(rpc_error_object) => {
+                                                format!("{} ({})",  rpc_error_object.message, rpc_error_object.code)
+                                            }
+                                            Err(err) => format!(
+                                                "Failed to deserialize RPC error response: {} [{}]",
+                                                serde_json::to_string(error_object).unwrap(),
+                                                err
+                                            )
                                         }
-                                        _ => {
-                                            let Some(s_id) = response.get("result").map(|id| id.as_u64()).flatten() else {
-                                                status_sender.send(Err(WebsocketError::SubscriptionFailed(format!("Invalid result field: {}", text.clone())))).await.ok();
-                                                continue;
+                                    });
+
+                                    let s_id = match err {
+                                        Some(msg) => Err(WebsocketError::SubscriptionFailed(format!("{}: {}", msg, text.clone()))),
+                                        None => response
+                                            .get("result")
+                                            .map(|id| id.as_u64())
+                                            .flatten()
+                                            .ok_or_else(|| WebsocketError::SubscriptionFailed(format!("Invalid result field: {}", text.clone()))),
+                                    };
+
+                                    match pending_requests.remove(&r_id).map(|(_, request)| request) {
+                                        Some(PendingRequest::Unsubscribe { status_sender }) => {
+                                            println!("confirming unsub {}", r_id);
+                                            status_sender.send(()).await.ok();
+                                        }
+                                        Some(PendingRequest::Subscribe { params, silence_timeout, status_sender }) => {
+                                            let s_id = match s_id {
+                                                Ok(s_id) => s_id,
+                                                Err(e) => {
+                                                    status_sender.send(Err(e)).await.ok();
+                                                    continue;
+                                                }
                                             };
 
-                                            println!("Confirmed subscription {}, {}", &method, r_id);
+                                            println!("Confirmed subscription {}, {}", params.method(), r_id);
+
+                                            let subscription_id = next_subscription_id;
+                                            next_subscription_id += 1;
 
                                             let (notification_sender, notification_receiver) = mpsc::unbounded_channel();
 
-                                            if status_sender.send(Ok((s_id, notification_receiver))).await.is_ok() {
-                                                active_subscriptions.insert(s_id, ActiveSubscription { method, notification_sender });
+                                            if status_sender.send(Ok((subscription_id, notification_receiver))).await.is_ok() {
+                                                subscription_ids.insert(s_id, subscription_id);
+                                                active_subscriptions.insert(subscription_id, ActiveSubscription {
+                                                    params,
+                                                    notification_sender,
+                                                    server_id: Some(s_id),
+                                                    silence_timeout,
+                                                    last_notification_at: Instant::now(),
+                                                });
                                             }
                                         }
-                                    }
-                                    continue;
-                                }
-                            }
-                            None => {
-                                let Some(Value::Object(params)) = response.get("params") else {
-                                    continue;
-                                };
+                                        Some(PendingRequest::Resubscribe { subscription_id, method }) => {
+                                            let s_id = match s_id {
+                                                Ok(s_id) => s_id,
+                                                Err(e) => {
+                                                    // dropping the sender ends the consumer's stream
+                                                    println!("Resubscription {} failed: {}", subscription_id, e.to_string());
+                                                    active_subscriptions.remove(&subscription_id);
+                                                    continue;
+                                                }
+                                            };
 
-                                let s_id = params.get("subscription").map(|id| id.as_u64()).flatten();
-                                let result = params.get("result");
-                                let method = response.get("method").map(|m| m.as_str()).flatten();
+                                            if let Some(subscription) = active_subscriptions.get_mut(&subscription_id) {
+                                                if subscription.notification_sender.send(Notification::Gap).is_ok() {
+                                                    println!("Confirmed resubscription {}, {}", subscription_id, r_id);
+                                                    subscription.server_id = Some(s_id);
+                                                    subscription.last_notification_at = Instant::now();
+                                                    subscription_ids.insert(s_id, subscription_id);
+                                                    continue;
+                                                }
 
-                                match (s_id, result, method) {
-                                    (Some(s_id), Some(result), Some(method)) => {
-                                        let mut should_unsub = false;
+                                                active_subscriptions.remove(&subscription_id);
+                                            }
 
-                                        if let Some(subscription) = active_subscriptions.get(&s_id) {
-                                            if !subscription.notification_sender.send(result.clone()).is_ok() {
-                                                println!("Subscription no longer active, remove");
-                                                active_subscriptions.remove(&s_id);
-                                                should_unsub = true;
+                                            // unsubscribed or dropped while the replay was pending
+                                            if let Some(unsub_method) = SubscribeParams::into_unsub_method(method) {
+                                                let req = SubscribeParams::build_unsubscribe_request(unsub_method, request_id, s_id);
+                                                ws.send(Message::Text(req)).await?;
+                                                request_id += 1;
                                             }
-                                        } else {
-                                            should_unsub = true;
                                         }
+                                        None => (),
+                                    }
+                                }
+                                None => {
+                                    let Some(Value::Object(params)) = response.get("params") else {
+                                        continue;
+                                    };
+
+                                    let s_id = params.get("subscription").map(|id| id.as_u64()).flatten();
+                                    let result = params.get("result");
+                                    let method = response.get("method").map(|m| m.as_str()).flatten();
+
+                                    match (s_id, result, method) {
+                                        (Some(s_id), Some(result), Some(method)) => {
+                                            let mut should_unsub = false;
+                                            let mut should_remove = false;
+
+                                            let subscription_id = subscription_ids.get(&s_id).copied();
+                                            match subscription_id.and_then(|id| active_subscriptions.get_mut(&id)) {
+                                                Some(subscription) => {
+                                                    subscription.last_notification_at = Instant::now();
+
+                                                    if !subscription.notification_sender.send(Notification::Value(result.clone())).is_ok() {
+                                                        println!("Subscription no longer active, remove");
+                                                        should_unsub = true;
+                                                    } else if method == "signatureNotification" && result["value"] != json!("receivedSignature") {
+                                                        // closed by the server after the processed notification
+                                                        should_remove = true;
+                                                    }
+                                                }
+                                                None => should_unsub = true,
+                                            }
 
-                                        if should_unsub {
-                                            println!("Subscription no longer active, unsub");
-                                            let unsub_method = SubscribeParams::notification_into_unsub_method(method.to_string());
-                                            let req = SubscribeParams::build_unsubscribe_request(unsub_method.to_string(), request_id);
+                                            if let (true, Some(subscription_id)) = (should_unsub || should_remove, subscription_id) {
+                                                subscription_ids.remove(&s_id);
+                                                active_subscriptions.remove(&subscription_id);
+                                            }
 
-                                            ws.send(Message::Text(req)).await?;
+                                            let unsub_method = SubscribeParams::into_unsub_method(method);
+                                            if let (true, Some(unsub_method)) = (should_unsub, unsub_method) {
+                                                println!("Subscription no longer active, unsub");
+                                                let req = SubscribeParams::build_unsubscribe_request(unsub_method, request_id, s_id);
 
-                                            request_id += 1;
+                                                ws.send(Message::Text(req)).await?;
+
+                                                request_id += 1;
+                                            }
                                         }
+                                        _ => ()
                                     }
-                                    _ => ()
                                 }
                             }
+
                         }
+                    }
+                }
 
+                Ok(())
+            }
+            .await;
+
+            if let Err(e) = res {
+                println!("Websocket connection error: {}", e.to_string());
+            }
+
+            // Requests in flight were lost with the connection, replays are sent again on the next one
+            for (_, (_, request)) in pending_requests.drain() {
+                match request {
+                    PendingRequest::Subscribe { status_sender, .. } => {
+                        status_sender
+                            .send(Err(WebsocketError::ConnectionLost))
+                            .await
+                            .ok();
+                    }
+                    // the server side subscription is gone with the connection
+                    PendingRequest::Unsubscribe { status_sender } => {
+                        status_sender.send(()).await.ok();
                     }
+                    PendingRequest::Resubscribe { .. } => (),
                 }
             }
 
-            *client.connection_status.lock().await = ConnectionStatus::Reconnecting;
+            client
+                .connection_status
+                .send_replace(ConnectionStatus::Reconnecting);
+
+            if connected_at.elapsed() >= STABLE_CONNECTION {
+                attempt = 0;
+            }
+
+            // requested reconnects are not drops, they reconnect to the same endpoint right away
+            if pending_reconnect.is_none() {
+                let delay = client.backoff.delay(attempt);
+                endpoint = (endpoint + 1) % client.urls.len();
+                attempt = attempt.saturating_add(1);
+
+                println!(
+                    "Websocket connection dropped, reconnecting to {} in {}ms",
+                    client.urls[endpoint],
+                    delay.as_millis()
+                );
+                sleep(delay).await;
+            }
         }
     });
 
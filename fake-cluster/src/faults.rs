@@ -0,0 +1,61 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// Fault injected into the next request of a method, or the next notification
+/// for notification methods such as `accountNotification`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Served normally, used to script faults after the first requests
+    Pass,
+    /// Never answered, notifications are not delivered
+    Drop,
+    /// Answered after the delay
+    Delay(Duration),
+    /// Answered with a JSON-RPC error
+    Error { code: i64, message: String },
+    /// The connection is closed instead of answering
+    Disconnect,
+}
+
+impl Fault {
+    /// Error returned by RPC nodes that are behind, ex: `minContextSlot` not reached
+    pub fn node_unhealthy() -> Self {
+        Self::Error {
+            code: -32005,
+            message: "Node is unhealthy".to_string(),
+        }
+    }
+}
+
+/// Scripted faults per method, consumed in order
+#[derive(Default)]
+pub struct Faults {
+    scripts: Mutex<HashMap<String, VecDeque<Fault>>>,
+}
+
+impl Faults {
+    pub fn script(&self, method: &str, faults: impl IntoIterator<Item = Fault>) {
+        self.scripts
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_default()
+            .extend(faults);
+    }
+
+    pub fn clear(&self) {
+        self.scripts.lock().unwrap().clear();
+    }
+
+    pub(crate) fn next(&self, method: &str) -> Fault {
+        self.scripts
+            .lock()
+            .unwrap()
+            .get_mut(method)
+            .and_then(|faults| faults.pop_front())
+            .unwrap_or(Fault::Pass)
+    }
+}
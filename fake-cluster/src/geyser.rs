@@ -0,0 +1,290 @@
+use std::{collections::HashMap, future::pending, pin::Pin, str::FromStr, sync::Arc};
+
+use futures_util::Stream;
+use solana_sdk::pubkey::Pubkey;
+use tokio::{
+    net::TcpListener,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    time::sleep,
+};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+use yellowstone_grpc_proto::prelude::{
+    geyser_server::{Geyser, GeyserServer},
+    subscribe_update::UpdateOneof,
+    GetBlockHeightRequest, GetBlockHeightResponse, GetLatestBlockhashRequest,
+    GetLatestBlockhashResponse, GetSlotRequest, GetSlotResponse, GetVersionRequest,
+    GetVersionResponse, IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest,
+    PongResponse, SubscribeRequest, SubscribeRequestAccountsDataSlice, SubscribeUpdate,
+    SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+};
+
+use crate::{
+    faults::{Fault, Faults},
+    ledger::{AccountWrite, Ledger},
+};
+
+const UPDATES_CAPACITY: usize = 1024;
+
+/// Accounts filters of a subscribe request, by filter name
+#[derive(Default)]
+struct AccountFilters {
+    filters: HashMap<String, (Vec<Pubkey>, Vec<Pubkey>)>,
+    data_slices: Vec<SubscribeRequestAccountsDataSlice>,
+}
+
+impl AccountFilters {
+    /// Each request replaces the filters of the subscription, like Yellowstone
+    fn from_request(request: &SubscribeRequest) -> Result<Self, Status> {
+        let parse = |pubkeys: &Vec<String>| {
+            pubkeys
+                .iter()
+                .map(|pubkey| Pubkey::from_str(pubkey))
+                .collect::<Result<Vec<Pubkey>, _>>()
+                .map_err(|_| Status::invalid_argument("Invalid pubkey in accounts filter"))
+        };
+
+        let filters = request
+            .accounts
+            .iter()
+            .map(|(name, filter)| {
+                Ok((
+                    name.clone(),
+                    (parse(&filter.account)?, parse(&filter.owner)?),
+                ))
+            })
+            .collect::<Result<_, Status>>()?;
+
+        Ok(Self {
+            filters,
+            data_slices: request.accounts_data_slice.clone(),
+        })
+    }
+
+    /// Names of the filters matching the write, an empty filter matches every account
+    fn matching(&self, write: &AccountWrite) -> Vec<String> {
+        self.filters
+            .iter()
+            .filter(|(_, (accounts, owners))| {
+                (accounts.is_empty() && owners.is_empty())
+                    || accounts.contains(&write.pubkey)
+                    || owners.contains(&write.account.owner)
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn slice(&self, data: &[u8]) -> Vec<u8> {
+        if self.data_slices.is_empty() {
+            return data.to_vec();
+        }
+
+        self.data_slices
+            .iter()
+            .flat_map(|data_slice| {
+                let start = (data_slice.offset as usize).min(data.len());
+                let end = start
+                    .saturating_add(data_slice.length as usize)
+                    .min(data.len());
+                data[start..end].iter().copied()
+            })
+            .collect()
+    }
+
+    fn update(&self, write: &AccountWrite) -> Option<SubscribeUpdate> {
+        let filters = self.matching(write);
+        if filters.is_empty() {
+            return None;
+        }
+
+        Some(SubscribeUpdate {
+            filters,
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: write.pubkey.to_bytes().to_vec(),
+                    lamports: write.account.lamports,
+                    owner: write.account.owner.to_bytes().to_vec(),
+                    executable: write.account.executable,
+                    rent_epoch: write.account.rent_epoch,
+                    data: self.slice(&write.account.data),
+                    // one write per slot
+                    write_version: write.slot,
+                    ..Default::default()
+                }),
+                slot: write.slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+}
+
+/// Yellowstone `Geyser` service streaming the ledger account writes, only
+/// subscriptions are served
+struct FakeGeyser {
+    ledger: Arc<Ledger>,
+    faults: Arc<Faults>,
+    disconnect: broadcast::Sender<()>,
+}
+
+type UpdateStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Geyser for FakeGeyser {
+    type SubscribeStream = UpdateStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<Streaming<SubscribeRequest>>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        match self.faults.next("geyserSubscribe") {
+            Fault::Pass => {}
+            Fault::Drop => pending::<()>().await,
+            Fault::Delay(delay) => sleep(delay).await,
+            Fault::Error { message, .. } => return Err(Status::unavailable(message)),
+            Fault::Disconnect => return Err(Status::aborted("Scripted disconnect")),
+        }
+
+        let mut requests = request.into_inner();
+        let mut writes = self.ledger.subscribe_writes();
+        let mut disconnect = self.disconnect.subscribe();
+        let faults = self.faults.clone();
+        let (sender, receiver) = mpsc::channel(UPDATES_CAPACITY);
+
+        // unlike Yellowstone the first filters apply before the response, so writes
+        // made once `subscribe` returned are never missed
+        let mut filters = match requests.message().await? {
+            Some(request) => AccountFilters::from_request(&request)?,
+            None => AccountFilters::default(),
+        };
+        let mut requests_open = true;
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    biased;
+
+                    request = requests.message(), if requests_open => match request {
+                        Ok(Some(request)) => match AccountFilters::from_request(&request) {
+                            Ok(new_filters) => filters = new_filters,
+                            Err(status) => {
+                                sender.send(Err(status)).await.ok();
+                                return;
+                            }
+                        },
+                        // the client may stop sending requests and keep streaming
+                        Ok(None) => requests_open = false,
+                        Err(_) => return,
+                    },
+                    write = writes.recv() => match write {
+                        Ok(write) => {
+                            if !send_update(&filters, &faults, &sender, &write).await {
+                                return;
+                            }
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return,
+                    },
+                    _ = disconnect.recv() => {
+                        sender.send(Err(Status::unavailable("Scripted disconnect"))).await.ok();
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+
+    async fn ping(&self, _: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        Err(Status::unimplemented("ping"))
+    }
+
+    async fn get_latest_blockhash(
+        &self,
+        _: Request<GetLatestBlockhashRequest>,
+    ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+        Err(Status::unimplemented("get_latest_blockhash"))
+    }
+
+    async fn get_block_height(
+        &self,
+        _: Request<GetBlockHeightRequest>,
+    ) -> Result<Response<GetBlockHeightResponse>, Status> {
+        Err(Status::unimplemented("get_block_height"))
+    }
+
+    async fn get_slot(
+        &self,
+        _: Request<GetSlotRequest>,
+    ) -> Result<Response<GetSlotResponse>, Status> {
+        Err(Status::unimplemented("get_slot"))
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        _: Request<IsBlockhashValidRequest>,
+    ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+        Err(Status::unimplemented("is_blockhash_valid"))
+    }
+
+    async fn get_version(
+        &self,
+        _: Request<GetVersionRequest>,
+    ) -> Result<Response<GetVersionResponse>, Status> {
+        Err(Status::unimplemented("get_version"))
+    }
+}
+
+/// `false` once the stream is over
+async fn send_update(
+    filters: &AccountFilters,
+    faults: &Faults,
+    sender: &mpsc::Sender<Result<SubscribeUpdate, Status>>,
+    write: &AccountWrite,
+) -> bool {
+    let Some(update) = filters.update(write) else {
+        return true;
+    };
+
+    match faults.next("geyserAccountUpdate") {
+        Fault::Pass => sender.send(Ok(update)).await.is_ok(),
+        Fault::Drop => true,
+        Fault::Delay(delay) => {
+            sleep(delay).await;
+            sender.send(Ok(update)).await.is_ok()
+        }
+        Fault::Error { message, .. } => {
+            sender.send(Err(Status::internal(message))).await.ok();
+            false
+        }
+        Fault::Disconnect => {
+            sender
+                .send(Err(Status::unavailable("Scripted disconnect")))
+                .await
+                .ok();
+            false
+        }
+    }
+}
+
+pub(crate) async fn serve(
+    listener: TcpListener,
+    ledger: Arc<Ledger>,
+    faults: Arc<Faults>,
+    disconnect: broadcast::Sender<()>,
+) -> Result<(), tonic::transport::Error> {
+    let service = FakeGeyser {
+        ledger,
+        faults,
+        disconnect,
+    };
+
+    Server::builder()
+        .add_service(GeyserServer::new(service))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_rpc_client_api::config::RpcAccountInfoConfig;
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature};
+
+/// Error object of a JSON-RPC response
+#[derive(Debug)]
+pub(crate) struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    pub fn invalid_params(message: &str) -> Self {
+        Self::new(-32602, message)
+    }
+
+    pub fn method_not_found() -> Self {
+        Self::new(-32601, "Method not found")
+    }
+}
+
+pub(crate) fn success(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "result": result, "id": id })
+}
+
+pub(crate) fn error(id: &Value, error: RpcError) -> Value {
+    let mut object = json!({ "code": error.code, "message": error.message });
+    if let Some(data) = error.data {
+        object["data"] = data;
+    }
+
+    json!({ "jsonrpc": "2.0", "error": object, "id": id })
+}
+
+/// Positional parameter, `null` when missing
+pub(crate) fn param<T: DeserializeOwned>(params: &Value, index: usize) -> Result<T, RpcError> {
+    let value = params.get(index).cloned().unwrap_or(Value::Null);
+
+    serde_json::from_value(value)
+        .map_err(|e| RpcError::invalid_params(&format!("Invalid param {}: {}", index, e)))
+}
+
+pub(crate) fn parse_pubkey(value: &str) -> Result<Pubkey, RpcError> {
+    Pubkey::from_str(value).map_err(|_| RpcError::invalid_params("Invalid param: Invalid"))
+}
+
+pub(crate) fn parse_signature(value: &str) -> Result<Signature, RpcError> {
+    Signature::from_str(value).map_err(|_| RpcError::invalid_params("Invalid param: Invalid"))
+}
+
+/// Encodes the account as the RPC would for `config`, binary (base58) by default
+pub(crate) fn encode_account(
+    pubkey: &Pubkey,
+    account: &Account,
+    config: &Option<RpcAccountInfoConfig>,
+) -> UiAccount {
+    let (encoding, data_slice) = match config {
+        Some(config) => (config.encoding, config.data_slice),
+        None => (None, None),
+    };
+
+    UiAccount::encode(
+        pubkey,
+        account,
+        encoding.unwrap_or(UiAccountEncoding::Binary),
+        None,
+        data_slice,
+    )
+}
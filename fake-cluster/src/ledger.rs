@@ -0,0 +1,212 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use solana_sdk::{
+    account::Account,
+    clock::{Clock, Slot},
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::Signature,
+    sysvar,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use tokio::sync::broadcast;
+
+const NOTIFICATION_CAPACITY: usize = 1024;
+
+/// Applies a sent transaction, returns the accounts it writes
+pub type TransactionHandler = Box<
+    dyn Fn(&VersionedTransaction) -> Result<Vec<(Pubkey, Account)>, TransactionError> + Send + Sync,
+>;
+
+#[derive(Clone, Debug)]
+pub struct LandedTransaction {
+    pub slot: Slot,
+    pub transaction: VersionedTransaction,
+    pub status: Result<(), TransactionError>,
+}
+
+/// Account written at `slot`, sent to the pubsub subscriptions
+#[derive(Clone, Debug)]
+pub struct AccountWrite {
+    pub slot: Slot,
+    pub pubkey: Pubkey,
+    pub account: Account,
+}
+
+struct Bank {
+    slot: Slot,
+    blockhash: Hash,
+    accounts: HashMap<Pubkey, Account>,
+    transactions: HashMap<Signature, LandedTransaction>,
+}
+
+impl Bank {
+    /// Every write lands in a new slot so consumers can order updates
+    fn write(&mut self, pubkey: Pubkey, account: Account) -> AccountWrite {
+        self.slot += 1;
+        self.accounts.insert(pubkey, account.clone());
+
+        AccountWrite {
+            slot: self.slot,
+            pubkey,
+            account,
+        }
+    }
+}
+
+/// In-memory accounts and transactions served by the fake cluster
+pub struct Ledger {
+    bank: Mutex<Bank>,
+    transaction_handler: Mutex<Option<TransactionHandler>>,
+    writes: broadcast::Sender<AccountWrite>,
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self {
+            bank: Mutex::new(Bank {
+                slot: 1,
+                blockhash: Hash::new_unique(),
+                accounts: HashMap::new(),
+                transactions: HashMap::new(),
+            }),
+            transaction_handler: Mutex::new(None),
+            writes: broadcast::channel(NOTIFICATION_CAPACITY).0,
+        }
+    }
+}
+
+impl Ledger {
+    pub fn slot(&self) -> Slot {
+        self.bank.lock().unwrap().slot
+    }
+
+    pub fn advance_slot(&self, slots: u64) -> Slot {
+        let mut bank = self.bank.lock().unwrap();
+        bank.slot += slots;
+        bank.slot
+    }
+
+    pub fn latest_blockhash(&self) -> (Slot, Hash) {
+        let bank = self.bank.lock().unwrap();
+        (bank.slot, bank.blockhash)
+    }
+
+    /// Expires every transaction built on the previous blockhash
+    pub fn new_blockhash(&self) -> Hash {
+        let mut bank = self.bank.lock().unwrap();
+        bank.blockhash = Hash::new_unique();
+        bank.blockhash
+    }
+
+    pub fn account(&self, pubkey: &Pubkey) -> (Slot, Option<Account>) {
+        let bank = self.bank.lock().unwrap();
+        (bank.slot, bank.accounts.get(pubkey).cloned())
+    }
+
+    pub fn accounts(&self, pubkeys: &[Pubkey]) -> (Slot, Vec<Option<Account>>) {
+        let bank = self.bank.lock().unwrap();
+        let accounts = pubkeys
+            .iter()
+            .map(|pubkey| bank.accounts.get(pubkey).cloned())
+            .collect();
+
+        (bank.slot, accounts)
+    }
+
+    pub fn program_accounts(&self, program_id: &Pubkey) -> (Slot, Vec<(Pubkey, Account)>) {
+        let bank = self.bank.lock().unwrap();
+        let accounts = bank
+            .accounts
+            .iter()
+            .filter(|(_, account)| &account.owner == program_id)
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect();
+
+        (bank.slot, accounts)
+    }
+
+    /// Writes the account in a new slot and notifies its subscriptions
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) -> Slot {
+        let write = self.bank.lock().unwrap().write(pubkey, account);
+        let slot = write.slot;
+        // no subscriptions is not an error
+        let _ = self.writes.send(write);
+
+        slot
+    }
+
+    /// Writes the clock sysvar read by `ClusterClock`
+    pub fn set_unix_timestamp(&self, unix_timestamp: i64) -> Slot {
+        let slot = self.slot() + 1;
+        let clock = Clock {
+            slot,
+            unix_timestamp,
+            ..Clock::default()
+        };
+        let account = Account::new_data(1, &clock, &sysvar::id()).unwrap();
+
+        self.set_account(sysvar::clock::id(), account)
+    }
+
+    pub fn set_transaction_handler(&self, handler: TransactionHandler) {
+        *self.transaction_handler.lock().unwrap() = Some(handler);
+    }
+
+    /// Lands the transaction in a new slot, the handler decides its status and writes
+    pub(crate) fn process_transaction(&self, transaction: VersionedTransaction) -> Signature {
+        let signature = transaction.signatures[0];
+        let result = match &*self.transaction_handler.lock().unwrap() {
+            Some(handler) => handler(&transaction),
+            None => Ok(vec![]),
+        };
+
+        let (status, writes) = match result {
+            Ok(writes) => (Ok(()), writes),
+            Err(e) => (Err(e), vec![]),
+        };
+
+        for (pubkey, account) in writes {
+            self.set_account(pubkey, account);
+        }
+
+        let mut bank = self.bank.lock().unwrap();
+        bank.slot += 1;
+        let landed = LandedTransaction {
+            slot: bank.slot,
+            transaction,
+            status,
+        };
+        bank.transactions.insert(signature, landed);
+
+        signature
+    }
+
+    pub fn transaction(&self, signature: &Signature) -> Option<LandedTransaction> {
+        self.bank
+            .lock()
+            .unwrap()
+            .transactions
+            .get(signature)
+            .cloned()
+    }
+
+    /// Landed transactions in slot order
+    pub fn transactions(&self) -> Vec<LandedTransaction> {
+        let mut transactions = self
+            .bank
+            .lock()
+            .unwrap()
+            .transactions
+            .values()
+            .cloned()
+            .collect::<Vec<LandedTransaction>>();
+        transactions.sort_by_key(|t| t.slot);
+
+        transactions
+    }
+
+    pub(crate) fn subscribe_writes(&self) -> broadcast::Receiver<AccountWrite> {
+        self.writes.subscribe()
+    }
+}
@@ -0,0 +1,164 @@
+//! In-process stand-in for a Solana cluster: a JSON-RPC endpoint, a pubsub
+//! websocket and a Yellowstone Geyser gRPC endpoint served from an in-memory
+//! `Ledger`, with scripted `Faults` so the bot can be tested end to end without
+//! network access
+
+mod faults;
+mod geyser;
+mod jsonrpc;
+mod ledger;
+mod pubsub;
+mod rpc;
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::{sync::broadcast, task::JoinHandle};
+
+pub use faults::{Fault, Faults};
+pub use ledger::{AccountWrite, LandedTransaction, Ledger, TransactionHandler};
+
+pub struct FakeCluster {
+    ledger: Arc<Ledger>,
+    faults: Arc<Faults>,
+    rpc_addr: SocketAddr,
+    pubsub_addr: SocketAddr,
+    geyser_addr: SocketAddr,
+    disconnect: broadcast::Sender<()>,
+    geyser_disconnect: broadcast::Sender<()>,
+    pubsub_subscriptions: Arc<AtomicUsize>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl FakeCluster {
+    /// Serves an empty ledger on local ports picked by the OS
+    pub async fn start() -> io::Result<Self> {
+        let ledger = Arc::new(Ledger::default());
+        let faults = Arc::new(Faults::default());
+        let (disconnect, _) = broadcast::channel(1);
+        let (geyser_disconnect, _) = broadcast::channel(1);
+        let pubsub_subscriptions = Arc::new(AtomicUsize::new(0));
+
+        let rpc_listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        rpc_listener.set_nonblocking(true)?;
+        let rpc_addr = rpc_listener.local_addr()?;
+
+        let pubsub_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let pubsub_addr = pubsub_listener.local_addr()?;
+
+        let geyser_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let geyser_addr = geyser_listener.local_addr()?;
+
+        let rpc_handle = tokio::spawn({
+            let ledger = ledger.clone();
+            let faults = faults.clone();
+
+            async move {
+                if let Err(e) = rpc::serve(rpc_listener, ledger, faults).await {
+                    println!("Fake cluster rpc shutdown: {}", e);
+                }
+            }
+        });
+        let pubsub_handle = tokio::spawn(pubsub::serve(
+            pubsub_listener,
+            ledger.clone(),
+            faults.clone(),
+            disconnect.clone(),
+            pubsub_subscriptions.clone(),
+        ));
+        let geyser_handle = tokio::spawn({
+            let ledger = ledger.clone();
+            let faults = faults.clone();
+            let geyser_disconnect = geyser_disconnect.clone();
+
+            async move {
+                if let Err(e) =
+                    geyser::serve(geyser_listener, ledger, faults, geyser_disconnect).await
+                {
+                    println!("Fake cluster geyser shutdown: {}", e);
+                }
+            }
+        });
+
+        Ok(Self {
+            ledger,
+            faults,
+            rpc_addr,
+            pubsub_addr,
+            geyser_addr,
+            disconnect,
+            geyser_disconnect,
+            pubsub_subscriptions,
+            handles: vec![rpc_handle, pubsub_handle, geyser_handle],
+        })
+    }
+
+    /// Writes the fixtures, each in its own slot
+    pub fn with_accounts(self, accounts: impl IntoIterator<Item = (Pubkey, Account)>) -> Self {
+        for (pubkey, account) in accounts {
+            self.ledger.set_account(pubkey, account);
+        }
+        self
+    }
+
+    pub fn rpc_url(&self) -> String {
+        format!("http://{}", self.rpc_addr)
+    }
+
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}", self.pubsub_addr)
+    }
+
+    /// Yellowstone Geyser gRPC endpoint, faults are scripted under `geyserSubscribe`
+    /// and `geyserAccountUpdate`
+    pub fn geyser_url(&self) -> String {
+        format!("http://{}", self.geyser_addr)
+    }
+
+    pub fn ledger(&self) -> &Arc<Ledger> {
+        &self.ledger
+    }
+
+    pub fn faults(&self) -> &Arc<Faults> {
+        &self.faults
+    }
+
+    /// Drops every open websocket without a close frame, new connections are accepted
+    pub fn disconnect_websockets(&self) {
+        // no open connection is not an error
+        let _ = self.disconnect.send(());
+    }
+
+    /// Pubsub subscriptions open on the server across all connections, subscriptions
+    /// of a dropped connection are closed with it
+    pub fn pubsub_subscription_count(&self) -> usize {
+        self.pubsub_subscriptions.load(Ordering::Relaxed)
+    }
+
+    /// Ends every open Geyser stream with an `UNAVAILABLE` status
+    pub fn disconnect_geyser_streams(&self) {
+        // no open stream is not an error
+        let _ = self.geyser_disconnect.send(());
+    }
+
+    /// Geyser streams currently open, a stream closed by its client is counted until
+    /// the next account write notices it
+    pub fn geyser_stream_count(&self) -> usize {
+        self.geyser_disconnect.receiver_count()
+    }
+}
+
+impl Drop for FakeCluster {
+    fn drop(&mut self) {
+        for handle in self.handles.iter() {
+            handle.abort();
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    response::{Response as RpcResponse, RpcKeyedAccount, RpcResponseContext},
+};
+use solana_sdk::{account::AccountSharedData, pubkey::Pubkey};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+    time::sleep,
+};
+use tokio_tungstenite::{accept_async, tungstenite::Message, WebSocketStream};
+
+use crate::{
+    faults::{Fault, Faults},
+    jsonrpc::{self, encode_account, param, parse_pubkey, RpcError},
+    ledger::{AccountWrite, Ledger},
+};
+
+/// Subscription ids are unique across connections like on a real node, so ids
+/// of a previous connection are never valid again
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+enum Subscription {
+    Account {
+        pubkey: Pubkey,
+        config: Option<RpcAccountInfoConfig>,
+    },
+    Program {
+        program_id: Pubkey,
+        config: Option<RpcProgramAccountsConfig>,
+    },
+}
+
+impl Subscription {
+    fn method(&self) -> &'static str {
+        match self {
+            Self::Account { .. } => "accountNotification",
+            Self::Program { .. } => "programNotification",
+        }
+    }
+
+    /// Notification result for the write, `None` if the subscription does not match it
+    fn notification(&self, write: &AccountWrite) -> Option<Value> {
+        let context = RpcResponseContext::new(write.slot);
+
+        match self {
+            Self::Account { pubkey, config } => {
+                if pubkey != &write.pubkey {
+                    return None;
+                }
+
+                Some(json!(RpcResponse {
+                    context,
+                    value: encode_account(pubkey, &write.account, config),
+                }))
+            }
+            Self::Program { program_id, config } => {
+                if program_id != &write.account.owner {
+                    return None;
+                }
+
+                let filters = config.as_ref().and_then(|c| c.filters.as_ref());
+                if let Some(filters) = filters {
+                    let account = AccountSharedData::from(write.account.clone());
+                    if !filters.iter().all(|filter| filter.allows(&account)) {
+                        return None;
+                    }
+                }
+
+                let account_config = config.as_ref().map(|c| c.account_config.clone());
+                Some(json!(RpcResponse {
+                    context,
+                    value: RpcKeyedAccount {
+                        pubkey: write.pubkey.to_string(),
+                        account: encode_account(&write.pubkey, &write.account, &account_config),
+                    },
+                }))
+            }
+        }
+    }
+}
+
+/// Subscriptions of one connection, counted in the cluster wide `open` count until
+/// unsubscribed or the connection is dropped
+struct Subscriptions {
+    by_id: HashMap<u64, Subscription>,
+    open: Arc<AtomicUsize>,
+}
+
+impl Subscriptions {
+    fn new(open: Arc<AtomicUsize>) -> Self {
+        Self {
+            by_id: HashMap::new(),
+            open,
+        }
+    }
+
+    fn insert(&mut self, subscription_id: u64, subscription: Subscription) {
+        self.by_id.insert(subscription_id, subscription);
+        self.open.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn remove(&mut self, subscription_id: &u64) -> Option<Subscription> {
+        let subscription = self.by_id.remove(subscription_id)?;
+        self.open.fetch_sub(1, Ordering::Relaxed);
+        Some(subscription)
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        self.open.fetch_sub(self.by_id.len(), Ordering::Relaxed);
+    }
+}
+
+pub(crate) async fn serve(
+    listener: TcpListener,
+    ledger: Arc<Ledger>,
+    faults: Arc<Faults>,
+    disconnect: broadcast::Sender<()>,
+    open_subscriptions: Arc<AtomicUsize>,
+) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+
+        tokio::spawn(connection(
+            stream,
+            ledger.clone(),
+            faults.clone(),
+            disconnect.subscribe(),
+            open_subscriptions.clone(),
+        ));
+    }
+}
+
+/// Returning drops the socket without a close frame, like a lost connection
+async fn connection(
+    stream: TcpStream,
+    ledger: Arc<Ledger>,
+    faults: Arc<Faults>,
+    mut disconnect: broadcast::Receiver<()>,
+    open_subscriptions: Arc<AtomicUsize>,
+) {
+    let Ok(mut ws) = accept_async(stream).await else {
+        return;
+    };
+
+    let mut writes = ledger.subscribe_writes();
+    let mut subscriptions = Subscriptions::new(open_subscriptions);
+
+    loop {
+        tokio::select! {
+            message = ws.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    // pings are answered by tungstenite
+                    Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Binary(_) | Message::Frame(_))) => continue,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                };
+
+                let Ok(request) = serde_json::from_str::<Value>(&text) else {
+                    let response = jsonrpc::error(&Value::Null, RpcError::new(-32700, "Parse error"));
+                    if send(&mut ws, response).await.is_err() {
+                        return;
+                    }
+                    continue;
+                };
+
+                let id = request.get("id").cloned().unwrap_or(Value::Null);
+                let method = request["method"].as_str().unwrap_or_default();
+                let params = request.get("params").cloned().unwrap_or(json!([]));
+
+                let response = match faults.next(method) {
+                    Fault::Pass => handle_request(&mut subscriptions, method, &params),
+                    Fault::Drop => continue,
+                    Fault::Delay(delay) => {
+                        sleep(delay).await;
+                        handle_request(&mut subscriptions, method, &params)
+                    }
+                    Fault::Error { code, message } => Err(RpcError::new(code, &message)),
+                    Fault::Disconnect => return,
+                };
+
+                let response = match response {
+                    Ok(result) => jsonrpc::success(&id, result),
+                    Err(e) => jsonrpc::error(&id, e),
+                };
+                if send(&mut ws, response).await.is_err() {
+                    return;
+                }
+            }
+            write = writes.recv() => {
+                let write = match write {
+                    Ok(write) => write,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                };
+
+                for (subscription_id, subscription) in subscriptions.by_id.iter() {
+                    let Some(result) = subscription.notification(&write) else {
+                        continue;
+                    };
+
+                    match faults.next(subscription.method()) {
+                        Fault::Pass | Fault::Error { .. } => {}
+                        Fault::Drop => continue,
+                        Fault::Delay(delay) => sleep(delay).await,
+                        Fault::Disconnect => return,
+                    }
+
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": subscription.method(),
+                        "params": { "result": result, "subscription": subscription_id },
+                    });
+                    if send(&mut ws, notification).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            _ = disconnect.recv() => return,
+        }
+    }
+}
+
+async fn send(
+    ws: &mut WebSocketStream<TcpStream>,
+    message: Value,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    ws.send(Message::Text(message.to_string())).await
+}
+
+fn handle_request(
+    subscriptions: &mut Subscriptions,
+    method: &str,
+    params: &Value,
+) -> Result<Value, RpcError> {
+    let subscription = match method {
+        "accountSubscribe" => Subscription::Account {
+            pubkey: parse_pubkey(&param::<String>(params, 0)?)?,
+            config: param(params, 1)?,
+        },
+        "programSubscribe" => Subscription::Program {
+            program_id: parse_pubkey(&param::<String>(params, 0)?)?,
+            config: param(params, 1)?,
+        },
+        "accountUnsubscribe" | "programUnsubscribe" => {
+            let subscription_id: u64 = param(params, 0)?;
+            return match subscriptions.remove(&subscription_id) {
+                Some(_) => Ok(json!(true)),
+                None => Err(RpcError::invalid_params("Invalid subscription id.")),
+            };
+        }
+        _ => return Err(RpcError::method_not_found()),
+    };
+
+    let subscription_id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+    subscriptions.insert(subscription_id, subscription);
+
+    Ok(json!(subscription_id))
+}
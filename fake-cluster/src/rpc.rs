@@ -0,0 +1,263 @@
+use std::{convert::Infallible, future::pending, io, net::TcpListener, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use serde_json::{json, Value};
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcSendTransactionConfig, RpcTransactionConfig},
+    response::{Response as RpcResponse, RpcBlockhash, RpcResponseContext, RpcVersionInfo},
+};
+use solana_sdk::{clock::Slot, transaction::VersionedTransaction};
+use solana_transaction_status::{
+    ConfirmedTransactionWithStatusMeta, EncodeError, TransactionBinaryEncoding,
+    TransactionStatusMeta, TransactionWithStatusMeta, UiTransactionEncoding,
+    VersionedTransactionWithStatusMeta,
+};
+use tokio::time::sleep;
+
+use crate::{
+    faults::{Fault, Faults},
+    jsonrpc::{self, encode_account, param, parse_pubkey, parse_signature, RpcError},
+    ledger::Ledger,
+};
+
+/// Version reported by `getVersion`, the client picks transaction encodings from it
+const SOLANA_CORE_VERSION: &str = "1.16.12";
+const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+/// Blocks a blockhash stays valid for
+const MAX_PROCESSING_AGE: u64 = 150;
+/// Keys a `getMultipleAccounts` request can ask for
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+pub(crate) async fn serve(
+    listener: TcpListener,
+    ledger: Arc<Ledger>,
+    faults: Arc<Faults>,
+) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let ledger = ledger.clone();
+        let faults = faults.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(request, ledger.clone(), faults.clone())
+            }))
+        }
+    });
+
+    Server::from_tcp(listener)?.serve(make_service).await
+}
+
+/// A service error makes hyper close the connection without answering
+async fn handle(
+    request: Request<Body>,
+    ledger: Arc<Ledger>,
+    faults: Arc<Faults>,
+) -> Result<Response<Body>, io::Error> {
+    let body = hyper::body::to_bytes(request.into_body())
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let response = match serde_json::from_slice::<Value>(&body) {
+        Ok(request) => {
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            let method = request["method"].as_str().unwrap_or_default();
+            let params = request.get("params").cloned().unwrap_or(json!([]));
+
+            match faults.next(method) {
+                Fault::Pass => {}
+                Fault::Drop => pending::<()>().await,
+                Fault::Delay(delay) => sleep(delay).await,
+                Fault::Error { code, message } => {
+                    return Ok(json_response(jsonrpc::error(
+                        &id,
+                        RpcError::new(code, &message),
+                    )))
+                }
+                Fault::Disconnect => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "Scripted disconnect",
+                    ))
+                }
+            }
+
+            match dispatch(&ledger, method, &params) {
+                Ok(result) => jsonrpc::success(&id, result),
+                Err(e) => jsonrpc::error(&id, e),
+            }
+        }
+        Err(_) => jsonrpc::error(&Value::Null, RpcError::new(-32700, "Parse error")),
+    };
+
+    Ok(json_response(response))
+}
+
+fn json_response(body: Value) -> Response<Body> {
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn with_context<T: serde::Serialize>(slot: Slot, value: T) -> Value {
+    json!(RpcResponse {
+        context: RpcResponseContext::new(slot),
+        value,
+    })
+}
+
+fn check_min_context_slot(slot: Slot, min_context_slot: Option<Slot>) -> Result<(), RpcError> {
+    match min_context_slot {
+        Some(min_context_slot) if slot < min_context_slot => Err(RpcError {
+            code: -32016,
+            message: "Minimum context slot has not been reached".to_string(),
+            data: Some(json!({ "contextSlot": slot })),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn dispatch(ledger: &Ledger, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "getAccountInfo" => {
+            let pubkey = parse_pubkey(&param::<String>(params, 0)?)?;
+            let config: Option<RpcAccountInfoConfig> = param(params, 1)?;
+            let (slot, account) = ledger.account(&pubkey);
+            check_min_context_slot(slot, config.as_ref().and_then(|c| c.min_context_slot))?;
+
+            let value = account.map(|account| encode_account(&pubkey, &account, &config));
+            Ok(with_context(slot, value))
+        }
+        "getMultipleAccounts" => {
+            let pubkeys = param::<Vec<String>>(params, 0)?
+                .iter()
+                .map(|pubkey| parse_pubkey(pubkey))
+                .collect::<Result<Vec<_>, RpcError>>()?;
+            if pubkeys.len() > MAX_MULTIPLE_ACCOUNTS {
+                return Err(RpcError::invalid_params(&format!(
+                    "Too many inputs provided; max {}",
+                    MAX_MULTIPLE_ACCOUNTS
+                )));
+            }
+            let config: Option<RpcAccountInfoConfig> = param(params, 1)?;
+            let (slot, accounts) = ledger.accounts(&pubkeys);
+            check_min_context_slot(slot, config.as_ref().and_then(|c| c.min_context_slot))?;
+
+            let value = pubkeys
+                .iter()
+                .zip(accounts)
+                .map(|(pubkey, account)| {
+                    account.map(|account| encode_account(pubkey, &account, &config))
+                })
+                .collect::<Vec<_>>();
+            Ok(with_context(slot, value))
+        }
+        "getSlot" => Ok(json!(ledger.slot())),
+        "getVersion" => Ok(json!(RpcVersionInfo {
+            solana_core: SOLANA_CORE_VERSION.to_string(),
+            feature_set: None,
+        })),
+        "getLatestBlockhash" => {
+            let (slot, blockhash) = ledger.latest_blockhash();
+
+            Ok(with_context(
+                slot,
+                RpcBlockhash {
+                    blockhash: blockhash.to_string(),
+                    last_valid_block_height: slot + MAX_PROCESSING_AGE,
+                },
+            ))
+        }
+        "sendTransaction" => {
+            let encoded: String = param(params, 0)?;
+            let config: Option<RpcSendTransactionConfig> = param(params, 1)?;
+            let encoding = config
+                .and_then(|c| c.encoding)
+                .unwrap_or(UiTransactionEncoding::Base58);
+
+            let transaction = decode_transaction(&encoded, encoding)?;
+            if transaction.signatures.is_empty() {
+                return Err(RpcError::invalid_params("Missing signature"));
+            }
+
+            // like a leader, transactions on an unknown blockhash are dropped
+            let (_, blockhash) = ledger.latest_blockhash();
+            if transaction.message.recent_blockhash() != &blockhash {
+                return Ok(json!(transaction.signatures[0].to_string()));
+            }
+
+            Ok(json!(ledger.process_transaction(transaction).to_string()))
+        }
+        "getTransaction" => {
+            let signature = parse_signature(&param::<String>(params, 0)?)?;
+            let config: Option<RpcTransactionConfig> = param(params, 1)?;
+            let (encoding, max_supported_transaction_version) = match config {
+                Some(config) => (config.encoding, config.max_supported_transaction_version),
+                None => (None, None),
+            };
+
+            let Some(landed) = ledger.transaction(&signature) else {
+                return Ok(Value::Null);
+            };
+
+            let account_keys = landed.transaction.message.static_account_keys().len();
+            let meta = TransactionStatusMeta {
+                status: landed.status,
+                fee: LAMPORTS_PER_SIGNATURE * landed.transaction.signatures.len() as u64,
+                pre_balances: vec![0; account_keys],
+                post_balances: vec![0; account_keys],
+                ..TransactionStatusMeta::default()
+            };
+
+            let confirmed = ConfirmedTransactionWithStatusMeta {
+                slot: landed.slot,
+                tx_with_meta: TransactionWithStatusMeta::Complete(
+                    VersionedTransactionWithStatusMeta {
+                        transaction: landed.transaction,
+                        meta,
+                    },
+                ),
+                block_time: None,
+            };
+
+            match confirmed.encode(
+                encoding.unwrap_or(UiTransactionEncoding::Json),
+                max_supported_transaction_version,
+            ) {
+                Ok(encoded) => Ok(json!(encoded)),
+                Err(EncodeError::UnsupportedTransactionVersion(version)) => Err(RpcError::new(
+                    -32015,
+                    &format!(
+                        "Transaction version ({}) is not supported by the requesting client",
+                        version
+                    ),
+                )),
+            }
+        }
+        _ => Err(RpcError::method_not_found()),
+    }
+}
+
+fn decode_transaction(
+    encoded: &str,
+    encoding: UiTransactionEncoding,
+) -> Result<VersionedTransaction, RpcError> {
+    let bytes = match encoding.into_binary_encoding() {
+        Some(TransactionBinaryEncoding::Base58) => bs58::decode(encoded).into_vec().ok(),
+        Some(TransactionBinaryEncoding::Base64) => STANDARD.decode(encoded).ok(),
+        None => {
+            return Err(RpcError::invalid_params(&format!(
+                "Unsupported encoding: {}",
+                encoding
+            )))
+        }
+    }
+    .ok_or(RpcError::invalid_params("Invalid transaction encoding"))?;
+
+    bincode::deserialize(&bytes).map_err(|_| RpcError::invalid_params("Invalid transaction"))
+}
@@ -0,0 +1,239 @@
+// each test binary uses a subset of the helpers
+#![allow(dead_code)]
+
+use std::{sync::Arc, time::Duration};
+
+use anchor_lang::AccountSerialize;
+use bot::{
+    account_source::RpcAccountSource,
+    addresses::StaticAddresses,
+    args::Wallet,
+    error::Error,
+    services::{
+        cluster_clock::ClusterClock,
+        funding_relayer::{start_funding_relayer, RelayerConfig},
+    },
+    state::{fetch_markets, State},
+    utils::{
+        account_log::Recorder,
+        websocket_client::{
+            create_persisted_websocket_connection, Backoff, ConnectionStatus, WebsocketClient,
+        },
+    },
+};
+use drift::{
+    accounts::PerpMarket as DriftPerpMarket,
+    types::{Amm, HistoricalOracleData},
+};
+use fake_cluster::FakeCluster;
+use funding_program::state::{
+    Exchange, FundingAccountConfig, FundingAccountFixed, FundingAccountLoader,
+};
+use pyth_sdk_solana::state::{AccountType, PriceAccount, PriceInfo, PriceStatus, MAGIC, VERSION_2};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair,
+    signer::Signer,
+};
+use tokio::task::JoinHandle;
+
+pub fn rpc_client(cluster: &FakeCluster) -> Arc<RpcClient> {
+    Arc::new(RpcClient::new_with_commitment(
+        cluster.rpc_url(),
+        CommitmentConfig::confirmed(),
+    ))
+}
+
+/// Connected client with a short backoff so reconnects do not slow the tests down
+pub async fn ws_client(
+    cluster: &FakeCluster,
+    request_timeout: Duration,
+) -> (Arc<WebsocketClient>, JoinHandle<()>) {
+    ws_client_with_urls(vec![cluster.ws_url()], request_timeout).await
+}
+
+/// Connected to the first of `urls`
+pub async fn ws_client_with_urls(
+    urls: Vec<String>,
+    request_timeout: Duration,
+) -> (Arc<WebsocketClient>, JoinHandle<()>) {
+    let client = Arc::new(
+        WebsocketClient::new(urls)
+            .with_backoff(Backoff {
+                initial: Duration::from_millis(10),
+                max: Duration::from_millis(100),
+            })
+            .with_request_timeout(request_timeout),
+    );
+    let mut status = client.connection_status();
+
+    let handle = create_persisted_websocket_connection(client.clone())
+        .await
+        .unwrap();
+    while *status.borrow_and_update() != ConnectionStatus::Connected {
+        status.changed().await.unwrap();
+    }
+
+    (
+        client,
+        tokio::spawn(async move {
+            handle.await.unwrap().unwrap();
+        }),
+    )
+}
+
+pub fn wallet() -> Arc<Wallet> {
+    let keypair = Keypair::new();
+    let pubkey = keypair.pubkey();
+    Arc::new(Wallet { keypair, pubkey })
+}
+
+pub fn account(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account {
+        lamports: 1_000_000,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Drift market trading 1% above its pyth oracle at `ts`, with the funding account
+/// of the market due for an update. Returns the market address and the accounts
+pub fn drift_market(market_index: u16, ts: i64) -> (Pubkey, Vec<(Pubkey, Account)>) {
+    let market_address = StaticAddresses::get_drift_markets_from_ids(&vec![market_index])[0];
+    let oracle_address = Pubkey::new_unique();
+    let mark_price = 100_000_000;
+    let oracle_price = 99_000_000;
+
+    let market = DriftPerpMarket {
+        pubkey: market_address,
+        market_index,
+        amm: Amm {
+            oracle: oracle_address,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price: oracle_price,
+                last_oracle_price_twap: oracle_price,
+                last_oracle_price_twap_5min: oracle_price,
+                last_oracle_price_twap_ts: ts,
+                ..Default::default()
+            },
+            base_asset_reserve: 1_000_000_000_000,
+            quote_asset_reserve: 1_000_000_000_000,
+            sqrt_k: 1_000_000_000_000,
+            peg_multiplier: mark_price as u128,
+            last_bid_price_twap: mark_price,
+            last_ask_price_twap: mark_price,
+            last_mark_price_twap: mark_price,
+            last_mark_price_twap_5min: mark_price,
+            last_mark_price_twap_ts: ts,
+            funding_period: 3600,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut market_data = vec![];
+    market.try_serialize(&mut market_data).unwrap();
+
+    let oracle = PriceAccount {
+        magic: MAGIC,
+        ver: VERSION_2,
+        atype: AccountType::Price as u32,
+        expo: -6,
+        timestamp: ts,
+        agg: PriceInfo {
+            price: oracle_price,
+            conf: 10_000,
+            status: PriceStatus::Trading,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let exchange = Exchange::Drift;
+    let (funding_account_address, bump) = FundingAccountLoader::pda(0, market_index, &exchange);
+    let funding_account = FundingAccountFixed {
+        discriminator: FundingAccountFixed::DISCRIMINATOR,
+        config: FundingAccountConfig {
+            update_frequency_secs: 30,
+            staleness_threshold_secs: 600,
+            period_length: 5,
+            data_points_count: 0,
+            ..Default::default()
+        },
+        market_index,
+        bump,
+        exchange: exchange.discriminator(),
+        ..Default::default()
+    };
+
+    (
+        market_address,
+        vec![
+            (market_address, account(market_data, drift::id())),
+            (
+                oracle_address,
+                account(bytemuck::bytes_of(&oracle).to_vec(), Pubkey::new_unique()),
+            ),
+            (
+                funding_account_address,
+                account(
+                    bytemuck::bytes_of(&funding_account).to_vec(),
+                    funding_program::id(),
+                ),
+            ),
+        ],
+    )
+}
+
+/// Relayer timings short enough for the first funding rate to be published within
+/// a second
+pub fn fast_relayer_config() -> RelayerConfig {
+    RelayerConfig {
+        start_delay: Duration::from_millis(200),
+        snapshot_interval: Duration::from_millis(200),
+        send_interval: Duration::from_millis(100),
+        dry_run: false,
+    }
+}
+
+/// Funding relayer of the drift markets of the cluster, reading through a polling RPC
+/// source. Returns the state and the handles of the subscriptions and relayer tasks
+pub async fn start_relayer(
+    cluster: &FakeCluster,
+    markets: &Vec<Pubkey>,
+    wallet: Arc<Wallet>,
+    recorder: Option<Arc<Recorder>>,
+    config: RelayerConfig,
+) -> (Arc<State>, Vec<JoinHandle<Result<(), Error>>>) {
+    let rpc_client = rpc_client(cluster);
+    let source = Arc::new(RpcAccountSource::new(
+        rpc_client.clone(),
+        Duration::from_millis(50),
+    ));
+
+    let mut static_addresses = StaticAddresses::new();
+    static_addresses.set_drift_markets(
+        &fetch_markets::<DriftPerpMarket>(source.as_ref(), markets)
+            .await
+            .unwrap(),
+    );
+
+    let mut state = State::new(source, static_addresses);
+    if let Some(recorder) = recorder {
+        state = state.with_recorder(recorder);
+    }
+    let state = Arc::new(state);
+
+    let subscriptions_handle = State::subscribe_to_accounts(state.clone());
+    let clock = Arc::new(ClusterClock::new(rpc_client.clone()).await.unwrap());
+    let (cache_handle, relayer_handle) =
+        start_funding_relayer(rpc_client, wallet, state.clone(), clock, config)
+            .await
+            .unwrap();
+
+    (
+        state,
+        vec![subscriptions_handle, cache_handle, relayer_handle],
+    )
+}
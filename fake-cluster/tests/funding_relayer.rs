@@ -0,0 +1,201 @@
+//! Funding relayer against the fake cluster
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+use borsh::BorshDeserialize;
+use bot::{
+    account_source::LocalAccountSource,
+    addresses::StaticAddresses,
+    services::funding_relayer::{replay_funding, ReplayedPublish},
+    state::{fetch_markets, State},
+    utils::account_log::{first_recorded_accounts, read_log, LogRecord, Recorder},
+};
+use drift::accounts::PerpMarket as DriftPerpMarket;
+use fake_cluster::{FakeCluster, Fault, Ledger};
+use funding_program::{
+    instructions::InstructionData,
+    state::{Exchange, FundingAccountFixed, FundingAccountLoader, PodOptionI64},
+};
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::{account, drift_market, fast_relayer_config, start_relayer, wallet};
+
+const TS: i64 = 1_700_000_000;
+
+/// Waits for the relayer to log a funding rate publish
+async fn wait_for_publish(path: &Path) -> Vec<LogRecord> {
+    timeout(Duration::from_secs(10), async {
+        loop {
+            let records = read_log(path).unwrap();
+            if records
+                .iter()
+                .any(|r| matches!(r, LogRecord::Publish { ts, .. } if *ts >= TS))
+            {
+                return records;
+            }
+
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_record_then_replay() {
+    let (market, accounts) = drift_market(0, TS);
+    let funding_account = FundingAccountLoader::pda(0, 0, &Exchange::Drift).0;
+    let cluster = FakeCluster::start().await.unwrap().with_accounts(accounts);
+    cluster.ledger().set_unix_timestamp(TS);
+    let path = std::env::temp_dir().join(format!("relayer-{}.log", Pubkey::new_unique()));
+
+    // previous session, it would not replay if the new one was appended to it
+    Recorder::create(&path)
+        .unwrap()
+        .lock()
+        .await
+        .append(&LogRecord::Publish {
+            ts: 0,
+            funding_account,
+            funding_rate: 1,
+            confidence: None,
+        })
+        .unwrap();
+
+    let recorder = Arc::new(Recorder::create(&path).unwrap());
+    let (_, handles) = start_relayer(
+        &cluster,
+        &vec![market],
+        wallet(),
+        Some(recorder),
+        fast_relayer_config().with_dry_run(true),
+    )
+    .await;
+
+    let records = wait_for_publish(&path).await;
+    handles.iter().for_each(|handle| handle.abort());
+    assert!(cluster.ledger().transactions().is_empty());
+
+    let source = Arc::new(LocalAccountSource::from_data(
+        first_recorded_accounts(&records).unwrap(),
+    ));
+    let mut static_addresses = StaticAddresses::new();
+    static_addresses.set_drift_markets(
+        &fetch_markets::<DriftPerpMarket>(source.as_ref(), &vec![market])
+            .await
+            .unwrap(),
+    );
+    let state = Arc::new(State::new(source, static_addresses));
+    State::fetch_all(&state).await.unwrap();
+
+    let publishes = replay_funding(&state, &records).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(!publishes.is_empty());
+    assert!(publishes.iter().all(ReplayedPublish::matches));
+}
+
+/// Applies the data points of `UpdateFundingData` the way the funding program would,
+/// without the EMA
+fn apply_funding_updates(
+    ledger: &Ledger,
+    tx: &VersionedTransaction,
+) -> Result<Vec<(Pubkey, Account)>, TransactionError> {
+    let keys = tx.message.static_account_keys();
+    let mut writes = vec![];
+
+    for (i, ix) in tx.message.instructions().iter().enumerate() {
+        if keys[ix.program_id_index as usize] != funding_program::id() {
+            continue;
+        }
+        let Ok(InstructionData::UpdateFundingData { data_point, .. }) =
+            InstructionData::try_from_slice(&ix.data)
+        else {
+            return Err(TransactionError::InstructionError(
+                i as u8,
+                InstructionError::InvalidInstructionData,
+            ));
+        };
+
+        let address = keys[ix.accounts[1] as usize];
+        let (_, Some(ai)) = ledger.account(&address) else {
+            return Err(TransactionError::AccountNotFound);
+        };
+        let mut fixed: FundingAccountFixed = bytemuck::pod_read_unaligned(&ai.data);
+        fixed.last_updated_ts = TS;
+        fixed.funding_ema = PodOptionI64::from(Some(data_point));
+        writes.push((
+            address,
+            account(bytemuck::bytes_of(&fixed).to_vec(), ai.owner),
+        ));
+    }
+
+    Ok(writes)
+}
+
+#[tokio::test]
+async fn test_relayer_sends_updates() {
+    let (market, accounts) = drift_market(0, TS);
+    let funding_account = FundingAccountLoader::pda(0, 0, &Exchange::Drift).0;
+    let cluster = FakeCluster::start().await.unwrap().with_accounts(accounts);
+    cluster.ledger().set_unix_timestamp(TS);
+
+    // weak, the ledger owns the handler
+    let ledger = Arc::downgrade(cluster.ledger());
+    cluster
+        .ledger()
+        .set_transaction_handler(Box::new(move |tx| {
+            apply_funding_updates(&ledger.upgrade().unwrap(), tx)
+        }));
+
+    // the first round exhausts its retries, the second lands
+    cluster.faults().script(
+        "sendTransaction",
+        [
+            Fault::Error {
+                code: -32002,
+                message: "Transaction simulation failed".to_string(),
+            },
+            Fault::Disconnect,
+        ],
+    );
+
+    let (_, handles) = start_relayer(
+        &cluster,
+        &vec![market],
+        wallet(),
+        None,
+        fast_relayer_config(),
+    )
+    .await;
+
+    let fixed = timeout(Duration::from_secs(10), async {
+        loop {
+            let (_, ai) = cluster.ledger().account(&funding_account);
+            let fixed: FundingAccountFixed = bytemuck::pod_read_unaligned(&ai.unwrap().data);
+            if fixed.funding_ema().is_some() {
+                return fixed;
+            }
+
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(fixed.last_updated_ts, TS);
+    assert!(handles.iter().all(|handle| !handle.is_finished()));
+    assert!(cluster
+        .ledger()
+        .transactions()
+        .iter()
+        .all(|landed| landed.status.is_ok()));
+    handles.iter().for_each(|handle| handle.abort());
+}
@@ -0,0 +1,215 @@
+//! `GeyserAccountSource` against the fake cluster Geyser endpoint
+
+use std::time::Duration;
+
+use bot::{
+    account_source::{
+        geyser::GeyserAccountFilter, AccountSource, AccountSubscription, AccountUpdate,
+        GeyserAccountSource,
+    },
+    utils::{
+        deser::AccountEncoding,
+        websocket_client::{Backoff, Notification},
+    },
+};
+use fake_cluster::{FakeCluster, Fault};
+use futures_util::{Stream, StreamExt};
+use solana_sdk::pubkey::Pubkey;
+use tokio::time::timeout;
+
+mod common;
+use common::{account, rpc_client};
+
+const UPDATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn geyser_source(cluster: &FakeCluster) -> GeyserAccountSource {
+    GeyserAccountSource::new(rpc_client(cluster), cluster.geyser_url()).with_backoff(Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(100),
+    })
+}
+
+async fn next_update<T>(stream: &mut (impl Stream<Item = Notification<T>> + Unpin)) -> T {
+    match timeout(UPDATE_TIMEOUT, stream.next()).await.unwrap() {
+        Some(Notification::Value(value)) => value,
+        _ => panic!("Expected an account update"),
+    }
+}
+
+#[tokio::test]
+async fn test_owner_filter() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let source = geyser_source(&cluster);
+    let owner = Pubkey::new_unique();
+    let pubkey = Pubkey::new_unique();
+
+    let mut updates = source.subscribe_accounts(
+        GeyserAccountFilter {
+            owners: vec![owner],
+            ..Default::default()
+        },
+        &AccountEncoding::base64(),
+        None,
+    );
+    // the stream subscribes in the background
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    cluster
+        .ledger()
+        .set_account(Pubkey::new_unique(), account(vec![0], Pubkey::new_unique()));
+    let slot = cluster
+        .ledger()
+        .set_account(pubkey, account(vec![1, 2], owner));
+
+    let (
+        updated,
+        AccountUpdate {
+            slot: updated_slot,
+            account,
+        },
+    ) = next_update(&mut updates).await;
+    assert_eq!(updated, pubkey);
+    assert_eq!(updated_slot, slot);
+    assert_eq!(account.owner, owner);
+    assert_eq!(account.data, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn test_data_slice() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let source = geyser_source(&cluster);
+    let pubkey = Pubkey::new_unique();
+
+    let mut updates = source
+        .subscribe(
+            pubkey,
+            &AccountEncoding::base64().with_data_slice(1, 2),
+            None,
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    cluster
+        .ledger()
+        .set_account(pubkey, account(vec![0, 1, 2, 3], Pubkey::new_unique()));
+
+    assert_eq!(next_update(&mut updates).await.account.data, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn test_gap_after_disconnect() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let source = geyser_source(&cluster);
+    let pubkey = Pubkey::new_unique();
+
+    let mut updates = source
+        .subscribe(pubkey, &AccountEncoding::base64(), None)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    cluster.disconnect_geyser_streams();
+
+    let notification = timeout(UPDATE_TIMEOUT, updates.next()).await.unwrap();
+    assert!(matches!(notification, Some(Notification::Gap)));
+
+    let slot = cluster
+        .ledger()
+        .set_account(pubkey, account(vec![1], Pubkey::new_unique()));
+    assert_eq!(next_update(&mut updates).await.slot, slot);
+}
+
+#[tokio::test]
+async fn test_subscribe_retry() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let source = geyser_source(&cluster);
+    let pubkey = Pubkey::new_unique();
+
+    cluster.faults().script(
+        "geyserSubscribe",
+        [
+            Fault::Error {
+                code: 0,
+                message: "Unavailable".to_string(),
+            },
+            Fault::Disconnect,
+        ],
+    );
+
+    let mut updates = source
+        .subscribe(pubkey, &AccountEncoding::base64(), None)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // no gap, nothing was streamed before the subscription succeeded
+    let slot = cluster
+        .ledger()
+        .set_account(pubkey, account(vec![1], Pubkey::new_unique()));
+    assert_eq!(next_update(&mut updates).await.slot, slot);
+}
+
+#[tokio::test]
+async fn test_silence_timeout() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let source = geyser_source(&cluster);
+    let pubkey = Pubkey::new_unique();
+
+    let mut updates = source
+        .subscribe(
+            pubkey,
+            &AccountEncoding::base64(),
+            Some(Duration::from_millis(300)),
+        )
+        .await
+        .unwrap();
+
+    // the stream is resubscribed after the silence and the gap signaled
+    let notification = timeout(UPDATE_TIMEOUT, updates.next()).await.unwrap();
+    assert!(matches!(notification, Some(Notification::Gap)));
+}
+
+#[tokio::test]
+async fn test_subscribe_many_shares_stream() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let source = geyser_source(&cluster);
+    let pubkeys = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+    let subscriptions = pubkeys
+        .iter()
+        .map(|address| AccountSubscription {
+            address: *address,
+            encoding: AccountEncoding::base64(),
+            silence_timeout: None,
+        })
+        .collect::<Vec<AccountSubscription>>();
+    let mut updates = source.subscribe_many(&subscriptions).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(cluster.geyser_stream_count(), 1);
+
+    for pubkey in pubkeys {
+        let slot = cluster
+            .ledger()
+            .set_account(pubkey, account(vec![1], Pubkey::new_unique()));
+
+        match timeout(UPDATE_TIMEOUT, updates.next()).await.unwrap() {
+            Some((updated, Notification::Value(update))) => {
+                assert_eq!(updated, pubkey);
+                assert_eq!(update.slot, slot);
+            }
+            _ => panic!("Expected an account update"),
+        }
+    }
+
+    // the gap of the shared stream is signaled for each account
+    cluster.disconnect_geyser_streams();
+    let mut gaps = vec![];
+    for _ in pubkeys {
+        match timeout(UPDATE_TIMEOUT, updates.next()).await.unwrap() {
+            Some((address, Notification::Gap)) => gaps.push(address),
+            _ => panic!("Expected a gap"),
+        }
+    }
+    assert_eq!(gaps, pubkeys);
+}
@@ -0,0 +1,257 @@
+//! Bot RPC paths against the fake cluster JSON-RPC endpoint
+
+use std::time::Duration;
+
+use bot::{
+    account_source::{AccountSource, BatchedFetch, RpcAccountSource},
+    error::{Error, RpcErrorKind},
+    services::cluster_clock::ClusterClock,
+    utils::{
+        deser::AccountEncoding,
+        transaction::{build_signed_transaction, send_and_confirm_transaction, TransactionResult},
+        websocket_client::Backoff,
+    },
+};
+use fake_cluster::{FakeCluster, Fault};
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    transaction::TransactionError,
+};
+
+mod common;
+use common::{account, rpc_client, wallet};
+
+#[tokio::test]
+async fn test_get_multiple_accounts() {
+    let pubkey = Pubkey::new_unique();
+    let cluster = FakeCluster::start()
+        .await
+        .unwrap()
+        .with_accounts([(pubkey, account(vec![1, 2, 3], Pubkey::new_unique()))]);
+    let source = RpcAccountSource::new(rpc_client(&cluster), Duration::from_secs(1));
+
+    let (slot, accounts) = source
+        .get_multiple_accounts(
+            &[pubkey, Pubkey::new_unique()],
+            &AccountEncoding::base64(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(slot, cluster.ledger().slot());
+    assert_eq!(accounts[0].as_ref().unwrap().data, vec![1, 2, 3]);
+    assert!(accounts[1].is_none());
+}
+
+#[tokio::test]
+async fn test_get_multiple_accounts_faults() {
+    let pubkey = Pubkey::new_unique();
+    let cluster = FakeCluster::start()
+        .await
+        .unwrap()
+        .with_accounts([(pubkey, account(vec![1], Pubkey::new_unique()))]);
+    let source = RpcAccountSource::new(rpc_client(&cluster), Duration::from_secs(1));
+
+    cluster.faults().script(
+        "getMultipleAccounts",
+        [
+            Fault::node_unhealthy(),
+            Fault::Disconnect,
+            Fault::Delay(Duration::from_millis(100)),
+        ],
+    );
+
+    for kind in [RpcErrorKind::NodeUnhealthy, RpcErrorKind::Transport] {
+        let res = source
+            .get_multiple_accounts(&[pubkey], &AccountEncoding::base64(), None)
+            .await;
+        assert!(matches!(res, Err(Error::RpcError(k)) if k == kind));
+    }
+
+    let (_, accounts) = source
+        .get_multiple_accounts(&[pubkey], &AccountEncoding::base64(), None)
+        .await
+        .unwrap();
+    assert_eq!(accounts[0].as_ref().unwrap().data, vec![1]);
+}
+
+#[tokio::test]
+async fn test_get_multiple_accounts_min_context_slot() {
+    let pubkey = Pubkey::new_unique();
+    let cluster = FakeCluster::start()
+        .await
+        .unwrap()
+        .with_accounts([(pubkey, account(vec![1], Pubkey::new_unique()))]);
+    let source = RpcAccountSource::new(rpc_client(&cluster), Duration::from_secs(1));
+    let slot = cluster.ledger().slot();
+
+    let res = source
+        .get_multiple_accounts(&[pubkey], &AccountEncoding::base64(), Some(slot + 1))
+        .await;
+    assert!(matches!(
+        res,
+        Err(Error::RpcError(RpcErrorKind::MinContextSlotNotReached))
+    ));
+
+    cluster.ledger().advance_slot(1);
+    let (context_slot, accounts) = source
+        .get_multiple_accounts(&[pubkey], &AccountEncoding::base64(), Some(slot + 1))
+        .await
+        .unwrap();
+    assert_eq!(context_slot, slot + 1);
+    assert_eq!(accounts[0].as_ref().unwrap().data, vec![1]);
+}
+
+#[tokio::test]
+async fn test_batched_fetch() {
+    let pubkeys = (0..250).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    let cluster = FakeCluster::start().await.unwrap().with_accounts(
+        pubkeys
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % 50 != 0)
+            .map(|(i, pubkey)| (*pubkey, account(vec![i as u8], Pubkey::new_unique()))),
+    );
+    let source = RpcAccountSource::new(rpc_client(&cluster), Duration::from_secs(1));
+    let backoff = Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(10),
+    };
+
+    // more keys than a request accepts, and a transient error to retry
+    cluster
+        .faults()
+        .script("getMultipleAccounts", [Fault::node_unhealthy()]);
+
+    let fetched = BatchedFetch::default()
+        .with_backoff(backoff)
+        .get_multiple_accounts(&source, &pubkeys, &AccountEncoding::base64(), None)
+        .await;
+
+    assert_eq!(fetched.len(), pubkeys.len());
+    for (i, fetched) in fetched.into_iter().enumerate() {
+        match fetched.unwrap() {
+            Some(update) => assert_eq!(update.account.data, vec![i as u8]),
+            None => assert_eq!(i % 50, 0),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_batched_fetch_failed_chunk() {
+    let pubkeys = (0..6).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    let cluster = FakeCluster::start().await.unwrap().with_accounts(
+        pubkeys
+            .iter()
+            .map(|pubkey| (*pubkey, account(vec![1], Pubkey::new_unique()))),
+    );
+    let source = RpcAccountSource::new(rpc_client(&cluster), Duration::from_secs(1));
+
+    cluster.faults().script(
+        "getMultipleAccounts",
+        [Fault::Pass, Fault::node_unhealthy()],
+    );
+
+    let fetched = BatchedFetch::default()
+        .with_chunk_size(2)
+        .with_concurrency(1)
+        .with_retries(0)
+        .get_multiple_accounts(&source, &pubkeys, &AccountEncoding::base64(), None)
+        .await;
+
+    assert!(fetched[0..2].iter().all(|f| matches!(f, Ok(Some(_)))));
+    assert!(fetched[2..4].iter().all(|f| matches!(
+        f,
+        Err(Error::FetchChunkFailed(e)) if matches!(**e, Error::RpcError(RpcErrorKind::NodeUnhealthy))
+    )));
+    assert!(fetched[4..6].iter().all(|f| matches!(f, Ok(Some(_)))));
+}
+
+#[tokio::test]
+async fn test_batched_fetch_rejected_request() {
+    let pubkey = Pubkey::new_unique();
+    let cluster = FakeCluster::start()
+        .await
+        .unwrap()
+        .with_accounts([(pubkey, account(vec![1], Pubkey::new_unique()))]);
+    let source = RpcAccountSource::new(rpc_client(&cluster), Duration::from_secs(1));
+
+    // retrying would succeed, the request is rejected so it is not retried
+    cluster.faults().script(
+        "getMultipleAccounts",
+        [Fault::Error {
+            code: -32602,
+            message: "Invalid params".to_string(),
+        }],
+    );
+
+    let fetched = BatchedFetch::default()
+        .get_multiple_accounts(&source, &[pubkey], &AccountEncoding::base64(), None)
+        .await;
+
+    assert!(matches!(
+        &fetched[0],
+        Err(Error::FetchChunkFailed(e)) if matches!(**e, Error::RpcError(RpcErrorKind::Other))
+    ));
+}
+
+#[tokio::test]
+async fn test_cluster_clock() {
+    let cluster = FakeCluster::start().await.unwrap();
+    cluster.ledger().set_unix_timestamp(1_700_000_000);
+
+    let clock = ClusterClock::new(rpc_client(&cluster)).await.unwrap();
+    let now_ts = clock.now_ts().await;
+
+    assert!((1_700_000_000..1_700_000_002).contains(&now_ts));
+}
+
+#[tokio::test]
+async fn test_send_and_confirm_transaction() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let rpc_client = rpc_client(&cluster);
+    let wallet = wallet();
+
+    let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]);
+    let tx = build_signed_transaction(&rpc_client, &wallet, &[instruction], &[])
+        .await
+        .unwrap();
+
+    let res = send_and_confirm_transaction(&rpc_client, &tx)
+        .await
+        .unwrap();
+    assert!(res.is_success());
+
+    let transactions = cluster.ledger().transactions();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].transaction.signatures, tx.signatures);
+}
+
+#[tokio::test]
+async fn test_send_and_confirm_failed_transaction() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let rpc_client = rpc_client(&cluster);
+    let wallet = wallet();
+
+    cluster.ledger().set_transaction_handler(Box::new(|_| {
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(1),
+        ))
+    }));
+
+    let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]);
+    let tx = build_signed_transaction(&rpc_client, &wallet, &[instruction], &[])
+        .await
+        .unwrap();
+
+    let res = send_and_confirm_transaction(&rpc_client, &tx)
+        .await
+        .unwrap();
+    assert!(matches!(
+        res,
+        TransactionResult::Error(_, TransactionError::InstructionError(0, _))
+    ));
+}
@@ -0,0 +1,163 @@
+//! `State` subscriptions against the fake cluster
+
+use std::{sync::Arc, time::Duration};
+
+use bot::{
+    account_source::{AccountSource, GeyserAccountSource, LocalAccountSource, RpcAccountSource},
+    addresses::StaticAddresses,
+    error::Error,
+    state::{fetch_markets, State, DEFAULT_SNAPSHOT_SLOT_WINDOW},
+    utils::{
+        account_log::{first_recorded_accounts, read_log, LogRecord, Recorder},
+        websocket_client::Backoff,
+    },
+};
+use drift::accounts::PerpMarket as DriftPerpMarket;
+use fake_cluster::FakeCluster;
+use pyth_sdk_solana::state::PriceAccount;
+use solana_sdk::pubkey::Pubkey;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::{account, drift_market, rpc_client};
+
+const TS: i64 = 1_700_000_000;
+
+/// State tracking the drift market and its oracle
+async fn drift_state(
+    source: Arc<dyn AccountSource>,
+    market: Pubkey,
+    recorder: Option<Arc<Recorder>>,
+) -> Arc<State> {
+    let mut static_addresses = StaticAddresses::new();
+    static_addresses.set_drift_markets(
+        &fetch_markets::<DriftPerpMarket>(source.as_ref(), &vec![market])
+            .await
+            .unwrap(),
+    );
+
+    let mut state = State::new(source, static_addresses);
+    if let Some(recorder) = recorder {
+        state = state.with_recorder(recorder);
+    }
+    Arc::new(state)
+}
+
+async fn wait_for_snapshot(state: &State, market: Pubkey) {
+    timeout(Duration::from_secs(5), async {
+        while state.get_drift_market_and_oracle(market).await.is_err() {
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_quiet_market_leaves_snapshot_window() {
+    let (market, accounts) = drift_market(0, TS);
+    let oracle = accounts[1].0;
+    let cluster = FakeCluster::start().await.unwrap().with_accounts(accounts);
+    let source = Arc::new(RpcAccountSource::new(
+        rpc_client(&cluster),
+        Duration::from_millis(20),
+    ));
+    let state = drift_state(source, market, None).await;
+    let handle = State::subscribe_to_accounts(state.clone());
+    wait_for_snapshot(&state, market).await;
+
+    // the oracle keeps ticking past the window, the market stays untouched
+    let (_, ai) = cluster.ledger().account(&oracle);
+    let ai = ai.unwrap();
+    let mut price_account: PriceAccount = bytemuck::pod_read_unaligned(&ai.data);
+    for _ in 0..=DEFAULT_SNAPSHOT_SLOT_WINDOW {
+        price_account.timestamp += 1;
+        cluster.ledger().set_account(
+            oracle,
+            account(bytemuck::bytes_of(&price_account).to_vec(), ai.owner),
+        );
+    }
+
+    timeout(Duration::from_secs(5), async {
+        loop {
+            let res = state.get_drift_market_and_oracle(market).await;
+            if matches!(res, Err(Error::SnapshotSlotWindowExceeded)) {
+                break;
+            }
+
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_replay_gaps() {
+    let (market, accounts) = drift_market(0, TS);
+    let cluster = FakeCluster::start().await.unwrap().with_accounts(accounts);
+    let source = Arc::new(
+        GeyserAccountSource::new(rpc_client(&cluster), cluster.geyser_url()).with_backoff(
+            Backoff {
+                initial: Duration::from_millis(10),
+                max: Duration::from_millis(100),
+            },
+        ),
+    );
+    let path = std::env::temp_dir().join(format!("state-{}.log", Pubkey::new_unique()));
+    let recorder = Arc::new(Recorder::create(&path).unwrap());
+
+    let state = drift_state(source, market, Some(recorder)).await;
+    let handle = State::subscribe_to_accounts(state.clone());
+    wait_for_snapshot(&state, market).await;
+    sleep(Duration::from_millis(200)).await;
+
+    // every account is refetched, its gap cleared once the refetch lands
+    cluster.disconnect_geyser_streams();
+    let records = timeout(Duration::from_secs(5), async {
+        loop {
+            let records = read_log(&path).unwrap();
+            let cleared = records
+                .iter()
+                .filter(|r| matches!(r, LogRecord::GapCleared { .. }))
+                .count();
+            if cleared == 2 {
+                return records;
+            }
+
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+    handle.abort();
+    assert!(records.iter().any(|r| matches!(r, LogRecord::Gap { .. })));
+
+    let replayed = drift_state(
+        Arc::new(LocalAccountSource::from_data(
+            first_recorded_accounts(&records).unwrap(),
+        )),
+        market,
+        None,
+    )
+    .await;
+    State::fetch_all(&replayed).await.unwrap();
+    for record in records.iter() {
+        replayed.ingest(record).await.unwrap();
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        replayed
+            .get_drift_market_and_oracle(market)
+            .await
+            .unwrap()
+            .slot,
+        state
+            .get_drift_market_and_oracle(market)
+            .await
+            .unwrap()
+            .slot,
+    );
+}
@@ -0,0 +1,251 @@
+//! `WebsocketClient` against the fake cluster pubsub
+
+use std::time::Duration;
+
+use bot::utils::websocket_client::{ConnectionStatus, Notification, WebsocketError};
+use fake_cluster::{FakeCluster, Fault};
+use futures_util::StreamExt;
+use solana_account_decoder::UiAccountEncoding;
+use solana_rpc_client_api::config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::{account, ws_client, ws_client_with_urls};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn base64_config() -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        ..RpcAccountInfoConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_account_notifications() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let (client, _handle) = ws_client(&cluster, REQUEST_TIMEOUT).await;
+    let pubkey = Pubkey::new_unique();
+
+    let (_, mut stream) = client
+        .account_subscribe(pubkey, base64_config(), None)
+        .await
+        .unwrap();
+
+    let slot = cluster
+        .ledger()
+        .set_account(pubkey, account(vec![1, 2, 3], Pubkey::new_unique()));
+
+    let Some(Notification::Value(response)) =
+        timeout(REQUEST_TIMEOUT, stream.next()).await.unwrap()
+    else {
+        panic!("Expected an account notification");
+    };
+    assert_eq!(response.context.slot, slot);
+    assert_eq!(
+        response.value.decode::<Account>().unwrap().data,
+        vec![1, 2, 3]
+    );
+}
+
+#[tokio::test]
+async fn test_program_notifications() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let (client, _handle) = ws_client(&cluster, REQUEST_TIMEOUT).await;
+    let program_id = Pubkey::new_unique();
+    let pubkey = Pubkey::new_unique();
+
+    let (_, mut stream) = client
+        .program_subscribe(
+            program_id,
+            RpcProgramAccountsConfig {
+                account_config: base64_config(),
+                ..RpcProgramAccountsConfig::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+    // other programs are not notified
+    cluster
+        .ledger()
+        .set_account(Pubkey::new_unique(), account(vec![0], Pubkey::new_unique()));
+    cluster
+        .ledger()
+        .set_account(pubkey, account(vec![1], program_id));
+
+    let Some(Notification::Value(response)) =
+        timeout(REQUEST_TIMEOUT, stream.next()).await.unwrap()
+    else {
+        panic!("Expected a program notification");
+    };
+    assert_eq!(response.value.pubkey, pubkey.to_string());
+}
+
+#[tokio::test]
+async fn test_gap_after_disconnect() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let (client, _handle) = ws_client(&cluster, REQUEST_TIMEOUT).await;
+    let pubkey = Pubkey::new_unique();
+
+    let (_, mut stream) = client
+        .account_subscribe(pubkey, base64_config(), None)
+        .await
+        .unwrap();
+
+    cluster.disconnect_websockets();
+
+    // the gap is signaled once the subscription is replayed on the new connection
+    let notification = timeout(REQUEST_TIMEOUT, stream.next()).await.unwrap();
+    assert!(matches!(notification, Some(Notification::Gap)));
+
+    let slot = cluster
+        .ledger()
+        .set_account(pubkey, account(vec![1], Pubkey::new_unique()));
+
+    let Some(Notification::Value(response)) =
+        timeout(REQUEST_TIMEOUT, stream.next()).await.unwrap()
+    else {
+        panic!("Expected an account notification after the gap");
+    };
+    assert_eq!(response.context.slot, slot);
+}
+
+#[tokio::test]
+async fn test_failover_after_drop() {
+    let primary = FakeCluster::start().await.unwrap();
+    let fallback = FakeCluster::start().await.unwrap();
+    let (client, _handle) =
+        ws_client_with_urls(vec![primary.ws_url(), fallback.ws_url()], REQUEST_TIMEOUT).await;
+    let pubkey = Pubkey::new_unique();
+
+    let (_, mut stream) = client
+        .account_subscribe(pubkey, base64_config(), None)
+        .await
+        .unwrap();
+
+    // a dropped connection moves on to the next endpoint
+    primary.disconnect_websockets();
+
+    let notification = timeout(REQUEST_TIMEOUT, stream.next()).await.unwrap();
+    assert!(matches!(notification, Some(Notification::Gap)));
+
+    let slot = fallback
+        .ledger()
+        .set_account(pubkey, account(vec![1], Pubkey::new_unique()));
+
+    let Some(Notification::Value(response)) =
+        timeout(REQUEST_TIMEOUT, stream.next()).await.unwrap()
+    else {
+        panic!("Expected an account notification from the fallback endpoint");
+    };
+    assert_eq!(response.context.slot, slot);
+}
+
+#[tokio::test]
+async fn test_subscribe_error() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let (client, _handle) = ws_client(&cluster, REQUEST_TIMEOUT).await;
+
+    cluster.faults().script(
+        "accountSubscribe",
+        [Fault::Error {
+            code: -32602,
+            message: "Invalid params".to_string(),
+        }],
+    );
+
+    let res = client
+        .account_subscribe(Pubkey::new_unique(), base64_config(), None)
+        .await;
+    assert!(matches!(res, Err(WebsocketError::SubscriptionFailed(_))));
+
+    // only the scripted request fails
+    let res = client
+        .account_subscribe(Pubkey::new_unique(), base64_config(), None)
+        .await;
+    assert!(res.is_ok());
+}
+
+#[tokio::test]
+async fn test_subscribe_timeout() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let (client, _handle) = ws_client(&cluster, Duration::from_millis(200)).await;
+
+    cluster.faults().script("accountSubscribe", [Fault::Drop]);
+
+    let res = client
+        .account_subscribe(Pubkey::new_unique(), base64_config(), None)
+        .await;
+    assert!(matches!(res, Err(WebsocketError::RequestTimeout)));
+}
+
+#[tokio::test]
+async fn test_dropped_notification() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let (client, _handle) = ws_client(&cluster, REQUEST_TIMEOUT).await;
+    let pubkey = Pubkey::new_unique();
+
+    let (_, mut stream) = client
+        .account_subscribe(pubkey, base64_config(), None)
+        .await
+        .unwrap();
+
+    cluster
+        .faults()
+        .script("accountNotification", [Fault::Drop, Fault::Pass]);
+    cluster
+        .ledger()
+        .set_account(pubkey, account(vec![1], Pubkey::new_unique()));
+    let slot = cluster
+        .ledger()
+        .set_account(pubkey, account(vec![2], Pubkey::new_unique()));
+
+    let Some(Notification::Value(response)) =
+        timeout(REQUEST_TIMEOUT, stream.next()).await.unwrap()
+    else {
+        panic!("Expected an account notification");
+    };
+    assert_eq!(response.context.slot, slot);
+}
+
+#[tokio::test]
+async fn test_unsubscribe_pending_replay() {
+    let cluster = FakeCluster::start().await.unwrap();
+    let (client, _handle) = ws_client(&cluster, REQUEST_TIMEOUT).await;
+    let mut status = client.connection_status();
+
+    let (subscription_id, _stream) = client
+        .account_subscribe(Pubkey::new_unique(), base64_config(), None)
+        .await
+        .unwrap();
+    assert_eq!(cluster.pubsub_subscription_count(), 1);
+
+    // the replay is confirmed after the unsubscribe
+    cluster.faults().script(
+        "accountSubscribe",
+        [Fault::Delay(Duration::from_millis(500))],
+    );
+    status.borrow_and_update();
+    cluster.disconnect_websockets();
+    timeout(REQUEST_TIMEOUT, async {
+        status.changed().await.unwrap();
+        while *status.borrow_and_update() != ConnectionStatus::Connected {
+            status.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    client.unsubscribe(subscription_id).await;
+
+    // quiet subscriptions are never notified, the confirmed replay is unsubscribed right away
+    timeout(REQUEST_TIMEOUT, async {
+        while cluster.pubsub_subscription_count() != 0 {
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,30 @@
+use num_derive::FromPrimitive;
+use solana_program::program_error::ProgramError;
+use thiserror::Error;
+
+#[derive(FromPrimitive, Error, Debug)]
+pub enum ConsumerError {
+    #[error("Funding account was not updated recently enough")]
+    StaleFundingAccount,
+
+    #[error("Funding EMA is not available yet")]
+    EmaNotAvailable,
+
+    #[error("Funding EMA is out of the accepted range")]
+    EmaOutOfRange,
+
+    #[error("Invalid relayer authority")]
+    InvalidRelayerAuthority,
+
+    #[error("Missing or invalid admin signature")]
+    InvalidAdmin,
+
+    #[error("Invalid consumer config account")]
+    InvalidConfig,
+}
+
+impl From<ConsumerError> for ProgramError {
+    fn from(value: ConsumerError) -> Self {
+        ProgramError::Custom(value as u32)
+    }
+}
@@ -0,0 +1,119 @@
+//! Program instructions reading and updating funding accounts, used as an integration
+//! reference and for instruction counts
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+use crate::id;
+
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, PartialEq)]
+pub enum ConsumerInstruction {
+    /// Only succeeds if the funding EMA is within `[min_ema, max_ema]` and the funding
+    /// account was updated in the last `max_staleness_secs`
+    ///
+    /// Accounts: funding account
+    GatedAction {
+        min_ema: i64,
+        max_ema: i64,
+        max_staleness_secs: u64,
+        /// Gate on the confidence weighted EMA instead of the plain one
+        confidence_weighted: bool,
+    },
+    /// Forwards a data point to a funding account the relayer PDA is the authority of,
+    /// only signed by the admin of the config
+    ///
+    /// Accounts: admin (signer), config, relayer authority, funding account (writable),
+    /// funding program, funding history (writable, optional)
+    RelayFundingData {
+        data_point: i64,
+        confidence: Option<u64>,
+    },
+    /// Don't do anything for comparison
+    ///
+    /// No accounts required for this instruction
+    Noop,
+    /// Creates the config, the first call sets the admin so it is meant to run right
+    /// after the program is deployed
+    ///
+    /// Accounts: payer (signer, writable), config (writable), system program
+    InitializeConfig { admin: Pubkey },
+}
+
+pub const RELAYER_AUTHORITY_NAMESPACE: &[u8; 7] = b"relayer";
+pub const CONFIG_NAMESPACE: &[u8; 6] = b"config";
+
+pub fn relayer_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RELAYER_AUTHORITY_NAMESPACE], &id())
+}
+
+pub fn config() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_NAMESPACE], &id())
+}
+
+pub fn initialize_config(payer: Pubkey, admin: Pubkey) -> Instruction {
+    Instruction::new_with_borsh(
+        id(),
+        &ConsumerInstruction::InitializeConfig { admin },
+        vec![
+            AccountMeta::new(payer, true),
+            AccountMeta::new(config().0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+pub fn gated_action(
+    funding_account: Pubkey,
+    min_ema: i64,
+    max_ema: i64,
+    max_staleness_secs: u64,
+    confidence_weighted: bool,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        id(),
+        &ConsumerInstruction::GatedAction {
+            min_ema,
+            max_ema,
+            max_staleness_secs,
+            confidence_weighted,
+        },
+        vec![AccountMeta::new_readonly(funding_account, false)],
+    )
+}
+
+pub fn relay_funding_data(
+    admin: Pubkey,
+    funding_account: Pubkey,
+    funding_history: Option<Pubkey>,
+    data_point: i64,
+    confidence: Option<u64>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(admin, true),
+        AccountMeta::new_readonly(config().0, false),
+        AccountMeta::new_readonly(relayer_authority().0, false),
+        AccountMeta::new(funding_account, false),
+        AccountMeta::new_readonly(funding_program::id(), false),
+    ];
+    if let Some(funding_history) = funding_history {
+        accounts.push(AccountMeta::new(funding_history, false));
+    }
+
+    Instruction::new_with_borsh(
+        id(),
+        &ConsumerInstruction::RelayFundingData {
+            data_point,
+            confidence,
+        },
+        accounts,
+    )
+}
+
+/// Noop instruction for comparison purposes
+pub fn noop() -> Instruction {
+    Instruction::new_with_borsh(id(), &ConsumerInstruction::Noop, vec![])
+}
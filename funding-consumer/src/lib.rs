@@ -0,0 +1,8 @@
+pub mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+
+// This is used only in local testing.
+solana_program::declare_id!("FundingConsumer1111111111111111111111111111");
@@ -0,0 +1,184 @@
+//! Program instruction processor reading funding accounts and updating them through CPI
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use funding_program::{
+    client::instructions::{update_funding_data, UpdateFundingDataAccounts},
+    state::FundingAccountFixed,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::ConsumerError,
+    instruction::{
+        config, relayer_authority, ConsumerInstruction, CONFIG_NAMESPACE,
+        RELAYER_AUTHORITY_NAMESPACE,
+    },
+    state::ConsumerConfig,
+};
+
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let instruction = ConsumerInstruction::try_from_slice(input)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    match instruction {
+        ConsumerInstruction::GatedAction {
+            min_ema,
+            max_ema,
+            max_staleness_secs,
+            confidence_weighted,
+        } => gated_action(
+            accounts,
+            min_ema,
+            max_ema,
+            max_staleness_secs,
+            confidence_weighted,
+        ),
+        ConsumerInstruction::RelayFundingData {
+            data_point,
+            confidence,
+        } => relay_funding_data(accounts, data_point, confidence),
+        ConsumerInstruction::Noop => Ok(()),
+        ConsumerInstruction::InitializeConfig { admin } => initialize_config(accounts, admin),
+    }
+}
+
+fn initialize_config(accounts: &[AccountInfo], admin: Pubkey) -> ProgramResult {
+    let mut accounts_iter = accounts.iter();
+    let payer_ai = next_account_info(&mut accounts_iter)?;
+    let config_ai = next_account_info(&mut accounts_iter)?;
+    let _system_program_ai = next_account_info(&mut accounts_iter)?;
+
+    let (address, bump) = config();
+    if config_ai.key != &address {
+        Err(ConsumerError::InvalidConfig)?;
+    }
+    if !config_ai.data_is_empty() {
+        Err(ProgramError::AccountAlreadyInitialized)?;
+    }
+
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_ai.key,
+            config_ai.key,
+            Rent::get()?.minimum_balance(ConsumerConfig::SIZE),
+            ConsumerConfig::SIZE as u64,
+            &crate::id(),
+        ),
+        &[payer_ai.clone(), config_ai.clone()],
+        &[&[CONFIG_NAMESPACE, &[bump]]],
+    )?;
+
+    ConsumerConfig { admin, bump }
+        .serialize(&mut &mut config_ai.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    msg!("Consumer admin: {}", admin);
+
+    Ok(())
+}
+
+/// Config of the program, checked to be the program owned singleton
+fn load_config(config_ai: &AccountInfo) -> Result<ConsumerConfig, ProgramError> {
+    if config_ai.key != &config().0 || config_ai.owner != &crate::id() {
+        Err(ConsumerError::InvalidConfig)?;
+    }
+
+    ConsumerConfig::try_from_slice(&config_ai.data.borrow())
+        .map_err(|_| ConsumerError::InvalidConfig.into())
+}
+
+fn gated_action(
+    accounts: &[AccountInfo],
+    min_ema: i64,
+    max_ema: i64,
+    max_staleness_secs: u64,
+    confidence_weighted: bool,
+) -> ProgramResult {
+    let mut accounts_iter = accounts.iter();
+    let funding_ai = next_account_info(&mut accounts_iter)?;
+
+    // checks the account is owned by the funding program and derived from its seeds
+    let funding_account = FundingAccountFixed::load(funding_ai)?;
+
+    let now_ts = Clock::get()?.unix_timestamp;
+    if now_ts - funding_account.last_updated_ts > max_staleness_secs as i64 {
+        Err(ConsumerError::StaleFundingAccount)?;
+    }
+
+    let ema = match confidence_weighted {
+        true => funding_account.confidence_weighted_ema(),
+        false => funding_account.funding_ema(),
+    }
+    .ok_or(ConsumerError::EmaNotAvailable)?;
+
+    if ema < min_ema || ema > max_ema {
+        Err(ConsumerError::EmaOutOfRange)?;
+    }
+
+    msg!("Action allowed, funding EMA: {}", ema);
+
+    Ok(())
+}
+
+fn relay_funding_data(
+    accounts: &[AccountInfo],
+    data_point: i64,
+    confidence: Option<u64>,
+) -> ProgramResult {
+    let mut accounts_iter = accounts.iter();
+    let admin_ai = next_account_info(&mut accounts_iter)?;
+    let config_ai = next_account_info(&mut accounts_iter)?;
+    let authority_ai = next_account_info(&mut accounts_iter)?;
+    let funding_ai = next_account_info(&mut accounts_iter)?;
+    let funding_program_ai = next_account_info(&mut accounts_iter)?;
+    let history_ai = accounts_iter.next();
+
+    // anyone could make the relayer PDA sign otherwise
+    let config = load_config(config_ai)?;
+    if !admin_ai.is_signer || admin_ai.key != &config.admin {
+        Err(ConsumerError::InvalidAdmin)?;
+    }
+
+    let (authority, bump) = relayer_authority();
+    if authority_ai.key != &authority {
+        Err(ConsumerError::InvalidRelayerAuthority)?;
+    }
+
+    let ix = update_funding_data(
+        UpdateFundingDataAccounts {
+            authority,
+            funding_account: *funding_ai.key,
+            funding_history: history_ai.map(|ai| *ai.key),
+        },
+        data_point,
+        confidence,
+    );
+
+    let mut account_infos = vec![
+        authority_ai.clone(),
+        funding_ai.clone(),
+        funding_program_ai.clone(),
+    ];
+    account_infos.extend(history_ai.cloned());
+
+    invoke_signed(
+        &ix,
+        &account_infos,
+        &[&[RELAYER_AUTHORITY_NAMESPACE, &[bump]]],
+    )
+}
@@ -0,0 +1,16 @@
+//! Consumer program accounts
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+/// Singleton set once by `InitializeConfig`, the admin is the only signer allowed to
+/// relay funding data through the relayer PDA
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, PartialEq)]
+pub struct ConsumerConfig {
+    pub admin: Pubkey,
+    pub bump: u8,
+}
+
+impl ConsumerConfig {
+    pub const SIZE: usize = 32 + 1;
+}
@@ -0,0 +1,188 @@
+//! Compute units consumed by reading funding accounts and updating them through CPI
+//!
+//! Run with `cargo test-sbf --test instruction_count -- --nocapture` so the
+//! numbers come from the compiled programs instead of the native processors.
+
+use funding_consumer::{error::ConsumerError, id, instruction, processor::process_instruction};
+use funding_program::test_support::{
+    self, funded_keypair, process, program_test, set_unix_timestamp, test_instr_exec_err,
+    test_instr_exec_ok, transfer_authority_ix, FundingAccountParams,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{processor, ProgramTestContext};
+use solana_sdk::{instruction::InstructionError, signature::Signer, transaction::TransactionError};
+
+const START_TS: i64 = 1_000_000;
+const DATA_POINT: i64 = 1_000_000;
+const UPDATE_FREQUENCY_SECS: i64 = 60;
+
+async fn start() -> ProgramTestContext {
+    let mut program_test = program_test();
+    program_test.add_program("funding_consumer", id(), processor!(process_instruction));
+    program_test.start_with_context().await
+}
+
+async fn setup_funding_account(
+    context: &mut ProgramTestContext,
+    confidence: Option<u64>,
+) -> Pubkey {
+    test_support::setup_funding_account(
+        context,
+        FundingAccountParams::new(UPDATE_FREQUENCY_SECS as u64, 12),
+        START_TS,
+        DATA_POINT,
+        confidence,
+    )
+    .await
+}
+
+fn consumer_error(error: ConsumerError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn test_noop() {
+    let mut context = start().await;
+
+    let units = test_instr_exec_ok(&mut context, instruction::noop()).await;
+    println!("Noop: {} CU", units);
+}
+
+#[tokio::test]
+async fn test_gated_action() {
+    let mut context = start().await;
+    let funding_account = setup_funding_account(&mut context, None).await;
+
+    let units = test_instr_exec_ok(
+        &mut context,
+        instruction::gated_action(funding_account, 0, 2 * DATA_POINT, 120, false),
+    )
+    .await;
+    println!("GatedAction: {} CU", units);
+
+    // no data point carries confidence
+    let err = test_instr_exec_err(
+        &mut context,
+        instruction::gated_action(funding_account, 0, 2 * DATA_POINT, 120, true),
+    )
+    .await;
+    assert_eq!(err, consumer_error(ConsumerError::EmaNotAvailable));
+
+    let err = test_instr_exec_err(
+        &mut context,
+        instruction::gated_action(funding_account, 2 * DATA_POINT, 3 * DATA_POINT, 120, false),
+    )
+    .await;
+    assert_eq!(err, consumer_error(ConsumerError::EmaOutOfRange));
+}
+
+#[tokio::test]
+async fn test_gated_action_confidence_weighted() {
+    let mut context = start().await;
+    let funding_account = setup_funding_account(&mut context, Some(10_000)).await;
+
+    let units = test_instr_exec_ok(
+        &mut context,
+        instruction::gated_action(funding_account, 0, 2 * DATA_POINT, 120, true),
+    )
+    .await;
+    println!("GatedAction (confidence weighted): {} CU", units);
+}
+
+#[tokio::test]
+async fn test_gated_action_stale() {
+    let mut context = start().await;
+    let funding_account = setup_funding_account(&mut context, None).await;
+
+    let last_update_ts = START_TS + 12 * UPDATE_FREQUENCY_SECS;
+    set_unix_timestamp(&mut context, last_update_ts + 121).await;
+
+    let err = test_instr_exec_err(
+        &mut context,
+        instruction::gated_action(funding_account, 0, 2 * DATA_POINT, 120, false),
+    )
+    .await;
+    assert_eq!(err, consumer_error(ConsumerError::StaleFundingAccount));
+}
+
+/// Funding account whose authority is the relayer PDA, the payer being the admin
+async fn setup_relayed_funding_account(context: &mut ProgramTestContext) -> Pubkey {
+    let funding_account = setup_funding_account(context, None).await;
+    let authority = context.payer.pubkey();
+    test_instr_exec_ok(
+        context,
+        transfer_authority_ix(
+            authority,
+            funding_account,
+            instruction::relayer_authority().0,
+        ),
+    )
+    .await;
+    test_instr_exec_ok(
+        context,
+        instruction::initialize_config(authority, authority),
+    )
+    .await;
+
+    set_unix_timestamp(context, START_TS + 13 * UPDATE_FREQUENCY_SECS).await;
+    funding_account
+}
+
+#[tokio::test]
+async fn test_relay_funding_data() {
+    let mut context = start().await;
+    let funding_account = setup_relayed_funding_account(&mut context).await;
+    let admin = context.payer.pubkey();
+
+    let units = test_instr_exec_ok(
+        &mut context,
+        instruction::relay_funding_data(admin, funding_account, None, DATA_POINT, None),
+    )
+    .await;
+    println!("RelayFundingData (12 data points): {} CU", units);
+}
+
+#[tokio::test]
+async fn test_relay_funding_data_unauthorized() {
+    let mut context = start().await;
+    let funding_account = setup_relayed_funding_account(&mut context).await;
+    let admin = context.payer.pubkey();
+    let attacker = funded_keypair(&mut context);
+
+    let err = process(
+        &mut context,
+        &[instruction::relay_funding_data(
+            attacker.pubkey(),
+            funding_account,
+            None,
+            DATA_POINT,
+            None,
+        )],
+        &attacker,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err, consumer_error(ConsumerError::InvalidAdmin));
+
+    // the admin is named but does not sign
+    let mut ix = instruction::relay_funding_data(admin, funding_account, None, DATA_POINT, None);
+    ix.accounts[0].is_signer = false;
+    let err = process(&mut context, &[ix], &attacker).await.unwrap_err();
+    assert_eq!(err, consumer_error(ConsumerError::InvalidAdmin));
+
+    // the config is set once
+    let err = process(
+        &mut context,
+        &[instruction::initialize_config(
+            attacker.pubkey(),
+            attacker.pubkey(),
+        )],
+        &attacker,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized)
+    );
+}
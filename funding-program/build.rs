@@ -0,0 +1,237 @@
+//! Generates the client instruction builders and account decoders from `idl.json`
+
+use std::{env, fmt::Write, fs, path::Path};
+
+use serde_json::Value;
+
+const IDL_PATH: &str = "idl.json";
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn pascal_case(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+fn rust_type(ty: &Value) -> String {
+    match ty {
+        Value::String(s) => match s.as_str() {
+            "publicKey" => "solana_program::pubkey::Pubkey".to_string(),
+            "bool" | "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64"
+            | "i128" => s.clone(),
+            _ => panic!("unsupported idl type: {}", s),
+        },
+        Value::Object(o) => {
+            if let Some(inner) = o.get("option") {
+                format!("Option<{}>", rust_type(inner))
+            } else if let Some(Value::String(defined)) = o.get("defined") {
+                format!("crate::state::{}", defined)
+            } else if let Some(Value::Array(array)) = o.get("array") {
+                format!("[{}; {}]", rust_type(&array[0]), array[1])
+            } else {
+                panic!("unsupported idl type: {}", ty)
+            }
+        }
+        _ => panic!("unsupported idl type: {}", ty),
+    }
+}
+
+fn str_field<'a>(value: &'a Value, field: &str) -> &'a str {
+    value[field]
+        .as_str()
+        .unwrap_or_else(|| panic!("missing `{}` in {}", field, value))
+}
+
+fn bool_field(value: &Value, field: &str) -> bool {
+    value[field].as_bool().unwrap_or(false)
+}
+
+fn array_field<'a>(value: &'a Value, field: &str) -> &'a Vec<Value> {
+    static EMPTY: Vec<Value> = Vec::new();
+    value[field].as_array().unwrap_or(&EMPTY)
+}
+
+fn write_docs(out: &mut String, value: &Value, indent: &str) {
+    for line in array_field(value, "docs") {
+        writeln!(out, "{}/// {}", indent, line.as_str().unwrap()).unwrap();
+    }
+}
+
+fn generate_instruction(out: &mut String, ix: &Value) {
+    let name = str_field(ix, "name");
+    let variant = pascal_case(name);
+    let accounts = array_field(ix, "accounts");
+    let args = array_field(ix, "args");
+
+    let mut seen_optional = false;
+    for account in accounts {
+        let optional = bool_field(account, "isOptional");
+        if seen_optional && !optional {
+            panic!("{}: optional accounts have to be trailing", name);
+        }
+        seen_optional |= optional;
+    }
+
+    writeln!(out, "pub struct {}Accounts {{", variant).unwrap();
+    for account in accounts.iter().filter(|a| a["address"].is_null()) {
+        write_docs(out, account, "    ");
+        let ty = if bool_field(account, "isOptional") {
+            "Option<solana_program::pubkey::Pubkey>"
+        } else {
+            "solana_program::pubkey::Pubkey"
+        };
+        writeln!(
+            out,
+            "    pub {}: {},",
+            snake_case(str_field(account, "name")),
+            ty
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    write_docs(out, ix, "");
+    writeln!(out, "#[allow(clippy::too_many_arguments)]").unwrap();
+    write!(
+        out,
+        "pub fn {}(accounts: {}Accounts",
+        snake_case(name),
+        variant
+    )
+    .unwrap();
+    for arg in args {
+        write!(
+            out,
+            ", {}: {}",
+            snake_case(str_field(arg, "name")),
+            rust_type(&arg["type"])
+        )
+        .unwrap();
+    }
+    writeln!(out, ") -> solana_program::instruction::Instruction {{").unwrap();
+
+    if args.is_empty() {
+        writeln!(
+            out,
+            "    let data = crate::instructions::InstructionData::{};",
+            variant
+        )
+        .unwrap();
+    } else {
+        writeln!(
+            out,
+            "    let data = crate::instructions::InstructionData::{} {{",
+            variant
+        )
+        .unwrap();
+        for arg in args {
+            writeln!(out, "        {},", snake_case(str_field(arg, "name"))).unwrap();
+        }
+        writeln!(out, "    }};").unwrap();
+    }
+
+    let account_meta = |account: &Value, pubkey: &str| {
+        format!(
+            "solana_program::instruction::AccountMeta {{ {}, is_signer: {}, is_writable: {} }}",
+            pubkey,
+            bool_field(account, "isSigner"),
+            bool_field(account, "isMut"),
+        )
+    };
+
+    let mutability = if seen_optional { "mut " } else { "" };
+    writeln!(out, "    let {}account_metas = vec![", mutability).unwrap();
+    for account in accounts.iter().filter(|a| !bool_field(a, "isOptional")) {
+        let pubkey = match account["address"].as_str() {
+            Some(address) => format!("pubkey: solana_program::pubkey!(\"{}\")", address),
+            None => format!(
+                "pubkey: accounts.{}",
+                snake_case(str_field(account, "name"))
+            ),
+        };
+        writeln!(out, "        {},", account_meta(account, &pubkey)).unwrap();
+    }
+    writeln!(out, "    ];").unwrap();
+    for account in accounts.iter().filter(|a| bool_field(a, "isOptional")) {
+        writeln!(
+            out,
+            "    if let Some(pubkey) = accounts.{} {{ account_metas.push({}); }}",
+            snake_case(str_field(account, "name")),
+            account_meta(account, "pubkey")
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "    solana_program::instruction::Instruction::new_with_borsh(crate::id(), &data, account_metas)"
+    )
+    .unwrap();
+    writeln!(out, "}}\n").unwrap();
+}
+
+fn generate_account_decoder(out: &mut String, account: &Value) {
+    let name = str_field(account, "name");
+
+    write_docs(out, account, "");
+    writeln!(
+        out,
+        "pub fn decode_{}(account_data: &[u8]) -> Result<crate::state::{}, DeserializeError> {{",
+        snake_case(name),
+        name
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    let bytes = account_data.get(..crate::state::{}::SIZE).ok_or(DeserializeError)?;",
+        name
+    )
+    .unwrap();
+    match account["serialization"].as_str().unwrap_or("borsh") {
+        // account data fetched over RPC is not guaranteed to be aligned
+        "bytemuck" => writeln!(out, "    Ok(bytemuck::pod_read_unaligned(bytes))").unwrap(),
+        "borsh" => writeln!(
+            out,
+            "    <crate::state::{} as borsh::BorshDeserialize>::deserialize(&mut &bytes[..]).map_err(|_| DeserializeError)",
+            name
+        )
+        .unwrap(),
+        serialization => panic!("{}: unsupported serialization {}", name, serialization),
+    }
+    writeln!(out, "}}\n").unwrap();
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", IDL_PATH);
+
+    let idl: Value = serde_json::from_str(&fs::read_to_string(IDL_PATH).unwrap()).unwrap();
+
+    let mut instructions = String::new();
+    for ix in array_field(&idl, "instructions") {
+        generate_instruction(&mut instructions, ix);
+    }
+
+    let mut accounts = String::new();
+    for account in array_field(&idl, "accounts") {
+        generate_account_decoder(&mut accounts, account);
+    }
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("instructions.rs"), instructions).unwrap();
+    fs::write(Path::new(&out_dir).join("accounts.rs"), accounts).unwrap();
+}
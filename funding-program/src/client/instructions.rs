@@ -1,167 +1,132 @@
-use solana_program::{
-    instruction::{AccountMeta, Instruction},
-    pubkey::Pubkey,
-    system_program,
-};
+use borsh::BorshSerialize;
+use solana_program::{ed25519_program, instruction::Instruction, pubkey::Pubkey};
 
-use crate::{instructions::InstructionData, state::Exchange};
+use crate::{instructions::SignedFundingUpdate, processor::ed25519};
 
-pub struct InitializeFundingAccountAccounts {
-    pub authority: Pubkey,
-    pub funding_account: Pubkey,
-}
+// Builders generated from `idl.json` by `build.rs`, one `{Instruction}Accounts` struct and
+// one builder function per `InstructionData` variant
+include!(concat!(env!("OUT_DIR"), "/instructions.rs"));
 
-pub fn initialize_funding_account(
-    accounts: InitializeFundingAccountAccounts,
-    id: u16,
-    exchange: Exchange,
-    market_index: u16,
-    update_frequency_secs: u64,
-    staleness_threshold_secs: u64,
-    period_length: u32,
-    data_points_count: u16,
-) -> Instruction {
-    let data = InstructionData::InitializeFundingAccount {
-        id,
-        exchange,
-        market_index,
-        update_frequency_secs,
-        staleness_threshold_secs,
-        period_length,
-        data_points_count,
-    };
-    let accounts = vec![
-        AccountMeta {
-            pubkey: accounts.authority,
-            is_signer: true,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: accounts.funding_account,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-    Instruction::new_with_borsh(crate::id(), &data, accounts)
+/// Bytes the funding account authority has to sign for `update_funding_data_signed`
+pub fn signed_funding_update_message(
+    funding_account: Pubkey,
+    data_point: i64,
+    confidence: Option<u64>,
+    observation_ts: i64,
+) -> Vec<u8> {
+    SignedFundingUpdate {
+        funding_account,
+        data_point,
+        confidence,
+        observation_ts,
+    }
+    .try_to_vec()
+    .unwrap()
 }
 
-pub struct ConfigureFundingAccountAccounts {
-    pub authority: Pubkey,
-    pub funding_account: Pubkey,
-}
+/// Ed25519 program instruction verifying a single signature, it has to be placed right
+/// before `update_funding_data_signed` in the transaction
+pub fn ed25519_verify(authority: Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let public_key_offset = ed25519::DATA_START;
+    let signature_offset = public_key_offset + ed25519::PUBKEY_SERIALIZED_SIZE;
+    let message_data_offset = signature_offset + ed25519::SIGNATURE_SERIALIZED_SIZE;
 
-pub fn configure_funding_account(
-    accounts: ConfigureFundingAccountAccounts,
-    update_frequency_secs: Option<u64>,
-    staleness_threshold_secs: Option<u64>,
-    period_length: Option<u32>,
-    data_points_count: Option<u16>,
-) -> Instruction {
-    let data = InstructionData::ConfigureFundingAccount {
-        update_frequency_secs,
-        staleness_threshold_secs,
-        period_length,
-        data_points_count,
-    };
-    let accounts = vec![
-        AccountMeta {
-            pubkey: accounts.authority,
-            is_signer: true,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: accounts.funding_account,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-    Instruction::new_with_borsh(crate::id(), &data, accounts)
-}
+    let mut data = Vec::with_capacity(message_data_offset + message.len());
+    // number of signatures and padding
+    data.extend_from_slice(&[1, 0]);
+    for offset in [
+        signature_offset as u16,
+        ed25519::CURRENT_INSTRUCTION_INDEX,
+        public_key_offset as u16,
+        ed25519::CURRENT_INSTRUCTION_INDEX,
+        message_data_offset as u16,
+        message.len() as u16,
+        ed25519::CURRENT_INSTRUCTION_INDEX,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(authority.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
 
-pub struct ConfigureFundingAccountAuthorityAccounts {
-    pub authority: Pubkey,
-    pub funding_account: Pubkey,
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
 }
 
-pub fn configure_funding_account_authority(
-    accounts: ConfigureFundingAccountAuthorityAccounts,
-    authority: Pubkey,
-) -> Instruction {
-    let data = InstructionData::ConfigureFundingAccountAuthority { authority };
-    let accounts = vec![
-        AccountMeta {
-            pubkey: accounts.authority,
-            is_signer: true,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: accounts.funding_account,
-            is_signer: false,
-            is_writable: true,
-        },
-    ];
-    Instruction::new_with_borsh(crate::id(), &data, accounts)
-}
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use num_traits::FromPrimitive;
+    use serde_json::Value;
+    use solana_program::pubkey::Pubkey;
 
-pub struct UpdateFundingAccountAccounts {
-    pub authority: Pubkey,
-    pub funding_account: Pubkey,
-}
+    use crate::{error::ErrorCode, instructions::InstructionData, state::Exchange};
 
-pub fn update_funding_account(
-    accounts: UpdateFundingAccountAccounts,
-    data_point: i64,
-) -> Instruction {
-    let data = InstructionData::UpdateFundingData { data_point };
-    let accounts = vec![
-        AccountMeta {
-            pubkey: accounts.authority,
-            is_signer: true,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: accounts.funding_account,
-            is_signer: false,
-            is_writable: true,
-        },
-    ];
-    Instruction::new_with_borsh(crate::id(), &data, accounts)
-}
+    fn idl() -> Value {
+        serde_json::from_str(include_str!("../../idl.json")).unwrap()
+    }
 
-pub struct CloseFundingAccountAccounts {
-    pub authority: Pubkey,
-    pub funding_account: Pubkey,
-    pub receiver: Pubkey,
-}
+    #[test]
+    fn idl_discriminants() {
+        let instructions = [
+            InstructionData::InitializeFundingAccount {
+                id: 0,
+                exchange: Exchange::Drift,
+                market_index: 0,
+                update_frequency_secs: 0,
+                staleness_threshold_secs: 0,
+                period_length: 0,
+                data_points_count: 0,
+            },
+            InstructionData::ConfigureFundingAccount {
+                update_frequency_secs: None,
+                staleness_threshold_secs: None,
+                period_length: None,
+                data_points_count: None,
+            },
+            InstructionData::ConfigureFundingAccountAuthority {
+                authority: Pubkey::default(),
+            },
+            InstructionData::UpdateFundingData {
+                data_point: 0,
+                confidence: None,
+            },
+            InstructionData::CloseFundingAccount,
+            InstructionData::InitializeFundingHistory,
+            InstructionData::UpdateFundingDataSigned {
+                data_point: 0,
+                confidence: None,
+                observation_ts: 0,
+            },
+            InstructionData::RegisterFundingAccount,
+        ];
+
+        let idl = idl();
+        let idl_instructions = idl["instructions"].as_array().unwrap();
+        assert_eq!(idl_instructions.len(), instructions.len());
+
+        for (idl_ix, ix) in idl_instructions.iter().zip(instructions) {
+            let discriminant = idl_ix["discriminant"]["value"].as_u64().unwrap();
+            assert_eq!(ix.try_to_vec().unwrap()[0] as u64, discriminant);
+        }
+    }
+
+    #[test]
+    fn idl_errors() {
+        let idl = idl();
+
+        for error in idl["errors"].as_array().unwrap() {
+            let code = error["code"].as_u64().unwrap();
+            let error_code = ErrorCode::from_u64(code).unwrap();
+
+            assert_eq!(format!("{:?}", error_code), error["name"].as_str().unwrap());
+            assert_eq!(error_code.to_string(), error["msg"].as_str().unwrap());
+        }
 
-pub fn close_funding_account(accounts: CloseFundingAccountAccounts) -> Instruction {
-    let data = InstructionData::CloseFundingAccount;
-    let accounts = vec![
-        AccountMeta {
-            pubkey: accounts.authority,
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: accounts.funding_account,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: accounts.receiver,
-            is_signer: false,
-            is_writable: true,
-        },
-    ];
-    Instruction::new_with_borsh(crate::id(), &data, accounts)
+        let count = idl["errors"].as_array().unwrap().len() as u64;
+        assert!(ErrorCode::from_u64(count).is_none());
+    }
 }
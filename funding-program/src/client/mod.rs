@@ -1,2 +1,4 @@
 pub mod instructions;
+#[cfg(feature = "client")]
+pub mod rpc;
 pub mod state;
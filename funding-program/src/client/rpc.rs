@@ -0,0 +1,407 @@
+use std::sync::Arc;
+
+use num_traits::FromPrimitive;
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use thiserror::Error;
+
+use crate::{
+    client::{
+        instructions::{
+            close_funding_account, configure_funding_account, configure_funding_account_authority,
+            initialize_funding_account, initialize_funding_history, register_funding_account,
+            update_funding_data, CloseFundingAccountAccounts, ConfigureFundingAccountAccounts,
+            ConfigureFundingAccountAuthorityAccounts, InitializeFundingAccountAccounts,
+            InitializeFundingHistoryAccounts, RegisterFundingAccountAccounts,
+            UpdateFundingDataAccounts,
+        },
+        state::{
+            load_funding_account, load_funding_history, load_funding_registry, FundingAccount,
+            FundingHistory, FundingRegistry,
+        },
+    },
+    error::ErrorCode,
+    state::{Exchange, FundingAccountLoader, FundingHistoryLoader, FundingRegistryLoader},
+};
+
+/// Max accounts per `getMultipleAccounts` request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+#[derive(Error, Debug)]
+pub enum FundingClientError {
+    #[error("Funding program error: {0}")]
+    Program(ErrorCode),
+
+    #[error("Transaction error: {0}")]
+    Transaction(TransactionError),
+
+    #[error("Rpc error: {0}")]
+    Rpc(ClientError),
+
+    #[error("Could not deserialize account: {0}")]
+    Deserialize(Pubkey),
+}
+
+pub type FundingClientResult<T> = Result<T, FundingClientError>;
+
+impl From<ClientError> for FundingClientError {
+    fn from(value: ClientError) -> Self {
+        Self::Rpc(value)
+    }
+}
+
+/// Maps a custom error returned by one of the funding program `instructions` back to its
+/// `ErrorCode`, custom errors of other programs are not decoded
+pub fn decode_transaction_error(
+    error: &TransactionError,
+    instructions: &[Instruction],
+) -> Option<ErrorCode> {
+    match error {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            let ix = instructions.get(*index as usize)?;
+            if ix.program_id != crate::id() {
+                return None;
+            }
+
+            ErrorCode::from_u32(*code)
+        }
+        _ => None,
+    }
+}
+
+/// Sends funding program instructions signed by `authority` and decodes funding accounts
+pub struct FundingProgramClient<S: Signer> {
+    pub rpc_client: Arc<RpcClient>,
+    pub authority: Arc<S>,
+}
+
+impl<S: Signer> FundingProgramClient<S> {
+    pub fn new(rpc_client: Arc<RpcClient>, authority: Arc<S>) -> Self {
+        Self {
+            rpc_client,
+            authority,
+        }
+    }
+
+    pub fn authority(&self) -> Pubkey {
+        self.authority.pubkey()
+    }
+
+    /// Sends and confirms `instructions` paid by the authority, custom errors of the
+    /// funding program are returned as `FundingClientError::Program`
+    pub async fn send(&self, instructions: &[Instruction]) -> FundingClientResult<Signature> {
+        let authority = self.authority();
+        let blockhash = self.rpc_client.get_latest_blockhash().await?;
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&authority),
+            &[self.authority.as_ref()],
+            blockhash,
+        );
+
+        self.rpc_client
+            .send_and_confirm_transaction(&tx)
+            .await
+            .map_err(|e| match e.get_transaction_error() {
+                Some(tx_error) => match decode_transaction_error(&tx_error, instructions) {
+                    Some(error_code) => FundingClientError::Program(error_code),
+                    None => FundingClientError::Transaction(tx_error),
+                },
+                None => FundingClientError::Rpc(e),
+            })
+    }
+
+    async fn account_exists(&self, address: &Pubkey) -> FundingClientResult<bool> {
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(address, self.rpc_client.commitment())
+            .await?
+            .value;
+
+        Ok(account.is_some_and(|ai| !ai.data.is_empty()))
+    }
+
+    /// Returns the funding account address
+    #[allow(clippy::too_many_arguments)]
+    pub async fn initialize(
+        &self,
+        id: u16,
+        exchange: Exchange,
+        market_index: u16,
+        update_frequency_secs: u64,
+        staleness_threshold_secs: u64,
+        period_length: u32,
+        data_points_count: u16,
+    ) -> FundingClientResult<Pubkey> {
+        let authority = self.authority();
+        let funding_account = FundingAccountLoader::pda(id, market_index, &exchange).0;
+
+        self.send(&[initialize_funding_account(
+            InitializeFundingAccountAccounts {
+                authority,
+                funding_account,
+                funding_registry: FundingRegistryLoader::pda(&authority).0,
+            },
+            id,
+            exchange,
+            market_index,
+            update_frequency_secs,
+            staleness_threshold_secs,
+            period_length,
+            data_points_count,
+        )])
+        .await?;
+
+        Ok(funding_account)
+    }
+
+    /// Returns the funding history address
+    pub async fn initialize_history(
+        &self,
+        funding_account: &Pubkey,
+    ) -> FundingClientResult<Pubkey> {
+        let funding_history = FundingHistoryLoader::pda(funding_account).0;
+
+        self.send(&[initialize_funding_history(
+            InitializeFundingHistoryAccounts {
+                authority: self.authority(),
+                funding_account: *funding_account,
+                funding_history,
+            },
+        )])
+        .await?;
+
+        Ok(funding_history)
+    }
+
+    pub async fn configure(
+        &self,
+        funding_account: &Pubkey,
+        update_frequency_secs: Option<u64>,
+        staleness_threshold_secs: Option<u64>,
+        period_length: Option<u32>,
+        data_points_count: Option<u16>,
+    ) -> FundingClientResult<Signature> {
+        self.send(&[configure_funding_account(
+            ConfigureFundingAccountAccounts {
+                authority: self.authority(),
+                funding_account: *funding_account,
+            },
+            update_frequency_secs,
+            staleness_threshold_secs,
+            period_length,
+            data_points_count,
+        )])
+        .await
+    }
+
+    /// Also moves the funding account to the registry of `new_authority`
+    pub async fn configure_authority(
+        &self,
+        funding_account: &Pubkey,
+        new_authority: Pubkey,
+    ) -> FundingClientResult<Signature> {
+        let authority = self.authority();
+
+        self.send(&[configure_funding_account_authority(
+            ConfigureFundingAccountAuthorityAccounts {
+                authority,
+                funding_account: *funding_account,
+                funding_registry: FundingRegistryLoader::pda(&authority).0,
+                new_funding_registry: FundingRegistryLoader::pda(&new_authority).0,
+            },
+            new_authority,
+        )])
+        .await
+    }
+
+    /// Adds a funding account created before the registry existed to the authority registry
+    pub async fn register(&self, funding_account: &Pubkey) -> FundingClientResult<Signature> {
+        let authority = self.authority();
+
+        self.send(&[register_funding_account(RegisterFundingAccountAccounts {
+            authority,
+            funding_account: *funding_account,
+            funding_registry: FundingRegistryLoader::pda(&authority).0,
+        })])
+        .await
+    }
+
+    /// Also rolls the funding history candles if the history account exists
+    pub async fn update(
+        &self,
+        funding_account: &Pubkey,
+        data_point: i64,
+        confidence: Option<u64>,
+    ) -> FundingClientResult<Signature> {
+        let funding_history = FundingHistoryLoader::pda(funding_account).0;
+        let funding_history = match self.account_exists(&funding_history).await? {
+            true => Some(funding_history),
+            false => None,
+        };
+
+        self.send(&[update_funding_data(
+            UpdateFundingDataAccounts {
+                authority: self.authority(),
+                funding_account: *funding_account,
+                funding_history,
+            },
+            data_point,
+            confidence,
+        )])
+        .await
+    }
+
+    /// Closes the funding history too if it exists
+    pub async fn close(
+        &self,
+        funding_account: &Pubkey,
+        receiver: &Pubkey,
+    ) -> FundingClientResult<Signature> {
+        let authority = self.authority();
+
+        self.send(&[close_funding_account(CloseFundingAccountAccounts {
+            authority,
+            funding_account: *funding_account,
+            receiver: *receiver,
+            funding_registry: FundingRegistryLoader::pda(&authority).0,
+            funding_history: FundingHistoryLoader::pda(funding_account).0,
+        })])
+        .await
+    }
+
+    pub async fn fetch(
+        &self,
+        id: u16,
+        market_index: u16,
+        exchange: Exchange,
+    ) -> FundingClientResult<Option<FundingAccount>> {
+        let address = FundingAccountLoader::pda(id, market_index, &exchange).0;
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&address, self.rpc_client.commitment())
+            .await?
+            .value;
+
+        match account {
+            Some(ai) if !ai.data.is_empty() => load_funding_account(&ai.data)
+                .map(Some)
+                .map_err(|_| FundingClientError::Deserialize(address)),
+            _ => Ok(None),
+        }
+    }
+
+    pub async fn fetch_history(
+        &self,
+        funding_account: &Pubkey,
+    ) -> FundingClientResult<Option<FundingHistory>> {
+        let address = FundingHistoryLoader::pda(funding_account).0;
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&address, self.rpc_client.commitment())
+            .await?
+            .value;
+
+        match account {
+            Some(ai) if !ai.data.is_empty() => load_funding_history(&ai.data)
+                .map(Some)
+                .map_err(|_| FundingClientError::Deserialize(address)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Registry without entries if `authority` never initialized a funding account
+    pub async fn fetch_registry(&self, authority: &Pubkey) -> FundingClientResult<FundingRegistry> {
+        let address = FundingRegistryLoader::pda(authority).0;
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&address, self.rpc_client.commitment())
+            .await?
+            .value;
+
+        match account {
+            Some(ai) if !ai.data.is_empty() => load_funding_registry(&ai.data)
+                .map_err(|_| FundingClientError::Deserialize(address)),
+            _ => Ok(FundingRegistry {
+                authority: *authority,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Funding accounts listed in the authority registry only, accounts created before the
+    /// registry existed are missing until added with `register`
+    pub async fn fetch_registered(&self) -> FundingClientResult<Vec<(Pubkey, FundingAccount)>> {
+        let registry = self.fetch_registry(&self.authority()).await?;
+        let addresses = registry
+            .entries
+            .iter()
+            .map(|entry| entry.address)
+            .collect::<Vec<Pubkey>>();
+        let mut funding_accounts = Vec::with_capacity(addresses.len());
+
+        for addresses in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let ais = self.rpc_client.get_multiple_accounts(addresses).await?;
+
+            for (address, ai) in addresses.iter().zip(ais) {
+                // entries are removed on close, the account might have been closed
+                // in between the two requests
+                let Some(ai) = ai else {
+                    continue;
+                };
+
+                let funding_account = load_funding_account(&ai.data)
+                    .map_err(|_| FundingClientError::Deserialize(*address))?;
+                funding_accounts.push((*address, funding_account));
+            }
+        }
+
+        Ok(funding_accounts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+    use super::decode_transaction_error;
+    use crate::{
+        client::instructions::{configure_funding_account, ConfigureFundingAccountAccounts},
+        error::ErrorCode,
+    };
+
+    #[test]
+    fn decode_custom_errors() {
+        let funding_ix = configure_funding_account(
+            ConfigureFundingAccountAccounts {
+                authority: Default::default(),
+                funding_account: Default::default(),
+            },
+            None,
+            None,
+            None,
+            None,
+        );
+        let mut other_ix = funding_ix.clone();
+        other_ix.program_id = Default::default();
+        let ixs = [other_ix, funding_ix];
+
+        let error = TransactionError::InstructionError(1, InstructionError::Custom(6));
+        assert!(matches!(
+            decode_transaction_error(&error, &ixs),
+            Some(ErrorCode::UpdateTooSoon)
+        ));
+
+        let error = TransactionError::InstructionError(0, InstructionError::Custom(6));
+        assert!(decode_transaction_error(&error, &ixs).is_none());
+
+        let error = TransactionError::InstructionError(1, InstructionError::Custom(u32::MAX));
+        assert!(decode_transaction_error(&error, &ixs).is_none());
+    }
+}
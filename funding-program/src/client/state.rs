@@ -1,7 +1,12 @@
 use borsh::BorshDeserialize;
+use funding_rate::{Apr, FundingRate, PerHour};
 use solana_program::pubkey::Pubkey;
 
-use crate::state::{Exchange, FundingAccountConfig, FundingAccountFixed};
+use crate::state::{
+    Candle, CandleResolution, DataPoint, DataPointSlot, Exchange, FundingAccountConfig,
+    FundingAccountFixed, FundingHistoryFixed, FundingHistoryLoader, FundingRegistryFixed,
+    RegistryEntry,
+};
 
 #[derive(Debug, Default)]
 pub struct FundingAccount {
@@ -12,29 +17,68 @@ pub struct FundingAccount {
     pub authority: Pubkey,
 
     pub last_updated_ts: i64,
+    /// Signed updates observed at or before this timestamp are rejected
+    pub last_observation_ts: i64,
     pub config: FundingAccountConfig,
-    /// Percentage with 6 decimals
-    /// ex: 1000000 = 10.000000%
-    pub funding_ema: Option<i64>,
-    pub data_points: Vec<Option<i64>>,
+    pub funding_ema: Option<FundingRate<Apr>>,
+    pub confidence_weighted_ema: Option<FundingRate<Apr>>,
+    pub data_points: Vec<Option<DataPoint>>,
+}
+
+impl FundingAccount {
+    /// The next update resets the data points instead of appending to them
+    pub fn is_stale(&self, now_ts: i64) -> bool {
+        now_ts > self.config.stale_ts(self.last_updated_ts)
+    }
+
+    /// Stale accounts accept updates before this timestamp too
+    pub fn next_update_allowed_at(&self) -> i64 {
+        self.config.next_update_ts(self.last_updated_ts)
+    }
+
+    pub fn can_update(&self, now_ts: i64) -> bool {
+        self.is_stale(now_ts) || now_ts >= self.next_update_allowed_at()
+    }
+
+    /// Annualized percentage, ex: 1.5 = 1.5%
+    pub fn ema_as_apr_f64(&self) -> Option<f64> {
+        self.funding_ema.map(FundingRate::as_percent_f64)
+    }
+
+    pub fn ema_per_hour(&self) -> Option<FundingRate<PerHour>> {
+        self.funding_ema.map(FundingRate::per_hour)
+    }
+
+    /// Set data points ordered from oldest to newest
+    pub fn ordered_data_points(&self) -> Vec<DataPoint> {
+        self.data_points.iter().filter_map(|dp| *dp).collect()
+    }
 }
 
 #[derive(Debug)]
 pub struct DeserializeError;
 
+// Fixed header decoders generated from `idl.json` by `build.rs`, the dynamic parts following
+// the headers are decoded by hand below
+include!(concat!(env!("OUT_DIR"), "/accounts.rs"));
+
 pub fn load_funding_account(account_data: &Vec<u8>) -> Result<FundingAccount, DeserializeError> {
-    let fixed_bytes = &mut &account_data[..FundingAccountFixed::SIZE];
-    let fixed = FundingAccountFixed::deserialize(fixed_bytes).map_err(|_| DeserializeError)?;
+    let fixed = decode_funding_account_fixed(account_data)?;
+    if !fixed.has_valid_discriminator() {
+        return Err(DeserializeError);
+    }
 
     let mut funding_account = FundingAccount {
         bump: fixed.bump,
         id: fixed.id,
-        exchange: fixed.exchange,
+        exchange: fixed.exchange().ok_or(DeserializeError)?,
         market_index: fixed.market_index,
         authority: fixed.authority,
         last_updated_ts: fixed.last_updated_ts,
+        last_observation_ts: fixed.last_observation_ts,
         config: fixed.config,
-        funding_ema: fixed.funding_ema,
+        funding_ema: fixed.funding_ema().map(FundingRate::from_raw),
+        confidence_weighted_ema: fixed.confidence_weighted_ema().map(FundingRate::from_raw),
         data_points: vec![],
     };
 
@@ -44,15 +88,164 @@ pub fn load_funding_account(account_data: &Vec<u8>) -> Result<FundingAccount, De
         return Err(DeserializeError);
     }
 
-    for i in 0..data_points_count {
-        let start = i * FundingAccountFixed::DATA_POINT_SIZE;
-        let end = start + FundingAccountFixed::DATA_POINT_SIZE;
+    for bytes in dynamic_bytes.chunks_exact(FundingAccountFixed::DATA_POINT_SIZE) {
+        let slot: DataPointSlot = bytemuck::pod_read_unaligned(bytes);
+        funding_account.data_points.push(slot.get());
+    }
+
+    Ok(funding_account)
+}
+
+#[derive(Debug, Default)]
+pub struct FundingHistory {
+    pub bump: u8,
+    pub funding_account: Pubkey,
+    /// Ordered from oldest to newest
+    pub hourly: Vec<Candle>,
+    /// Ordered from oldest to newest
+    pub daily: Vec<Candle>,
+}
+
+fn load_candles(
+    dynamic_bytes: &[u8],
+    fixed: &FundingHistoryFixed,
+    resolution: CandleResolution,
+) -> Result<Vec<Candle>, DeserializeError> {
+    let ring = fixed.ring(resolution);
+    let capacity = resolution.capacity();
+    let len = ring.len as usize;
+    if len > capacity || ring.head as usize >= capacity {
+        return Err(DeserializeError);
+    }
+
+    // oldest candle is `len - 1` slots behind the head
+    let oldest = (ring.head as usize + capacity + 1 - len) % capacity;
+    let mut candles = Vec::with_capacity(len);
+
+    for i in 0..len {
+        let start =
+            resolution.offset() + (oldest + i) % capacity * FundingHistoryFixed::CANDLE_SIZE;
+        let end = start + FundingHistoryFixed::CANDLE_SIZE;
         let bytes = &mut &dynamic_bytes[start..end];
 
-        funding_account
-            .data_points
-            .push(Option::<i64>::deserialize(bytes).map_err(|_| DeserializeError)?);
+        candles.push(Candle::deserialize(bytes).map_err(|_| DeserializeError)?);
     }
 
-    Ok(funding_account)
+    Ok(candles)
+}
+
+pub fn load_funding_history(account_data: &Vec<u8>) -> Result<FundingHistory, DeserializeError> {
+    if account_data.len() != FundingHistoryLoader::size() {
+        return Err(DeserializeError);
+    }
+
+    let fixed = decode_funding_history_fixed(account_data)?;
+    let dynamic_bytes = &account_data[FundingHistoryFixed::SIZE..];
+
+    Ok(FundingHistory {
+        bump: fixed.bump,
+        funding_account: fixed.funding_account,
+        hourly: load_candles(dynamic_bytes, &fixed, CandleResolution::Hourly)?,
+        daily: load_candles(dynamic_bytes, &fixed, CandleResolution::Daily)?,
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct FundingRegistry {
+    pub bump: u8,
+    pub authority: Pubkey,
+    pub entries: Vec<RegistryEntry>,
+}
+
+pub fn load_funding_registry(account_data: &Vec<u8>) -> Result<FundingRegistry, DeserializeError> {
+    let fixed = decode_funding_registry_fixed(account_data)?;
+
+    let dynamic_bytes = &account_data[FundingRegistryFixed::SIZE..];
+    let entries_count = fixed.entries_count as usize;
+    if dynamic_bytes.len() != entries_count * FundingRegistryFixed::ENTRY_SIZE {
+        return Err(DeserializeError);
+    }
+
+    let mut registry = FundingRegistry {
+        bump: fixed.bump,
+        authority: fixed.authority,
+        entries: Vec::with_capacity(entries_count),
+    };
+
+    for i in 0..entries_count {
+        let start = i * FundingRegistryFixed::ENTRY_SIZE;
+        let end = start + FundingRegistryFixed::ENTRY_SIZE;
+        let bytes = &mut &dynamic_bytes[start..end];
+
+        registry
+            .entries
+            .push(RegistryEntry::deserialize(bytes).map_err(|_| DeserializeError)?);
+    }
+
+    Ok(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use funding_rate::FundingRate;
+
+    use super::FundingAccount;
+    use crate::state::{DataPoint, FundingAccountConfig};
+
+    fn funding_account() -> FundingAccount {
+        FundingAccount {
+            last_updated_ts: 1_000,
+            config: FundingAccountConfig {
+                update_frequency_secs: 60,
+                staleness_threshold_secs: 300,
+                period_length: 5,
+                data_points_count: 3,
+                ..Default::default()
+            },
+            funding_ema: Some(FundingRate::from_raw(8_760_000)),
+            data_points: vec![
+                Some(DataPoint {
+                    value: 1,
+                    confidence: None,
+                }),
+                Some(DataPoint {
+                    value: 2,
+                    confidence: Some(1),
+                }),
+                None,
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update_rules() {
+        let funding_account = funding_account();
+
+        assert_eq!(funding_account.next_update_allowed_at(), 1_060);
+        assert!(!funding_account.can_update(1_059));
+        assert!(funding_account.can_update(1_060));
+
+        assert!(!funding_account.is_stale(1_300));
+        assert!(funding_account.is_stale(1_301));
+    }
+
+    #[test]
+    fn ema_conversion() {
+        let funding_account = funding_account();
+
+        assert_eq!(funding_account.ema_as_apr_f64(), Some(8.76));
+        assert_eq!(
+            funding_account.ema_per_hour(),
+            Some(FundingRate::from_raw(1_000))
+        );
+        assert_eq!(
+            funding_account
+                .ordered_data_points()
+                .iter()
+                .map(|dp| dp.value)
+                .collect::<Vec<i64>>(),
+            vec![1, 2]
+        );
+    }
 }
@@ -27,6 +27,15 @@ pub enum ErrorCode {
 
     #[error("Receiver account lamports overflow")]
     LamportsOverflow,
+
+    #[error("Missing or invalid ed25519 signature instruction")]
+    MissingOrInvalidSignature,
+
+    #[error("Observation timestamp is stale, in the future or already used")]
+    InvalidObservationTimestamp,
+
+    #[error("Account was written by an unsupported layout version")]
+    InvalidAccountVersion,
 }
 
 pub enum Error {
@@ -25,6 +25,24 @@ pub enum InstructionData {
     },
     UpdateFundingData {
         data_point: i64,
+        /// Standard deviation of the snapshots `data_point` was averaged from
+        confidence: Option<u64>,
     },
     CloseFundingAccount,
+    InitializeFundingHistory,
+    UpdateFundingDataSigned {
+        data_point: i64,
+        confidence: Option<u64>,
+        observation_ts: i64,
+    },
+    RegisterFundingAccount,
+}
+
+/// Message the authority signs off-chain for `UpdateFundingDataSigned`
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct SignedFundingUpdate {
+    pub funding_account: Pubkey,
+    pub data_point: i64,
+    pub confidence: Option<u64>,
+    pub observation_ts: i64,
 }
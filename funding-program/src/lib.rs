@@ -8,7 +8,9 @@ pub mod error;
 pub mod instructions;
 pub mod processor;
 pub mod state;
-#[cfg(all(test, feature = "integration"))]
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+#[cfg(test)]
 pub mod tests;
 
 declare_id!("Fnd1yWeU4ajtCbzuDLsZq3cuoUiroJCYRoUi2y6PVZfy");
@@ -80,9 +82,12 @@ pub fn process_instruction(
             processor::configure_funding_account_authority(accounts, authority)?;
             Ok(())
         }
-        InstructionData::UpdateFundingData { data_point } => {
+        InstructionData::UpdateFundingData {
+            data_point,
+            confidence,
+        } => {
             log_instruction("UpdateFundingAccount");
-            processor::update_funding(accounts, data_point)?;
+            processor::update_funding(accounts, data_point, confidence)?;
             Ok(())
         }
         InstructionData::CloseFundingAccount => {
@@ -90,5 +95,24 @@ pub fn process_instruction(
             processor::close_funding_account(accounts)?;
             Ok(())
         }
+        InstructionData::InitializeFundingHistory => {
+            log_instruction("InitializeFundingHistory");
+            processor::initialize_funding_history(accounts)?;
+            Ok(())
+        }
+        InstructionData::UpdateFundingDataSigned {
+            data_point,
+            confidence,
+            observation_ts,
+        } => {
+            log_instruction("UpdateFundingDataSigned");
+            processor::update_funding_signed(accounts, data_point, confidence, observation_ts)?;
+            Ok(())
+        }
+        InstructionData::RegisterFundingAccount => {
+            log_instruction("RegisterFundingAccount");
+            processor::register_funding_account(accounts)?;
+            Ok(())
+        }
     }
 }
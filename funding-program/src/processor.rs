@@ -4,19 +4,22 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     clock::Clock,
-    msg,
+    ed25519_program, msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
-    program_memory::sol_memset,
     pubkey::Pubkey,
     rent::Rent,
     system_instruction, system_program,
-    sysvar::Sysvar,
+    sysvar::{instructions, Sysvar},
 };
 
 use crate::{
     error::{ErrorCode, FundingResult},
-    state::{BpfWriter, Exchange, FundingAccountConfig, FundingAccountLoader},
+    instructions::SignedFundingUpdate,
+    state::{
+        BpfWriter, DataPoint, DataPointSlot, Exchange, FundingAccountConfig, FundingAccountFixed,
+        FundingAccountLoader, FundingHistoryLoader, FundingRegistryLoader, RegistryEntry,
+    },
 };
 
 pub fn deserialize_account_data<T: BorshDeserialize>(data: &mut &[u8]) -> FundingResult<T> {
@@ -49,6 +52,169 @@ fn load_signer_ai<'a, 'info>(
     Ok(account)
 }
 
+/// Creates the program owned PDA paid by `payer_ai`, accounts which were already sent
+/// lamports can not be created with `create_account`, they are allocated and assigned instead
+fn create_pda_account<'a, 'info>(
+    payer_ai: &'a AccountInfo<'info>,
+    pda_ai: &'a AccountInfo<'info>,
+    size: usize,
+    seeds: &[&[u8]],
+) -> FundingResult<()> {
+    let rent = Rent::get()?;
+    let lamports = rent.minimum_balance(size);
+    let current_lamports = pda_ai.lamports();
+
+    if current_lamports == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_ai.key,
+                pda_ai.key,
+                lamports,
+                size as u64,
+                &crate::id(),
+            ),
+            &[payer_ai.clone(), pda_ai.clone()],
+            &[seeds],
+        )?;
+
+        return Ok(());
+    }
+
+    let additional_lamports = lamports.saturating_sub(current_lamports);
+    if additional_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer_ai.key, pda_ai.key, additional_lamports),
+            &[payer_ai.clone(), pda_ai.clone()],
+        )?;
+    }
+
+    invoke_signed(
+        &system_instruction::allocate(pda_ai.key, size as u64),
+        &[pda_ai.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(pda_ai.key, &crate::id()),
+        &[pda_ai.clone()],
+        &[seeds],
+    )?;
+
+    Ok(())
+}
+
+/// Appends the entry to the registry of `authority` if it is not registered yet,
+/// creates the registry if it does not exist, paid by `payer_ai`
+fn add_to_registry<'a, 'info>(
+    payer_ai: &'a AccountInfo<'info>,
+    authority: &Pubkey,
+    registry_ai: &'a AccountInfo<'info>,
+    entry: RegistryEntry,
+) -> FundingResult<()> {
+    if !registry_ai.is_writable {
+        Err(ErrorCode::AccountsNeedToBeWritable)?;
+    }
+
+    let (address, bump) = FundingRegistryLoader::pda(authority);
+    if registry_ai.key != &address {
+        Err(ErrorCode::InvalidAccount)?;
+    }
+
+    if registry_ai.data_is_empty() {
+        create_pda_account(
+            payer_ai,
+            registry_ai,
+            FundingRegistryLoader::size(0),
+            &[
+                FundingRegistryLoader::NAMESPACE,
+                authority.as_ref(),
+                &[bump],
+            ],
+        )?;
+
+        let mut registry = FundingRegistryLoader::load(registry_ai)?;
+        registry.fixed.bump = bump;
+        registry.fixed.authority = *authority;
+        registry.save()?;
+
+        msg!("Initialized funding registry: {}", registry_ai.key);
+    }
+
+    let registry = FundingRegistryLoader::try_load(registry_ai, authority)?;
+    if registry.find_entry(&entry.address)?.is_some() {
+        msg!("Funding account is already registered: {}", entry.address);
+        return Ok(());
+    }
+    let entries_count = registry.fixed.entries_count;
+    drop(registry);
+
+    let rent = Rent::get()?;
+    let new_size = FundingRegistryLoader::size(entries_count + 1);
+    let additional_lamports = rent
+        .minimum_balance(new_size)
+        .saturating_sub(registry_ai.lamports());
+
+    if additional_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer_ai.key, registry_ai.key, additional_lamports),
+            &[payer_ai.clone(), registry_ai.clone()],
+        )?;
+    }
+
+    registry_ai.realloc(new_size, false)?;
+
+    let mut registry = FundingRegistryLoader::load(registry_ai)?;
+    registry.write_entry(&entry, entries_count as usize)?;
+    registry.fixed.entries_count = entries_count + 1;
+    registry.save()
+}
+
+/// Removes the funding account from the registry of `authority` and returns the excess
+/// lamports to the receiver, accounts created before the registry existed or registered
+/// under another authority are skipped
+fn remove_from_registry<'a, 'info>(
+    authority: &Pubkey,
+    registry_ai: &'a AccountInfo<'info>,
+    receiver: &'a AccountInfo<'info>,
+    funding_account: &Pubkey,
+) -> FundingResult<()> {
+    if registry_ai.key != &FundingRegistryLoader::pda(authority).0 {
+        Err(ErrorCode::InvalidAccount)?;
+    }
+
+    if registry_ai.owner != &crate::id() || registry_ai.data_is_empty() {
+        msg!("Funding registry does not exist: {}", registry_ai.key);
+        return Ok(());
+    }
+
+    let mut registry = FundingRegistryLoader::try_load(registry_ai, authority)?;
+
+    let Some(i) = registry.find_entry(funding_account)? else {
+        msg!("Funding account is not registered: {}", funding_account);
+        return Ok(());
+    };
+
+    registry.swap_remove_entry(i)?;
+    let new_size = FundingRegistryLoader::size(registry.fixed.entries_count);
+    registry.save()?;
+
+    registry_ai.realloc(new_size, false)?;
+
+    let rent = Rent::get()?;
+    let registry_lamports = registry_ai.lamports();
+    let remaining_lamports = registry_lamports.saturating_sub(rent.minimum_balance(new_size));
+
+    if remaining_lamports > 0 {
+        let receiver_lamports = receiver.lamports();
+
+        **receiver.try_borrow_mut_lamports()? = receiver_lamports
+            .checked_add(remaining_lamports)
+            .ok_or(ErrorCode::LamportsOverflow)?;
+        **registry_ai.try_borrow_mut_lamports()? = registry_lamports.sub(remaining_lamports);
+    }
+
+    Ok(())
+}
+
 pub fn initialize_funding_account(
     accounts: &[AccountInfo],
     id: u16,
@@ -63,6 +229,8 @@ pub fn initialize_funding_account(
 
     let signer_ai = load_signer_ai(next_account_info(&mut accounts_iter)?)?;
     let funding_ai = next_account_info(&mut accounts_iter)?;
+    let _system_program_ai = next_account_info(&mut accounts_iter)?;
+    let registry_ai = next_account_info(&mut accounts_iter)?;
 
     if !funding_ai.is_writable {
         Err(ErrorCode::AccountsNeedToBeWritable)?;
@@ -85,48 +253,41 @@ pub fn initialize_funding_account(
         Err(ProgramError::InvalidInstructionData)?;
     }
 
-    let rent = Rent::get()?;
-    let size = FundingAccountLoader::size(data_points_count);
-    let lamports = rent.minimum_balance(size);
-
-    invoke_signed(
-        &system_instruction::create_account(
-            signer_ai.key,
-            funding_ai.key,
-            lamports,
-            size as u64,
-            &crate::id(),
-        ),
-        &[signer_ai.clone(), funding_ai.clone()],
-        &[&[
+    create_pda_account(
+        signer_ai,
+        funding_ai,
+        FundingAccountLoader::size(data_points_count),
+        &[
             FundingAccountLoader::NAMESPACE,
             id.to_le_bytes().as_ref(),
             market_index.to_le_bytes().as_ref(),
             exchange.discriminator().to_le_bytes().as_ref(),
             &[bump],
-        ]],
+        ],
     )?;
 
     let mut funding_account = FundingAccountLoader::load(funding_ai)?;
 
+    funding_account.fixed.discriminator = FundingAccountFixed::DISCRIMINATOR;
     funding_account.fixed.bump = bump;
     funding_account.fixed.id = id;
     funding_account.fixed.authority = signer_ai.key.clone();
     funding_account.fixed.market_index = market_index;
-    funding_account.fixed.exchange = exchange;
+    funding_account.fixed.exchange = exchange.discriminator();
     funding_account.fixed.config = FundingAccountConfig {
         update_frequency_secs,
         staleness_threshold_secs,
         period_length,
         data_points_count,
+        ..Default::default()
     };
 
     msg!("Initialized funding account");
     funding_account.log();
+    let entry = funding_account.registry_entry()?;
+    drop(funding_account);
 
-    funding_account.save()?;
-
-    Ok(())
+    add_to_registry(signer_ai, signer_ai.key, registry_ai, entry)
 }
 
 pub fn configure_funding_account<'a, 'info>(
@@ -162,8 +323,6 @@ pub fn configure_funding_account<'a, 'info>(
             config.update_frequency_secs = new_update_freq;
             config.staleness_threshold_secs = new_staleness_threshold;
             config.period_length = new_period_length;
-
-            funding_account.save()?;
         }
         Some(new_count) => {
             if new_count <= 1 {
@@ -173,15 +332,17 @@ pub fn configure_funding_account<'a, 'info>(
             let prev_count = config.data_points_count;
             let new_size = FundingAccountLoader::size(new_count);
 
-            let mut new_fixed = funding_account.fixed.clone();
+            let mut new_fixed = *funding_account.fixed;
             drop(funding_account);
 
-            new_fixed.funding_ema = None;
+            new_fixed.funding_ema = None.into();
+            new_fixed.confidence_weighted_ema = None.into();
             new_fixed.config = FundingAccountConfig {
                 update_frequency_secs: new_update_freq,
                 staleness_threshold_secs: new_staleness_threshold,
                 period_length: new_period_length,
                 data_points_count: new_count,
+                ..Default::default()
             };
 
             let zero_init = if new_count < prev_count {
@@ -233,18 +394,11 @@ pub fn configure_funding_account<'a, 'info>(
             funding_ai.realloc(new_size, false)?;
 
             let mut funding_account = FundingAccountLoader::load(funding_ai)?;
-            funding_account.fixed = new_fixed;
+            *funding_account.fixed = new_fixed;
 
             if zero_init {
-                let dynamic_size = funding_account.dynamic.len();
-                sol_memset(
-                    &mut funding_account.dynamic[0..dynamic_size],
-                    0,
-                    dynamic_size,
-                );
+                funding_account.data_points.fill(DataPointSlot::default());
             }
-
-            funding_account.save()?;
         }
     }
 
@@ -258,53 +412,253 @@ pub fn configure_funding_account_authority<'a, 'info>(
     let mut accounts_iter = accounts.iter();
 
     let signer_ai = load_signer_ai(next_account_info(&mut accounts_iter)?)?;
-    let mut funding_account =
-        FundingAccountLoader::try_load(next_account_info(&mut accounts_iter)?, signer_ai.key)?;
+    let funding_ai = next_account_info(&mut accounts_iter)?;
+    let _system_program_ai = next_account_info(&mut accounts_iter)?;
+    let registry_ai = next_account_info(&mut accounts_iter)?;
+    let new_registry_ai = next_account_info(&mut accounts_iter)?;
+
+    if !signer_ai.is_writable {
+        Err(ErrorCode::AccountsNeedToBeWritable)?;
+    }
 
+    let mut funding_account = FundingAccountLoader::try_load(funding_ai, signer_ai.key)?;
     funding_account.fixed.authority = authority;
+    let entry = funding_account.registry_entry()?;
+    drop(funding_account);
+
+    // the entry moves to the registry of the new authority, the old authority pays for it
+    remove_from_registry(signer_ai.key, registry_ai, signer_ai, funding_ai.key)?;
+    add_to_registry(signer_ai, &authority, new_registry_ai, entry)?;
+
     msg!("Updated authority: {}", authority.to_string());
 
-    funding_account.save()?;
     Ok(())
 }
 
-pub fn update_funding<'a, 'info>(
+/// Adds funding accounts created before the registry existed to the registry of their authority
+pub fn register_funding_account<'a, 'info>(
     accounts: &'a [AccountInfo<'info>],
-    data_point: i64,
 ) -> FundingResult<()> {
     let mut accounts_iter = accounts.iter();
 
     let signer_ai = load_signer_ai(next_account_info(&mut accounts_iter)?)?;
-    let mut funding_account =
-        FundingAccountLoader::try_load(next_account_info(&mut accounts_iter)?, signer_ai.key)?;
+    let funding_ai = next_account_info(&mut accounts_iter)?;
+    let _system_program_ai = next_account_info(&mut accounts_iter)?;
+    let registry_ai = next_account_info(&mut accounts_iter)?;
 
-    let clock = Clock::get()?;
-    let now_ts = clock.unix_timestamp;
+    let funding_account = FundingAccountLoader::try_load(funding_ai, signer_ai.key)?;
+    let entry = funding_account.registry_entry()?;
+    drop(funding_account);
 
-    let stale_ts = funding_account.fixed.last_updated_ts
-        + funding_account.fixed.config.staleness_threshold_secs as i64;
+    add_to_registry(signer_ai, signer_ai.key, registry_ai, entry)
+}
+
+fn apply_data_point<'a, 'info>(
+    funding_ai: &'a AccountInfo<'info>,
+    mut funding_account: FundingAccountLoader<'a, 'info>,
+    history_ai: Option<&'a AccountInfo<'info>>,
+    data_point: DataPoint,
+    observation_ts: i64,
+    now_ts: i64,
+) -> FundingResult<()> {
+    let last_updated_ts = funding_account.fixed.last_updated_ts;
 
-    if now_ts > stale_ts {
+    if now_ts > funding_account.fixed.config.stale_ts(last_updated_ts) {
         funding_account.reset_data_points_and_write_first(data_point)?;
         funding_account.fixed.last_updated_ts = now_ts;
+        funding_account.fixed.last_observation_ts = observation_ts;
 
-        funding_account.save()?;
         return Ok(());
     }
 
-    let update_ts = funding_account.fixed.last_updated_ts
-        + funding_account.fixed.config.update_frequency_secs as i64;
-    if now_ts < update_ts {
+    if now_ts < funding_account.fixed.config.next_update_ts(last_updated_ts) {
         Err(ErrorCode::UpdateTooSoon)?;
     }
 
     funding_account.update_data_points(data_point)?;
     funding_account.fixed.last_updated_ts = now_ts;
+    funding_account.fixed.last_observation_ts = observation_ts;
+
+    if let (Some(history_ai), Some(ema)) = (history_ai, funding_account.fixed.funding_ema()) {
+        let mut funding_history = FundingHistoryLoader::try_load(history_ai, funding_ai.key)?;
+        funding_history.roll_candles(now_ts, ema)?;
+        funding_history.save()?;
+    }
+
+    msg!("Added new data point: {:?}", data_point);
+    msg!("Updated EMA: {:?}", funding_account.fixed.funding_ema());
+    msg!(
+        "Updated confidence weighted EMA: {:?}",
+        funding_account.fixed.confidence_weighted_ema()
+    );
+
+    Ok(())
+}
+
+pub fn update_funding<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    data_point: i64,
+    confidence: Option<u64>,
+) -> FundingResult<()> {
+    let mut accounts_iter = accounts.iter();
+
+    let signer_ai = load_signer_ai(next_account_info(&mut accounts_iter)?)?;
+    let funding_ai = next_account_info(&mut accounts_iter)?;
+    let funding_account = FundingAccountLoader::try_load(funding_ai, signer_ai.key)?;
+
+    let clock = Clock::get()?;
+    let data_point = DataPoint {
+        value: data_point,
+        confidence,
+    };
+
+    apply_data_point(
+        funding_ai,
+        funding_account,
+        accounts_iter.next(),
+        data_point,
+        clock.unix_timestamp,
+        clock.unix_timestamp,
+    )
+}
+
+/// Layout of the ed25519 program instruction data
+/// https://docs.solana.com/developing/runtime-facilities/programs#ed25519-program
+pub mod ed25519 {
+    pub const PUBKEY_SERIALIZED_SIZE: usize = 32;
+    pub const SIGNATURE_SERIALIZED_SIZE: usize = 64;
+    pub const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 14;
+    pub const SIGNATURE_OFFSETS_START: usize = 2;
+    pub const DATA_START: usize = SIGNATURE_OFFSETS_SERIALIZED_SIZE + SIGNATURE_OFFSETS_START;
+    /// Offsets pointing into the ed25519 instruction itself
+    pub const CURRENT_INSTRUCTION_INDEX: u16 = u16::MAX;
+}
+
+fn read_u16(data: &[u8], offset: usize) -> FundingResult<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or(ErrorCode::MissingOrInvalidSignature)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Returns the public key and the message of the single signature verified by the ed25519
+/// program instruction, the signature, public key and message have to be part of the
+/// ed25519 instruction itself
+pub fn parse_ed25519_instruction_data(data: &[u8]) -> FundingResult<(Pubkey, &[u8])> {
+    use ed25519::*;
+
+    if data.len() < DATA_START || data[0] != 1 {
+        Err(ErrorCode::MissingOrInvalidSignature)?;
+    }
+
+    let offsets = SIGNATURE_OFFSETS_START;
+    let signature_instruction_index = read_u16(data, offsets + 2)?;
+    let public_key_offset = read_u16(data, offsets + 4)? as usize;
+    let public_key_instruction_index = read_u16(data, offsets + 6)?;
+    let message_data_offset = read_u16(data, offsets + 8)? as usize;
+    let message_data_size = read_u16(data, offsets + 10)? as usize;
+    let message_instruction_index = read_u16(data, offsets + 12)?;
+
+    if signature_instruction_index != CURRENT_INSTRUCTION_INDEX
+        || public_key_instruction_index != CURRENT_INSTRUCTION_INDEX
+        || message_instruction_index != CURRENT_INSTRUCTION_INDEX
+    {
+        Err(ErrorCode::MissingOrInvalidSignature)?;
+    }
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + PUBKEY_SERIALIZED_SIZE)
+        .ok_or(ErrorCode::MissingOrInvalidSignature)?;
+    let message = data
+        .get(message_data_offset..message_data_offset + message_data_size)
+        .ok_or(ErrorCode::MissingOrInvalidSignature)?;
+
+    Ok((
+        Pubkey::try_from(public_key).map_err(|_| ErrorCode::MissingOrInvalidSignature)?,
+        message,
+    ))
+}
+
+pub fn update_funding_signed<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    data_point: i64,
+    confidence: Option<u64>,
+    observation_ts: i64,
+) -> FundingResult<()> {
+    let mut accounts_iter = accounts.iter();
+
+    let funding_ai = next_account_info(&mut accounts_iter)?;
+    let instructions_ai = next_account_info(&mut accounts_iter)?;
+
+    // signature needs to be verified by the ed25519 instruction right before this one
+    let current_index = instructions::load_current_index_checked(instructions_ai)?;
+    if current_index == 0 {
+        Err(ErrorCode::MissingOrInvalidSignature)?;
+    }
+    let ed25519_ix =
+        instructions::load_instruction_at_checked(current_index as usize - 1, instructions_ai)?;
+    if ed25519_ix.program_id != ed25519_program::id() {
+        Err(ErrorCode::MissingOrInvalidSignature)?;
+    }
+
+    let (authority, message) = parse_ed25519_instruction_data(&ed25519_ix.data)?;
+    let expected_message = SignedFundingUpdate {
+        funding_account: *funding_ai.key,
+        data_point,
+        confidence,
+        observation_ts,
+    }
+    .try_to_vec()
+    .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    if message != &expected_message[..] {
+        Err(ErrorCode::MissingOrInvalidSignature)?;
+    }
+
+    let funding_account = FundingAccountLoader::try_load(funding_ai, &authority)?;
+
+    let clock = Clock::get()?;
+    let now_ts = clock.unix_timestamp;
+
+    // signed updates can be submitted by anyone, prevent replaying old observations and
+    // holding future dated ones back to submit them again later
+    if observation_ts <= funding_account.fixed.last_observation_ts
+        || observation_ts > now_ts
+        || now_ts - observation_ts > funding_account.fixed.config.update_frequency_secs as i64
+    {
+        Err(ErrorCode::InvalidObservationTimestamp)?;
+    }
+
+    let data_point = DataPoint {
+        value: data_point,
+        confidence,
+    };
 
-    msg!("Added new data point: {}", data_point);
-    msg!("Updated EMA: {:?}", funding_account.fixed.funding_ema);
+    apply_data_point(
+        funding_ai,
+        funding_account,
+        accounts_iter.next(),
+        data_point,
+        observation_ts,
+        now_ts,
+    )
+}
+
+fn close_account<'a, 'info>(
+    account: &'a AccountInfo<'info>,
+    receiver: &'a AccountInfo<'info>,
+) -> FundingResult<()> {
+    let account_lamports = account.lamports();
+    let receiver_lamports = receiver.lamports();
+
+    **receiver.try_borrow_mut_lamports()? = receiver_lamports
+        .checked_add(account_lamports)
+        .ok_or(ErrorCode::LamportsOverflow)?;
+    **account.try_borrow_mut_lamports()? = 0;
+
+    account.realloc(0, false)?;
+    account.assign(&system_program::id());
 
-    funding_account.save()?;
     Ok(())
 }
 
@@ -315,6 +669,7 @@ pub fn close_funding_account<'a, 'info>(accounts: &'a [AccountInfo<'info>]) -> F
     let funding_ai = next_account_info(&mut accounts_iter)?;
     let _ = FundingAccountLoader::try_load(funding_ai, signer_ai.key)?;
     let receiver = next_account_info(&mut accounts_iter)?;
+    let registry_ai = next_account_info(&mut accounts_iter)?;
 
     if !receiver.is_writable {
         Err(ErrorCode::AccountsNeedToBeWritable)?;
@@ -323,16 +678,58 @@ pub fn close_funding_account<'a, 'info>(accounts: &'a [AccountInfo<'info>]) -> F
         Err(ErrorCode::AccountsNeedToBeWritable)?;
     }
 
-    let funding_lamports = funding_ai.lamports();
-    let receiver_lamports = receiver.lamports();
+    remove_from_registry(signer_ai.key, registry_ai, receiver, funding_ai.key)?;
 
-    **receiver.try_borrow_mut_lamports()? = receiver_lamports
-        .checked_add(funding_lamports)
-        .ok_or(ErrorCode::LamportsOverflow)?;
-    **funding_ai.try_borrow_mut_lamports()? = 0;
+    // the history can not be closed once its funding account is gone
+    let history_ai = next_account_info(&mut accounts_iter)?;
+    if history_ai.key != &FundingHistoryLoader::pda(funding_ai.key).0 {
+        Err(ErrorCode::InvalidAccount)?;
+    }
+    if history_ai.owner == &crate::id() {
+        let _ = FundingHistoryLoader::try_load(history_ai, funding_ai.key)?;
+        close_account(history_ai, receiver)?;
+    }
+
+    close_account(funding_ai, receiver)
+}
+
+pub fn initialize_funding_history<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+) -> FundingResult<()> {
+    let mut accounts_iter = accounts.iter();
+
+    let signer_ai = load_signer_ai(next_account_info(&mut accounts_iter)?)?;
+    let funding_ai = next_account_info(&mut accounts_iter)?;
+    let _ = FundingAccountLoader::try_load(funding_ai, signer_ai.key)?;
+    let history_ai = next_account_info(&mut accounts_iter)?;
+
+    if !history_ai.is_writable {
+        Err(ErrorCode::AccountsNeedToBeWritable)?;
+    }
+
+    let (address, bump) = FundingHistoryLoader::pda(funding_ai.key);
+    if history_ai.key != &address {
+        Err(ErrorCode::InvalidAccount)?;
+    }
+
+    create_pda_account(
+        signer_ai,
+        history_ai,
+        FundingHistoryLoader::size(),
+        &[
+            FundingHistoryLoader::NAMESPACE,
+            funding_ai.key.as_ref(),
+            &[bump],
+        ],
+    )?;
+
+    let mut funding_history = FundingHistoryLoader::load(history_ai)?;
+    funding_history.fixed.bump = bump;
+    funding_history.fixed.funding_account = funding_ai.key.clone();
+
+    msg!("Initialized funding history: {}", history_ai.key);
 
-    funding_ai.realloc(0, false)?;
-    funding_ai.assign(&system_program::id());
+    funding_history.save()?;
 
     Ok(())
 }
@@ -1,15 +1,13 @@
 use std::{
-    cell::RefMut,
+    cell::{Ref, RefMut},
     cmp,
     io::{self, Write},
 };
 
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
 use solana_program::{
-    account_info::AccountInfo,
-    msg,
-    program_error::ProgramError,
-    program_memory::{sol_memcpy, sol_memmove},
+    account_info::AccountInfo, msg, program_error::ProgramError, program_memory::sol_memcpy,
     pubkey::Pubkey,
 };
 
@@ -72,6 +70,14 @@ impl Exchange {
             Self::Mango => 1,
         }
     }
+
+    pub fn from_discriminator(discriminator: u8) -> Option<Self> {
+        match discriminator {
+            0 => Some(Self::Drift),
+            1 => Some(Self::Mango),
+            _ => None,
+        }
+    }
 }
 
 impl Default for Exchange {
@@ -80,7 +86,102 @@ impl Default for Exchange {
     }
 }
 
-#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize, Debug)]
+/// Funding rates are annualized percentages with 6 decimals, see `funding_rate::FundingRate<Apr>`
+pub const FUNDING_RATE_PRECISION: i64 = funding_rate::PRECISION;
+
+#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize, PartialEq, Debug)]
+pub struct DataPoint {
+    /// Percentage with 6 decimals
+    pub value: i64,
+    /// Standard deviation of the snapshots the data point was averaged from,
+    /// same precision as `value`
+    pub confidence: Option<u64>,
+}
+
+/// Zero-copy `Option<i64>`
+#[derive(Copy, Clone, Default, Pod, Zeroable, PartialEq, Debug)]
+#[repr(C)]
+pub struct PodOptionI64 {
+    pub value: i64,
+    pub is_some: u8,
+    pub padding: [u8; 7],
+}
+
+impl From<Option<i64>> for PodOptionI64 {
+    fn from(value: Option<i64>) -> Self {
+        match value {
+            Some(value) => Self {
+                value,
+                is_some: 1,
+                ..Default::default()
+            },
+            None => Self::default(),
+        }
+    }
+}
+
+impl From<PodOptionI64> for Option<i64> {
+    fn from(value: PodOptionI64) -> Self {
+        if value.is_some == 1 {
+            Some(value.value)
+        } else {
+            None
+        }
+    }
+}
+
+/// Zero-copy `Option<DataPoint>`
+#[derive(Copy, Clone, Default, Pod, Zeroable, PartialEq, Debug)]
+#[repr(C)]
+pub struct DataPointSlot {
+    pub value: i64,
+    pub confidence: u64,
+    pub flags: u8,
+    pub padding: [u8; 7],
+}
+
+impl DataPointSlot {
+    const IS_SET: u8 = 1;
+    const HAS_CONFIDENCE: u8 = 1 << 1;
+
+    pub fn is_set(&self) -> bool {
+        self.flags & Self::IS_SET != 0
+    }
+
+    pub fn get(&self) -> Option<DataPoint> {
+        if !self.is_set() {
+            return None;
+        }
+
+        Some(DataPoint {
+            value: self.value,
+            confidence: if self.flags & Self::HAS_CONFIDENCE != 0 {
+                Some(self.confidence)
+            } else {
+                None
+            },
+        })
+    }
+
+    pub fn set(&mut self, data_point: Option<DataPoint>) {
+        *self = match data_point {
+            Some(data_point) => Self {
+                value: data_point.value,
+                confidence: data_point.confidence.unwrap_or(0),
+                flags: Self::IS_SET
+                    | data_point
+                        .confidence
+                        .map(|_| Self::HAS_CONFIDENCE)
+                        .unwrap_or(0),
+                ..Default::default()
+            },
+            None => Self::default(),
+        };
+    }
+}
+
+#[derive(Copy, Clone, Default, Pod, Zeroable, Debug)]
+#[repr(C)]
 pub struct FundingAccountConfig {
     pub update_frequency_secs: u64,
     pub staleness_threshold_secs: u64,
@@ -88,9 +189,20 @@ pub struct FundingAccountConfig {
     /// (data_point - prev_ema) * 2 / (period + 1) + prev_ema
     pub period_length: u32,
     pub data_points_count: u16,
+    pub padding: [u8; 2],
 }
 
 impl FundingAccountConfig {
+    /// Data points are reset by updates after this timestamp
+    pub fn stale_ts(&self, last_updated_ts: i64) -> i64 {
+        last_updated_ts + self.staleness_threshold_secs as i64
+    }
+
+    /// Updates before this timestamp fail with `UpdateTooSoon` unless the account is stale
+    pub fn next_update_ts(&self, last_updated_ts: i64) -> i64 {
+        last_updated_ts + self.update_frequency_secs as i64
+    }
+
     pub fn log(&self) {
         msg!("update_frequency_secs: {}", self.update_frequency_secs);
         msg!(
@@ -102,34 +214,104 @@ impl FundingAccountConfig {
     }
 }
 
-#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize)]
+/// Accessed in place, fields are ordered by alignment so the layout has no implicit padding.
+/// Reordering or resizing fields breaks existing accounts, `DISCRIMINATOR` has to be bumped
+/// along with it so they fail to load instead of being misread
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
 pub struct FundingAccountFixed {
-    pub bump: u8,
-    pub id: u16,
-    pub exchange: Exchange,
-    pub market_index: u16,
+    /// `FundingAccountFixed::DISCRIMINATOR`, written on initialize
+    pub discriminator: [u8; 8],
     pub authority: Pubkey,
-
     pub last_updated_ts: i64,
+    /// Observation timestamp of the last accepted data point, signed updates have to
+    /// be observed after it
+    pub last_observation_ts: i64,
     pub config: FundingAccountConfig,
-    /// Percentage with 6 decimals
-    /// ex: 1000000 = 10.000000%
-    pub funding_ema: Option<i64>,
+    /// Annualized percentage with 6 decimals
+    /// ex: 1000000 = 1.000000%
+    pub funding_ema: PodOptionI64,
+    /// EMA where every data point is weighted by the inverse of its confidence,
+    /// only computed if all data points carry confidence
+    pub confidence_weighted_ema: PodOptionI64,
+
+    pub id: u16,
+    pub market_index: u16,
+    pub bump: u8,
+    /// `Exchange` discriminator
+    pub exchange: u8,
+    pub padding: [u8; 2],
 }
 
 impl FundingAccountFixed {
     pub const SIZE: usize = std::mem::size_of::<Self>();
-    pub const DATA_POINT_SIZE: usize = std::mem::size_of::<Option<i64>>();
+    pub const DATA_POINT_SIZE: usize = std::mem::size_of::<DataPointSlot>();
+    /// Identifies the layout version, accounts written by the borsh layout start with the bump
+    pub const DISCRIMINATOR: [u8; 8] = *b"fundacc1";
+
+    pub fn has_valid_discriminator(&self) -> bool {
+        self.discriminator == Self::DISCRIMINATOR
+    }
+
+    pub fn exchange(&self) -> Option<Exchange> {
+        Exchange::from_discriminator(self.exchange)
+    }
+
+    pub fn funding_ema(&self) -> Option<i64> {
+        self.funding_ema.into()
+    }
+
+    pub fn confidence_weighted_ema(&self) -> Option<i64> {
+        self.confidence_weighted_ema.into()
+    }
+
+    /// Read-only access for programs consuming funding accounts, checks the owner and that
+    /// the address matches the stored seeds
+    pub fn load<'a>(account_info: &'a AccountInfo) -> FundingResult<Ref<'a, Self>> {
+        if account_info.owner != &crate::id() {
+            Err(ErrorCode::InvalidAccount)?;
+        }
+
+        let data = account_info.try_borrow_data()?;
+        let fixed = Ref::filter_map(data, |b| {
+            b.get(..Self::SIZE)
+                .and_then(|b| bytemuck::try_from_bytes::<Self>(b).ok())
+        })
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+        if !fixed.has_valid_discriminator() {
+            Err(ErrorCode::InvalidAccountVersion)?;
+        }
+
+        let exchange = fixed.exchange().ok_or(ProgramError::InvalidAccountData)?;
+        let address = Pubkey::create_program_address(
+            &[
+                FundingAccountLoader::NAMESPACE,
+                fixed.id.to_le_bytes().as_ref(),
+                fixed.market_index.to_le_bytes().as_ref(),
+                exchange.discriminator().to_le_bytes().as_ref(),
+                &[fixed.bump],
+            ],
+            &crate::id(),
+        )
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+        if account_info.key != &address {
+            Err(ProgramError::InvalidAccountData)?;
+        }
+
+        Ok(fixed)
+    }
 }
 
 pub struct FundingAccountLoader<'a, 'info> {
     pub ai: &'a AccountInfo<'info>,
-    pub fixed: FundingAccountFixed,
-    pub dynamic: RefMut<'a, [u8]>,
+    pub fixed: RefMut<'a, FundingAccountFixed>,
+    pub data_points: RefMut<'a, [DataPointSlot]>,
 }
 
 impl<'a, 'info: 'a> FundingAccountLoader<'a, 'info> {
     pub const NAMESPACE: &'static [u8; 7] = b"funding";
+    /// Scale of the confidence weight, weight = CONFIDENCE_WEIGHT_SCALE / confidence
+    pub const CONFIDENCE_WEIGHT_SCALE: u64 = 1_000_000_000_000;
 
     pub fn size(data_points_count: u16) -> usize {
         FundingAccountFixed::SIZE
@@ -148,85 +330,492 @@ impl<'a, 'info: 'a> FundingAccountLoader<'a, 'info> {
         )
     }
 
-    fn get_start_and_end_index(i: usize) -> (usize, usize) {
-        let start_index = i * FundingAccountFixed::DATA_POINT_SIZE;
-        let end_index = start_index + FundingAccountFixed::DATA_POINT_SIZE;
+    fn confidence_weight(confidence: u64) -> i128 {
+        cmp::max(Self::CONFIDENCE_WEIGHT_SCALE / cmp::max(confidence, 1), 1) as i128
+    }
+
+    pub fn update_ema(&mut self) {
+        let first = self.data_points[0].get().unwrap();
+        let k = (self.fixed.config.period_length + 1) as i64;
+        let n = self.fixed.config.data_points_count as usize;
+
+        let mut ema = first.value;
+        // (EMA of value * weight, EMA of weight)
+        let mut weighted_ema = first.confidence.map(|confidence| {
+            let weight = Self::confidence_weight(confidence);
+            (first.value as i128 * weight, weight)
+        });
+
+        for i in 1..n {
+            let data_point = self.data_points[i].get().unwrap();
+            let diff = data_point.value - ema;
+            ema = diff * 2 / k + ema;
+
+            weighted_ema = match (weighted_ema, data_point.confidence) {
+                (Some((weighted_value_ema, weight_ema)), Some(confidence)) => {
+                    let weight = Self::confidence_weight(confidence);
+                    let weighted_value = data_point.value as i128 * weight;
+
+                    Some((
+                        (weighted_value - weighted_value_ema) * 2 / k as i128 + weighted_value_ema,
+                        (weight - weight_ema) * 2 / k as i128 + weight_ema,
+                    ))
+                }
+                _ => None,
+            };
+        }
+
+        self.fixed.funding_ema = Some(ema).into();
+        self.fixed.confidence_weighted_ema = weighted_ema
+            .map(|(weighted_value_ema, weight_ema)| (weighted_value_ema / weight_ema) as i64)
+            .into();
+    }
+
+    pub fn update_data_points(&mut self, new_data_point: DataPoint) -> FundingResult<()> {
+        let data_points_count = self.fixed.config.data_points_count as usize;
+
+        if let Some(slot) = self.data_points.iter_mut().find(|slot| !slot.is_set()) {
+            slot.set(Some(new_data_point));
+            return Ok(());
+        }
+
+        self.data_points.copy_within(1..data_points_count, 0);
+        self.data_points[data_points_count - 1].set(Some(new_data_point));
+        self.update_ema();
+
+        Ok(())
+    }
+
+    pub fn reset_data_points_and_write_first(
+        &mut self,
+        data_point: DataPoint,
+    ) -> FundingResult<()> {
+        self.fixed.funding_ema = None.into();
+        self.fixed.confidence_weighted_ema = None.into();
+
+        self.data_points.fill(DataPointSlot::default());
+        self.data_points[0].set(Some(data_point));
+
+        Ok(())
+    }
+
+    pub fn load(account_info: &'a AccountInfo<'info>) -> FundingResult<Self> {
+        let data = account_info.try_borrow_mut_data()?;
+        if data.len() < FundingAccountFixed::SIZE
+            || (data.len() - FundingAccountFixed::SIZE) % FundingAccountFixed::DATA_POINT_SIZE != 0
+        {
+            Err(ProgramError::InvalidAccountData)?;
+        }
+
+        let (fixed, data_points) =
+            RefMut::map_split(data, |b| b.split_at_mut(FundingAccountFixed::SIZE));
+
+        let fixed = RefMut::filter_map(fixed, |b| bytemuck::try_from_bytes_mut(b).ok())
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let data_points = RefMut::filter_map(data_points, |b| bytemuck::try_cast_slice_mut(b).ok())
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        Ok(Self {
+            ai: account_info,
+            fixed,
+            data_points,
+        })
+    }
+
+    pub fn try_load(
+        account_info: &'a AccountInfo<'info>,
+        authority: &Pubkey,
+    ) -> FundingResult<Self> {
+        if !account_info.is_writable {
+            Err(ErrorCode::AccountsNeedToBeWritable)?;
+        }
+        if account_info.owner != &crate::id() {
+            Err(ErrorCode::InvalidAccount)?;
+        }
+
+        let data_len = account_info.data_len();
+        if data_len < FundingAccountFixed::SIZE {
+            Err(ProgramError::AccountDataTooSmall)?;
+        }
+
+        let loader = Self::load(account_info)?;
+        let fixed = &loader.fixed;
+
+        if !fixed.has_valid_discriminator() {
+            Err(ErrorCode::InvalidAccountVersion)?;
+        }
+        if loader.data_points.len() != fixed.config.data_points_count as usize {
+            Err(ProgramError::InvalidAccountData)?;
+        }
+
+        let exchange = fixed.exchange().ok_or(ProgramError::InvalidAccountData)?;
+        let (address, bump) = Self::pda(fixed.id, fixed.market_index, &exchange);
+        if account_info.key != &address || fixed.bump != bump {
+            Err(ProgramError::InvalidAccountData)?;
+        }
+        if &fixed.authority != authority {
+            Err(ErrorCode::MissingOrInvalidAuthority)?;
+        }
+
+        Ok(loader)
+    }
+
+    pub fn log(&self) {
+        msg!("bump: {}", self.fixed.bump);
+        msg!("id: {}", self.fixed.id);
+        msg!("exchange: {:?}", self.fixed.exchange());
+        msg!("market_index: {}", self.fixed.market_index);
+        msg!("authority: {}", self.fixed.authority);
+        msg!("last_updated_ts: {}", self.fixed.last_updated_ts);
+        msg!("last_observation_ts: {}", self.fixed.last_observation_ts);
+        msg!("funding_ema: {:?}", self.fixed.funding_ema());
+        msg!(
+            "confidence_weighted_ema: {:?}",
+            self.fixed.confidence_weighted_ema()
+        );
+
+        self.fixed.config.log();
+    }
+
+    pub fn registry_entry(&self) -> FundingResult<RegistryEntry> {
+        Ok(RegistryEntry {
+            address: *self.ai.key,
+            id: self.fixed.id,
+            exchange: self.fixed.exchange().ok_or(ProgramError::InvalidAccountData)?,
+            market_index: self.fixed.market_index,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize, PartialEq, Debug)]
+pub struct Candle {
+    /// Start of the interval the candle covers
+    pub open_ts: i64,
+    pub open: i64,
+    pub high: i64,
+    pub low: i64,
+    pub close: i64,
+}
+
+impl Candle {
+    fn new(open_ts: i64, value: i64) -> Self {
+        Self {
+            open_ts,
+            open: value,
+            high: value,
+            low: value,
+            close: value,
+        }
+    }
+
+    fn update(&mut self, value: i64) {
+        self.high = cmp::max(self.high, value);
+        self.low = cmp::min(self.low, value);
+        self.close = value;
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CandleResolution {
+    Hourly,
+    Daily,
+}
+
+impl CandleResolution {
+    pub fn interval_secs(&self) -> i64 {
+        match self {
+            Self::Hourly => 3600,
+            Self::Daily => 86400,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        match self {
+            Self::Hourly => FundingHistoryFixed::HOURLY_CAPACITY,
+            Self::Daily => FundingHistoryFixed::DAILY_CAPACITY,
+        }
+    }
+
+    /// Offset of the first candle of the resolution in the dynamic part of the account
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Hourly => 0,
+            Self::Daily => FundingHistoryFixed::HOURLY_CAPACITY * FundingHistoryFixed::CANDLE_SIZE,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize, Debug)]
+pub struct CandleRingMeta {
+    /// Index of the most recent candle
+    pub head: u16,
+    pub len: u16,
+}
+
+#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize, Debug)]
+pub struct FundingHistoryFixed {
+    pub bump: u8,
+    pub funding_account: Pubkey,
+    pub hourly: CandleRingMeta,
+    pub daily: CandleRingMeta,
+}
+
+impl FundingHistoryFixed {
+    pub const SIZE: usize = std::mem::size_of::<Self>();
+    pub const CANDLE_SIZE: usize = std::mem::size_of::<Candle>();
+    /// One week of hourly candles
+    pub const HOURLY_CAPACITY: usize = 168;
+    /// Two months of daily candles
+    pub const DAILY_CAPACITY: usize = 60;
+
+    pub fn ring(&self, resolution: CandleResolution) -> &CandleRingMeta {
+        match resolution {
+            CandleResolution::Hourly => &self.hourly,
+            CandleResolution::Daily => &self.daily,
+        }
+    }
+
+    fn ring_mut(&mut self, resolution: CandleResolution) -> &mut CandleRingMeta {
+        match resolution {
+            CandleResolution::Hourly => &mut self.hourly,
+            CandleResolution::Daily => &mut self.daily,
+        }
+    }
+}
+
+pub struct FundingHistoryLoader<'a, 'info> {
+    pub ai: &'a AccountInfo<'info>,
+    pub fixed: FundingHistoryFixed,
+    pub dynamic: RefMut<'a, [u8]>,
+}
+
+impl<'a, 'info: 'a> FundingHistoryLoader<'a, 'info> {
+    pub const NAMESPACE: &'static [u8; 7] = b"history";
+
+    pub fn size() -> usize {
+        FundingHistoryFixed::SIZE
+            + FundingHistoryFixed::CANDLE_SIZE
+                * (FundingHistoryFixed::HOURLY_CAPACITY + FundingHistoryFixed::DAILY_CAPACITY)
+    }
+
+    pub fn pda(funding_account: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::NAMESPACE, funding_account.as_ref()], &crate::id())
+    }
+
+    fn get_start_and_end_index(resolution: CandleResolution, i: usize) -> (usize, usize) {
+        let start_index = resolution.offset() + i * FundingHistoryFixed::CANDLE_SIZE;
+        let end_index = start_index + FundingHistoryFixed::CANDLE_SIZE;
         (start_index, end_index)
     }
 
-    fn write_data_point(&mut self, data_point: Option<i64>, i: usize) -> FundingResult<()> {
-        let (start_index, end_index) = Self::get_start_and_end_index(i);
+    fn write_candle(
+        &mut self,
+        resolution: CandleResolution,
+        candle: &Candle,
+        i: usize,
+    ) -> FundingResult<()> {
+        let (start_index, end_index) = Self::get_start_and_end_index(resolution, i);
         let dst = &mut self.dynamic[start_index..end_index];
         let mut writer = BpfWriter::new(dst);
-        data_point
+        candle
             .serialize(&mut writer)
             .map_err(|_| ProgramError::InvalidAccountData)?;
 
         Ok(())
     }
 
-    fn load_data_point(&self, i: usize) -> Option<i64> {
-        let (start_index, end_index) = Self::get_start_and_end_index(i);
+    fn load_candle(&self, resolution: CandleResolution, i: usize) -> FundingResult<Candle> {
+        let (start_index, end_index) = Self::get_start_and_end_index(resolution, i);
         let bytes = &mut &self.dynamic[start_index..end_index];
-        Option::<i64>::deserialize(bytes).unwrap()
+        Candle::deserialize(bytes).map_err(|_| ProgramError::InvalidAccountData.into())
     }
 
-    pub fn update_ema(&mut self) {
-        let mut ema = self.load_data_point(0).unwrap();
-        let k = (self.fixed.config.period_length + 1) as i64;
-        let n = self.fixed.config.data_points_count as usize;
+    /// Updates the candle of the current interval or opens a new one,
+    /// overwriting the oldest candle once the capacity is reached
+    pub fn roll_candle(
+        &mut self,
+        resolution: CandleResolution,
+        now_ts: i64,
+        ema: i64,
+    ) -> FundingResult<()> {
+        let interval = resolution.interval_secs();
+        let open_ts = now_ts - now_ts.rem_euclid(interval);
+        let ring = *self.fixed.ring(resolution);
+        let head = ring.head as usize;
 
-        for i in 1..n {
-            let data_point = self.load_data_point(i).unwrap();
-            let diff = data_point - ema;
-            ema = diff * 2 / k + ema;
+        if ring.len > 0 {
+            let mut candle = self.load_candle(resolution, head)?;
+
+            if candle.open_ts == open_ts {
+                candle.update(ema);
+                return self.write_candle(resolution, &candle, head);
+            }
         }
 
-        self.fixed.funding_ema = Some(ema);
+        let capacity = resolution.capacity();
+        let new_head = if ring.len == 0 {
+            0
+        } else {
+            (head + 1) % capacity
+        };
+
+        self.write_candle(resolution, &Candle::new(open_ts, ema), new_head)?;
+
+        let ring = self.fixed.ring_mut(resolution);
+        ring.head = new_head as u16;
+        ring.len = cmp::min(ring.len as usize + 1, capacity) as u16;
+
+        Ok(())
     }
 
-    pub fn update_data_points(&mut self, new_data_point: i64) -> FundingResult<()> {
-        let data_points_count = self.fixed.config.data_points_count as usize;
+    pub fn roll_candles(&mut self, now_ts: i64, ema: i64) -> FundingResult<()> {
+        self.roll_candle(CandleResolution::Hourly, now_ts, ema)?;
+        self.roll_candle(CandleResolution::Daily, now_ts, ema)
+    }
+
+    pub fn load(account_info: &'a AccountInfo<'info>) -> FundingResult<Self> {
+        let (fixed, dynamic) = RefMut::map_split(account_info.try_borrow_mut_data()?, |b| {
+            b.split_at_mut(FundingHistoryFixed::SIZE)
+        });
 
-        for i in 0..data_points_count {
-            let data_point = self.load_data_point(i);
+        let fixed = FundingHistoryFixed::deserialize(&mut &fixed[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
 
-            if data_point.is_none() {
-                self.write_data_point(Some(new_data_point), i)?;
-                return Ok(());
-            }
+        Ok(Self {
+            ai: account_info,
+            fixed,
+            dynamic,
+        })
+    }
+
+    pub fn try_load(
+        account_info: &'a AccountInfo<'info>,
+        funding_account: &Pubkey,
+    ) -> FundingResult<Self> {
+        if !account_info.is_writable {
+            Err(ErrorCode::AccountsNeedToBeWritable)?;
+        }
+        if account_info.owner != &crate::id() {
+            Err(ErrorCode::InvalidAccount)?;
+        }
+        if account_info.data_len() != Self::size() {
+            Err(ProgramError::InvalidAccountData)?;
         }
 
-        unsafe {
-            sol_memmove(
-                &mut self.dynamic[0],
-                &mut self.dynamic[FundingAccountFixed::DATA_POINT_SIZE],
-                (data_points_count - 1) * FundingAccountFixed::DATA_POINT_SIZE,
-            );
+        let loader = Self::load(account_info)?;
+
+        let (address, bump) = Self::pda(funding_account);
+        if account_info.key != &address
+            || loader.fixed.bump != bump
+            || &loader.fixed.funding_account != funding_account
+        {
+            Err(ErrorCode::InvalidAccount)?;
         }
-        self.write_data_point(Some(new_data_point), data_points_count - 1)?;
-        self.update_ema();
+
+        Ok(loader)
+    }
+
+    pub fn save(self) -> FundingResult<()> {
+        drop(self.dynamic);
+
+        let data = &mut self.ai.try_borrow_mut_data()?[..FundingHistoryFixed::SIZE];
+        let mut writer = BpfWriter::new(data);
+        self.fixed
+            .serialize(&mut writer)
+            .map_err(|_| ProgramError::InvalidAccountData.into())
+    }
+}
+
+#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize, PartialEq, Debug)]
+pub struct RegistryEntry {
+    pub address: Pubkey,
+    pub id: u16,
+    pub exchange: Exchange,
+    pub market_index: u16,
+}
+
+#[derive(Copy, Clone, Default, BorshDeserialize, BorshSerialize, Debug)]
+pub struct FundingRegistryFixed {
+    pub bump: u8,
+    pub authority: Pubkey,
+    pub entries_count: u16,
+}
+
+impl FundingRegistryFixed {
+    pub const SIZE: usize = std::mem::size_of::<Self>();
+    pub const ENTRY_SIZE: usize = std::mem::size_of::<RegistryEntry>();
+}
+
+pub struct FundingRegistryLoader<'a, 'info> {
+    pub ai: &'a AccountInfo<'info>,
+    pub fixed: FundingRegistryFixed,
+    pub dynamic: RefMut<'a, [u8]>,
+}
+
+impl<'a, 'info: 'a> FundingRegistryLoader<'a, 'info> {
+    pub const NAMESPACE: &'static [u8; 8] = b"registry";
+
+    pub fn size(entries_count: u16) -> usize {
+        FundingRegistryFixed::SIZE + FundingRegistryFixed::ENTRY_SIZE * entries_count as usize
+    }
+
+    pub fn pda(authority: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::NAMESPACE, authority.as_ref()], &crate::id())
+    }
+
+    fn get_start_and_end_index(i: usize) -> (usize, usize) {
+        let start_index = i * FundingRegistryFixed::ENTRY_SIZE;
+        let end_index = start_index + FundingRegistryFixed::ENTRY_SIZE;
+        (start_index, end_index)
+    }
+
+    pub fn write_entry(&mut self, entry: &RegistryEntry, i: usize) -> FundingResult<()> {
+        let (start_index, end_index) = Self::get_start_and_end_index(i);
+        let dst = &mut self.dynamic[start_index..end_index];
+        let mut writer = BpfWriter::new(dst);
+        entry
+            .serialize(&mut writer)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
 
         Ok(())
     }
 
-    pub fn reset_data_points_and_write_first(&mut self, data_point: i64) -> FundingResult<()> {
-        self.fixed.funding_ema = None;
-        self.write_data_point(Some(data_point), 0)?;
+    pub fn load_entry(&self, i: usize) -> FundingResult<RegistryEntry> {
+        let (start_index, end_index) = Self::get_start_and_end_index(i);
+        let bytes = &mut &self.dynamic[start_index..end_index];
+        RegistryEntry::deserialize(bytes).map_err(|_| ProgramError::InvalidAccountData.into())
+    }
 
-        for i in 1..self.fixed.config.data_points_count {
-            self.write_data_point(None, i as usize)?;
+    pub fn find_entry(&self, address: &Pubkey) -> FundingResult<Option<usize>> {
+        for i in 0..self.fixed.entries_count as usize {
+            if &self.load_entry(i)?.address == address {
+                return Ok(Some(i));
+            }
         }
 
+        Ok(None)
+    }
+
+    /// Removes the entry by moving the last entry in its place,
+    /// account needs to be reallocated afterwards
+    pub fn swap_remove_entry(&mut self, i: usize) -> FundingResult<()> {
+        let last_index = self.fixed.entries_count as usize - 1;
+
+        if i != last_index {
+            let last = self.load_entry(last_index)?;
+            self.write_entry(&last, i)?;
+        }
+
+        self.fixed.entries_count -= 1;
         Ok(())
     }
 
     pub fn load(account_info: &'a AccountInfo<'info>) -> FundingResult<Self> {
         let (fixed, dynamic) = RefMut::map_split(account_info.try_borrow_mut_data()?, |b| {
-            b.split_at_mut(FundingAccountFixed::SIZE)
+            b.split_at_mut(FundingRegistryFixed::SIZE)
         });
 
-        let fixed = FundingAccountFixed::deserialize(&mut &fixed[..])
+        let fixed = FundingRegistryFixed::deserialize(&mut &fixed[..])
             .map_err(|_| ProgramError::InvalidAccountData)?;
 
         Ok(Self {
@@ -246,24 +835,23 @@ impl<'a, 'info: 'a> FundingAccountLoader<'a, 'info> {
         if account_info.owner != &crate::id() {
             Err(ErrorCode::InvalidAccount)?;
         }
-
-        let data_len = account_info.data_len();
-        if data_len < FundingAccountFixed::SIZE {
+        if account_info.data_len() < FundingRegistryFixed::SIZE {
             Err(ProgramError::AccountDataTooSmall)?;
         }
 
         let loader = Self::load(account_info)?;
         let fixed = &loader.fixed;
 
-        if loader.dynamic.len()
-            != FundingAccountFixed::DATA_POINT_SIZE * fixed.config.data_points_count as usize
-        {
+        if !fixed.has_valid_discriminator() {
+            Err(ErrorCode::InvalidAccountVersion)?;
+        }
+        if loader.dynamic.len() != FundingRegistryFixed::ENTRY_SIZE * fixed.entries_count as usize {
             Err(ProgramError::InvalidAccountData)?;
         }
 
-        let (address, bump) = Self::pda(fixed.id, fixed.market_index, &fixed.exchange);
+        let (address, bump) = Self::pda(authority);
         if account_info.key != &address || fixed.bump != bump {
-            Err(ProgramError::InvalidAccountData)?;
+            Err(ErrorCode::InvalidAccount)?;
         }
         if &fixed.authority != authority {
             Err(ErrorCode::MissingOrInvalidAuthority)?;
@@ -275,24 +863,12 @@ impl<'a, 'info: 'a> FundingAccountLoader<'a, 'info> {
     pub fn save(self) -> FundingResult<()> {
         drop(self.dynamic);
 
-        let data = &mut self.ai.try_borrow_mut_data()?[..FundingAccountFixed::SIZE];
+        let data = &mut self.ai.try_borrow_mut_data()?[..FundingRegistryFixed::SIZE];
         let mut writer = BpfWriter::new(data);
         self.fixed
             .serialize(&mut writer)
             .map_err(|_| ProgramError::InvalidAccountData.into())
     }
-
-    pub fn log(&self) {
-        msg!("bump: {}", self.fixed.bump);
-        msg!("id: {}", self.fixed.id);
-        msg!("exchange: {:?}", self.fixed.exchange);
-        msg!("market_index: {}", self.fixed.market_index);
-        msg!("authority: {}", self.fixed.authority);
-        msg!("last_updated_ts: {}", self.fixed.last_updated_ts);
-        msg!("funding_ema: {:?}", self.fixed.funding_ema);
-
-        self.fixed.config.log();
-    }
 }
 
 #[cfg(test)]
@@ -300,11 +876,43 @@ pub mod tests {
     use std::cell::{RefCell, RefMut};
 
     use crate::state::{
-        BpfWriter, FundingAccountConfig, FundingAccountFixed, FundingAccountLoader,
+        Candle, CandleResolution, DataPoint, DataPointSlot, FundingAccountConfig,
+        FundingAccountFixed, FundingAccountLoader, FundingHistoryFixed, FundingHistoryLoader,
     };
-    use borsh::BorshSerialize;
     use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
 
+    fn compute_ema(data_points: &[DataPoint], period_length: u32) -> FundingAccountFixed {
+        let fixed = RefCell::new(FundingAccountFixed {
+            config: FundingAccountConfig {
+                period_length,
+                data_points_count: data_points.len() as u16,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let data_points = RefCell::new(
+            data_points
+                .iter()
+                .map(|data_point| {
+                    let mut slot = DataPointSlot::default();
+                    slot.set(Some(*data_point));
+                    slot
+                })
+                .collect::<Vec<DataPointSlot>>(),
+        );
+
+        let def_pk = Pubkey::default();
+        let mut l = 0u64;
+        let mut funding_account = FundingAccountLoader {
+            ai: &AccountInfo::new(&def_pk, false, false, &mut l, &mut [], &def_pk, false, 0),
+            fixed: fixed.borrow_mut(),
+            data_points: RefMut::map(data_points.borrow_mut(), |d| &mut d[..]),
+        };
+
+        funding_account.update_ema();
+        *funding_account.fixed
+    }
+
     #[test]
     fn ema() {
         // 1
@@ -312,35 +920,120 @@ pub mod tests {
         // (3 - 1,66) * 2 / 3 + 1,66 = 2,553
         // (4 - 2,553) * 2 / 3 + 2,553 = ...
         // ...
-        let mut data_points_bytes = [0u8; 192];
-        vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        let data_points = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
             .iter()
-            .enumerate()
-            .for_each(|(i, x)| {
-                let x = Some(x * 1000_000);
-                let offset = i * 16;
-                let dst = &mut data_points_bytes[offset..offset + 16];
-                let mut writer = BpfWriter::new(dst);
-                x.serialize(&mut writer).ok();
-            });
-        let dynamic = RefCell::new(data_points_bytes);
+            .map(|x| DataPoint {
+                value: x * 1000_000,
+                confidence: None,
+            })
+            .collect::<Vec<DataPoint>>();
+
+        let fixed = compute_ema(&data_points, 5);
+        assert_eq!(fixed.funding_ema(), Some(10023121));
+        assert_eq!(fixed.confidence_weighted_ema(), None);
+    }
+
+    #[test]
+    fn confidence_weighted_ema() {
+        // equal confidence everywhere equals the regular EMA
+        let data_points = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+            .iter()
+            .map(|x| DataPoint {
+                value: x * 1000_000,
+                confidence: Some(10_000),
+            })
+            .collect::<Vec<DataPoint>>();
+
+        let fixed = compute_ema(&data_points, 5);
+        let ema = fixed.funding_ema().unwrap();
+        let weighted_ema = fixed.confidence_weighted_ema().unwrap();
+        assert!((ema - weighted_ema).abs() <= 1);
+
+        // noisy outlier gets discounted
+        let mut data_points = vec![
+            DataPoint {
+                value: 1000_000,
+                confidence: Some(10_000),
+            };
+            12
+        ];
+        data_points[11] = DataPoint {
+            value: 50_000_000,
+            confidence: Some(10_000_000),
+        };
+
+        let fixed = compute_ema(&data_points, 5);
+        let ema = fixed.funding_ema().unwrap();
+        let weighted_ema = fixed.confidence_weighted_ema().unwrap();
+        assert!(weighted_ema < ema);
+        assert!((weighted_ema - 1000_000).abs() < 100_000);
+    }
+
+    #[test]
+    fn roll_candles() {
+        let dynamic = RefCell::new(vec![
+            0u8;
+            FundingHistoryLoader::size() - FundingHistoryFixed::SIZE
+        ]);
 
         let def_pk = Pubkey::default();
         let mut l = 0u64;
-        let mut funding_account = FundingAccountLoader {
+        let mut funding_history = FundingHistoryLoader {
             ai: &AccountInfo::new(&def_pk, false, false, &mut l, &mut [], &def_pk, false, 0),
-            fixed: FundingAccountFixed {
-                config: FundingAccountConfig {
-                    period_length: 5,
-                    data_points_count: 12,
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            dynamic: RefMut::from(dynamic.borrow_mut()),
+            fixed: FundingHistoryFixed::default(),
+            dynamic: RefMut::map(dynamic.borrow_mut(), |d| &mut d[..]),
         };
 
-        funding_account.update_ema();
-        assert_eq!(funding_account.fixed.funding_ema, Some(10023121));
+        let hour = CandleResolution::Hourly.interval_secs();
+        assert!(funding_history.roll_candles(hour * 10 + 5, 100).is_ok());
+        assert!(funding_history.roll_candles(hour * 10 + 60, 300).is_ok());
+        assert!(funding_history.roll_candles(hour * 10 + 120, 50).is_ok());
+        assert!(funding_history.roll_candles(hour * 11, 200).is_ok());
+
+        assert_eq!(funding_history.fixed.hourly.len, 2);
+        assert_eq!(funding_history.fixed.hourly.head, 1);
+        assert_eq!(funding_history.fixed.daily.len, 1);
+        assert_eq!(
+            funding_history
+                .load_candle(CandleResolution::Hourly, 0)
+                .ok(),
+            Some(Candle {
+                open_ts: hour * 10,
+                open: 100,
+                high: 300,
+                low: 50,
+                close: 50,
+            })
+        );
+        assert_eq!(
+            funding_history.load_candle(CandleResolution::Daily, 0).ok(),
+            Some(Candle {
+                open_ts: 0,
+                open: 100,
+                high: 300,
+                low: 50,
+                close: 200,
+            })
+        );
+
+        // ring wraps around once capacity is reached
+        for i in 0..FundingHistoryFixed::HOURLY_CAPACITY as i64 {
+            assert!(funding_history
+                .roll_candle(CandleResolution::Hourly, hour * (12 + i), i)
+                .is_ok());
+        }
+
+        assert_eq!(
+            funding_history.fixed.hourly.len as usize,
+            FundingHistoryFixed::HOURLY_CAPACITY
+        );
+        assert_eq!(funding_history.fixed.hourly.head, 1);
+        assert_eq!(
+            funding_history
+                .load_candle(CandleResolution::Hourly, 1)
+                .map(|c| c.close)
+                .ok(),
+            Some(FundingHistoryFixed::HOURLY_CAPACITY as i64 - 1)
+        );
     }
 }
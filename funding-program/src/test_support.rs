@@ -0,0 +1,254 @@
+//! `ProgramTest` fixtures shared by the funding program tests, the compute unit tests and
+//! programs consuming funding accounts, enabled by the `test-support` feature
+
+use solana_program::{
+    clock::Clock, instruction::Instruction, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    instruction::InstructionError,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
+
+use crate::{
+    client::{
+        instructions::{self, InitializeFundingAccountAccounts},
+        state::{load_funding_account, FundingAccount},
+    },
+    error::ErrorCode,
+    state::{Exchange, FundingAccountLoader, FundingHistoryLoader, FundingRegistryLoader},
+};
+
+/// Config of funding accounts created by `initialize_ix`
+#[derive(Clone, Copy, Debug)]
+pub struct FundingAccountParams {
+    pub exchange: Exchange,
+    pub update_frequency_secs: u64,
+    pub staleness_threshold_secs: u64,
+    pub period_length: u32,
+    pub data_points_count: u16,
+}
+
+impl FundingAccountParams {
+    pub fn new(update_frequency_secs: u64, data_points_count: u16) -> Self {
+        Self {
+            exchange: Exchange::Drift,
+            update_frequency_secs,
+            staleness_threshold_secs: 600,
+            period_length: 5,
+            data_points_count,
+        }
+    }
+
+    pub fn address(&self) -> Pubkey {
+        FundingAccountLoader::pda(0, 0, &self.exchange).0
+    }
+}
+
+/// Funding program added as a native processor, other programs can be added before starting
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "funding",
+        crate::id(),
+        processor!(crate::process_instruction),
+    )
+}
+
+pub async fn start() -> ProgramTestContext {
+    program_test().start_with_context().await
+}
+
+pub async fn set_unix_timestamp(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
+
+pub fn funded_keypair(context: &mut ProgramTestContext) -> Keypair {
+    let keypair = Keypair::new();
+    context.set_account(
+        &keypair.pubkey(),
+        &AccountSharedData::new(1000 * LAMPORTS_PER_SOL, 0, &system_program::id()),
+    );
+    keypair
+}
+
+/// Signed and paid by `signer`
+pub async fn process(
+    context: &mut ProgramTestContext,
+    ixs: &[Instruction],
+    signer: &Keypair,
+) -> Result<(), TransactionError> {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(ixs, Some(&signer.pubkey()), &[signer], blockhash);
+
+    context
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+pub async fn process_payer(
+    context: &mut ProgramTestContext,
+    ixs: &[Instruction],
+) -> Result<(), TransactionError> {
+    let payer = context.payer.insecure_clone();
+    process(context, ixs, &payer).await
+}
+
+// Panics if running instruction fails, returns consumed compute units
+pub async fn test_instr_exec_ok(context: &mut ProgramTestContext, instr: Instruction) -> u64 {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instr],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        blockhash,
+    );
+
+    let simulation = context
+        .banks_client
+        .simulate_transaction(transaction.clone())
+        .await
+        .unwrap();
+    let units_consumed = simulation.simulation_details.unwrap().units_consumed;
+
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    units_consumed
+}
+
+// Panics if running instruction succeeds
+pub async fn test_instr_exec_err(
+    context: &mut ProgramTestContext,
+    instr: Instruction,
+) -> TransactionError {
+    process_payer(context, &[instr]).await.unwrap_err()
+}
+
+pub fn assert_funding_error(
+    result: Result<(), TransactionError>,
+    index: u8,
+    error_code: ErrorCode,
+) {
+    assert_eq!(
+        result,
+        Err(TransactionError::InstructionError(
+            index,
+            InstructionError::Custom(error_code as u32)
+        ))
+    );
+}
+
+pub fn assert_instruction_error(result: Result<(), TransactionError>, error: InstructionError) {
+    assert_eq!(result, Err(TransactionError::InstructionError(0, error)));
+}
+
+pub async fn get_account(context: &mut ProgramTestContext, address: &Pubkey) -> Option<Account> {
+    context.banks_client.get_account(*address).await.unwrap()
+}
+
+pub async fn get_funding_account(
+    context: &mut ProgramTestContext,
+    address: &Pubkey,
+) -> FundingAccount {
+    let ai = get_account(context, address).await.unwrap();
+    load_funding_account(&ai.data).unwrap()
+}
+
+pub fn initialize_ix(authority: Pubkey, params: FundingAccountParams) -> Instruction {
+    instructions::initialize_funding_account(
+        InitializeFundingAccountAccounts {
+            authority,
+            funding_account: params.address(),
+            funding_registry: FundingRegistryLoader::pda(&authority).0,
+        },
+        0,
+        params.exchange,
+        0,
+        params.update_frequency_secs,
+        params.staleness_threshold_secs,
+        params.period_length,
+        params.data_points_count,
+    )
+}
+
+pub fn update_ix(
+    authority: Pubkey,
+    funding_account: Pubkey,
+    funding_history: Option<Pubkey>,
+    data_point: i64,
+    confidence: Option<u64>,
+) -> Instruction {
+    instructions::update_funding_data(
+        instructions::UpdateFundingDataAccounts {
+            authority,
+            funding_account,
+            funding_history,
+        },
+        data_point,
+        confidence,
+    )
+}
+
+pub fn transfer_authority_ix(
+    authority: Pubkey,
+    funding_account: Pubkey,
+    new_authority: Pubkey,
+) -> Instruction {
+    instructions::configure_funding_account_authority(
+        instructions::ConfigureFundingAccountAuthorityAccounts {
+            authority,
+            funding_account,
+            funding_registry: FundingRegistryLoader::pda(&authority).0,
+            new_funding_registry: FundingRegistryLoader::pda(&new_authority).0,
+        },
+        new_authority,
+    )
+}
+
+pub fn close_ix(authority: Pubkey, funding_account: Pubkey) -> Instruction {
+    instructions::close_funding_account(instructions::CloseFundingAccountAccounts {
+        authority,
+        funding_account,
+        receiver: authority,
+        funding_registry: FundingRegistryLoader::pda(&authority).0,
+        funding_history: FundingHistoryLoader::pda(&funding_account).0,
+    })
+}
+
+/// Funding account of the payer with all `data_points_count` data points set, one per
+/// `update_frequency_secs` starting at `start_ts`, so both EMAs are available
+pub async fn setup_funding_account(
+    context: &mut ProgramTestContext,
+    params: FundingAccountParams,
+    start_ts: i64,
+    data_point: i64,
+    confidence: Option<u64>,
+) -> Pubkey {
+    let authority = context.payer.pubkey();
+    let funding_account = params.address();
+
+    test_instr_exec_ok(context, initialize_ix(authority, params)).await;
+
+    // the EMA is computed once the data points window is full and shifts
+    for i in 0..=params.data_points_count as i64 {
+        set_unix_timestamp(context, start_ts + i * params.update_frequency_secs as i64).await;
+        test_instr_exec_ok(
+            context,
+            update_ix(authority, funding_account, None, data_point, confidence),
+        )
+        .await;
+    }
+
+    funding_account
+}
@@ -1,412 +1,747 @@
-use std::{rc::Rc, sync::Arc, time::Duration};
-
-use bot::{
-    error::Error,
-    utils::transaction::{self},
-};
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use funding_rate::FundingRate;
+use solana_program::{instruction::Instruction, pubkey::Pubkey, system_program};
+use solana_program_test::ProgramTestContext;
 use solana_sdk::{
-    commitment_config::CommitmentConfig, signature::Keypair, signer::Signer,
-    transaction::Transaction,
+    account::AccountSharedData, instruction::InstructionError, signature::Keypair, signer::Signer,
 };
-use tokio::time::sleep;
 
 use crate::{
     client::{
         instructions::{self, InitializeFundingAccountAccounts},
-        state::load_funding_account,
+        state::{load_funding_account, load_funding_history, load_funding_registry},
+    },
+    error::ErrorCode,
+    state::{
+        Exchange, FundingAccountLoader, FundingHistoryLoader, FundingRegistryLoader, RegistryEntry,
+    },
+    test_support::{
+        assert_funding_error, assert_instruction_error, close_ix, funded_keypair, get_account,
+        get_funding_account, process, process_payer, set_unix_timestamp, start,
+        transfer_authority_ix, FundingAccountParams,
     },
-    state::{Exchange, FundingAccountLoader},
 };
 
-const RPC_URL: &'static str = "http://127.0.0.1:8899";
+const START_TS: i64 = 1_000_000;
+const UPDATE_FREQUENCY_SECS: u64 = 300;
+const STALENESS_THRESHOLD_SECS: u64 = 600;
+
+fn initialize_ix(authority: Pubkey, exchange: Exchange, data_points_count: u16) -> Instruction {
+    crate::test_support::initialize_ix(
+        authority,
+        FundingAccountParams {
+            exchange,
+            staleness_threshold_secs: STALENESS_THRESHOLD_SECS,
+            ..FundingAccountParams::new(UPDATE_FREQUENCY_SECS, data_points_count)
+        },
+    )
+}
 
-struct Wallet {
-    keypair: Keypair,
-    pubkey: Pubkey,
+fn update_ix(
+    authority: Pubkey,
+    funding_account: Pubkey,
+    funding_history: Option<Pubkey>,
+    data_point: i64,
+) -> Instruction {
+    crate::test_support::update_ix(
+        authority,
+        funding_account,
+        funding_history,
+        data_point,
+        None,
+    )
 }
 
-async fn mock_wallet<'a>(rpc_client: &Arc<RpcClient>) -> Rc<Wallet> {
-    let keypair = Keypair::new();
-    let pubkey = keypair.try_pubkey().unwrap();
-    println!("Mock address: {}", pubkey);
+fn configure_ix(
+    authority: Pubkey,
+    funding_account: Pubkey,
+    update_frequency_secs: Option<u64>,
+    staleness_threshold_secs: Option<u64>,
+    data_points_count: Option<u16>,
+) -> Instruction {
+    instructions::configure_funding_account(
+        instructions::ConfigureFundingAccountAccounts {
+            authority,
+            funding_account,
+        },
+        update_frequency_secs,
+        staleness_threshold_secs,
+        None,
+        data_points_count,
+    )
+}
 
-    let lamports: u64 = 1000 * LAMPORTS_PER_SOL;
-    let sig = rpc_client.request_airdrop(&pubkey, lamports).await;
-    assert!(sig.is_ok());
+/// Funding accounts in the registry of `authority`, empty if the registry does not exist
+async fn registered_accounts(context: &mut ProgramTestContext, authority: &Pubkey) -> Vec<Pubkey> {
+    match get_account(context, &FundingRegistryLoader::pda(authority).0).await {
+        Some(ai) => load_funding_registry(&ai.data)
+            .unwrap()
+            .entries
+            .iter()
+            .map(|e| e.address)
+            .collect(),
+        None => vec![],
+    }
+}
 
-    sleep(Duration::from_secs(5)).await;
-    let res = rpc_client
-        .get_signature_status_with_commitment(&sig.unwrap(), CommitmentConfig::confirmed())
-        .await;
+/// Initialized drift funding account of the payer
+async fn setup(context: &mut ProgramTestContext, data_points_count: u16) -> Pubkey {
+    let authority = context.payer.pubkey();
+    set_unix_timestamp(context, START_TS).await;
+
+    let res = process_payer(
+        context,
+        &[initialize_ix(authority, Exchange::Drift, data_points_count)],
+    )
+    .await;
     assert!(res.is_ok());
-    let res = res.unwrap();
-    assert!(res.is_some());
-    assert!(res.unwrap().is_ok());
 
-    Rc::new(Wallet { keypair, pubkey })
+    FundingAccountLoader::pda(0, 0, &Exchange::Drift).0
+}
+
+/// Writes data point `100_000 + i` at `START_TS + i * UPDATE_FREQUENCY_SECS` for each `i`
+async fn update_range(
+    context: &mut ProgramTestContext,
+    funding_account: Pubkey,
+    funding_history: Option<Pubkey>,
+    range: std::ops::Range<i64>,
+) {
+    let authority = context.payer.pubkey();
+
+    for i in range {
+        set_unix_timestamp(context, START_TS + i * UPDATE_FREQUENCY_SECS as i64).await;
+        let res = process_payer(
+            context,
+            &[update_ix(
+                authority,
+                funding_account,
+                funding_history,
+                100_000 + i,
+            )],
+        )
+        .await;
+        assert!(res.is_ok());
+    }
 }
 
-async fn initialize_success(
-    rpc_client: &Arc<RpcClient>,
-    wallet: &Rc<Wallet>,
-) -> Result<(Pubkey, Pubkey), Error> {
+#[tokio::test]
+async fn initialize() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
     let drift_funding_account = FundingAccountLoader::pda(0, 0, &Exchange::Drift).0;
     let mango_funding_account = FundingAccountLoader::pda(0, 0, &Exchange::Mango).0;
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-
-    let ixs = [
-        instructions::initialize_funding_account(
-            InitializeFundingAccountAccounts {
-                authority: wallet.pubkey,
-                funding_account: drift_funding_account,
-            },
-            0,
-            Exchange::Drift,
-            0,
-            300,
-            600,
-            5,
-            12,
-        ),
-        instructions::initialize_funding_account(
-            InitializeFundingAccountAccounts {
-                authority: wallet.pubkey,
-                funding_account: mango_funding_account,
-            },
-            0,
-            Exchange::Mango,
-            0,
-            300,
-            600,
-            5,
-            12,
-        ),
-    ];
-
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&wallet.pubkey),
-        &[&wallet.keypair],
-        blockhash,
-    );
-
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_success());
-
-    let funding_ais = rpc_client
-        .get_multiple_accounts(&[drift_funding_account, mango_funding_account])
-        .await?;
+    let funding_registry = FundingRegistryLoader::pda(&authority).0;
+
+    let res = process_payer(
+        &mut context,
+        &[
+            initialize_ix(authority, Exchange::Drift, 12),
+            initialize_ix(authority, Exchange::Mango, 12),
+        ],
+    )
+    .await;
+    assert!(res.is_ok());
 
-    for (i, exchange) in [Exchange::Drift, Exchange::Mango].iter().enumerate() {
-        let ai = funding_ais[i].clone();
-        assert!(ai.is_some());
-        let ai = ai.unwrap();
-        let funding_account = load_funding_account(&ai.data);
-        assert!(funding_account.is_ok());
-        let funding_account = funding_account.unwrap();
+    for (address, exchange) in [
+        (drift_funding_account, Exchange::Drift),
+        (mango_funding_account, Exchange::Mango),
+    ] {
+        let ai = get_account(&mut context, &address).await.unwrap();
+        let funding_account = load_funding_account(&ai.data).unwrap();
 
-        assert_eq!(&funding_account.exchange, exchange);
-        assert_eq!(funding_account.authority, wallet.pubkey);
+        assert_eq!(funding_account.exchange, exchange);
+        assert_eq!(funding_account.authority, authority);
         assert_eq!(funding_account.market_index, 0);
         assert_eq!(funding_account.funding_ema, None);
         assert_eq!(funding_account.id, 0);
         assert_eq!(funding_account.last_updated_ts, 0);
         assert_eq!(funding_account.config.period_length, 5);
-        assert_eq!(funding_account.config.update_frequency_secs, 300);
-        assert_eq!(funding_account.config.staleness_threshold_secs, 600);
+        assert_eq!(
+            funding_account.config.update_frequency_secs,
+            UPDATE_FREQUENCY_SECS
+        );
+        assert_eq!(
+            funding_account.config.staleness_threshold_secs,
+            STALENESS_THRESHOLD_SECS
+        );
         assert_eq!(funding_account.config.data_points_count, 12);
         assert_eq!(ai.data.len(), FundingAccountLoader::size(12));
-
-        assert!(funding_account.data_points.iter().all(|x| x.is_none()))
+        assert!(funding_account.data_points.iter().all(|x| x.is_none()));
     }
 
-    Ok((drift_funding_account, mango_funding_account))
-}
-
-async fn configure_funding_account(
-    rpc_client: &Arc<RpcClient>,
-    wallet: &Rc<Wallet>,
-    drift_address: Pubkey,
-) -> Result<(), Error> {
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-    let ixs = [instructions::configure_funding_account(
-        instructions::ConfigureFundingAccountAccounts {
-            authority: wallet.pubkey,
-            funding_account: drift_address,
-        },
-        Some(1000),
-        Some(2000),
-        None,
-        None,
-    )];
-
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&wallet.pubkey),
-        &[&wallet.keypair],
-        blockhash,
+    let ai = get_account(&mut context, &funding_registry).await.unwrap();
+    let registry = load_funding_registry(&ai.data).unwrap();
+    assert_eq!(registry.authority, authority);
+    assert_eq!(
+        registry.entries,
+        vec![
+            RegistryEntry {
+                address: drift_funding_account,
+                id: 0,
+                exchange: Exchange::Drift,
+                market_index: 0,
+            },
+            RegistryEntry {
+                address: mango_funding_account,
+                id: 0,
+                exchange: Exchange::Mango,
+                market_index: 0,
+            },
+        ]
     );
 
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_success());
+    // already initialized
+    let res = process_payer(
+        &mut context,
+        &[initialize_ix(authority, Exchange::Drift, 12)],
+    )
+    .await;
+    assert!(res.is_err());
+}
 
-    let ai = rpc_client.get_account(&drift_address).await?;
-    let account = load_funding_account(&ai.data).unwrap();
+#[tokio::test]
+async fn initialize_invalid_config() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+
+    let res = process_payer(
+        &mut context,
+        &[initialize_ix(authority, Exchange::Drift, 1)],
+    )
+    .await;
+    assert_instruction_error(res, InstructionError::InvalidInstructionData);
+
+    let res = process_payer(
+        &mut context,
+        &[instructions::initialize_funding_account(
+            InitializeFundingAccountAccounts {
+                authority,
+                funding_account: FundingAccountLoader::pda(0, 0, &Exchange::Drift).0,
+                funding_registry: FundingRegistryLoader::pda(&authority).0,
+            },
+            0,
+            Exchange::Drift,
+            0,
+            STALENESS_THRESHOLD_SECS,
+            STALENESS_THRESHOLD_SECS,
+            5,
+            12,
+        )],
+    )
+    .await;
+    assert_instruction_error(res, InstructionError::InvalidInstructionData);
+}
+
+#[tokio::test]
+async fn configure() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 12).await;
+
+    let res = process_payer(
+        &mut context,
+        &[configure_ix(
+            authority,
+            funding_account,
+            Some(1000),
+            Some(2000),
+            None,
+        )],
+    )
+    .await;
+    assert!(res.is_ok());
 
+    let account = get_funding_account(&mut context, &funding_account).await;
     assert_eq!(account.config.update_frequency_secs, 1000);
     assert_eq!(account.config.staleness_threshold_secs, 2000);
     assert_eq!(account.config.period_length, 5);
 
-    Ok(())
+    // update frequency has to be lower than the staleness threshold
+    let res = process_payer(
+        &mut context,
+        &[configure_ix(
+            authority,
+            funding_account,
+            Some(2000),
+            None,
+            None,
+        )],
+    )
+    .await;
+    assert_instruction_error(res, InstructionError::InvalidInstructionData);
+
+    let other = funded_keypair(&mut context);
+    let res = process(
+        &mut context,
+        &[configure_ix(
+            other.pubkey(),
+            funding_account,
+            Some(10),
+            None,
+            None,
+        )],
+        &other,
+    )
+    .await;
+    assert_funding_error(res, 0, ErrorCode::MissingOrInvalidAuthority);
 }
 
-async fn increase_data_points_count(
-    rpc_client: &Arc<RpcClient>,
-    wallet: &Rc<Wallet>,
-    drift_address: Pubkey,
-) -> Result<(), Error> {
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-    let ixs = [instructions::configure_funding_account(
-        instructions::ConfigureFundingAccountAccounts {
-            authority: wallet.pubkey,
-            funding_account: drift_address,
-        },
-        None,
-        None,
-        None,
-        Some(20),
-    )];
-
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&wallet.pubkey),
-        &[&wallet.keypair],
-        blockhash,
-    );
+#[tokio::test]
+async fn configure_authority() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 12).await;
+    let new_authority = funded_keypair(&mut context);
+
+    let res = process_payer(
+        &mut context,
+        &[transfer_authority_ix(
+            authority,
+            funding_account,
+            new_authority.pubkey(),
+        )],
+    )
+    .await;
+    assert!(res.is_ok());
 
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_success());
+    let res = process_payer(
+        &mut context,
+        &[update_ix(authority, funding_account, None, 100_000)],
+    )
+    .await;
+    assert_funding_error(res, 0, ErrorCode::MissingOrInvalidAuthority);
+
+    let res = process(
+        &mut context,
+        &[update_ix(
+            new_authority.pubkey(),
+            funding_account,
+            None,
+            100_000,
+        )],
+        &new_authority,
+    )
+    .await;
+    assert!(res.is_ok());
 
-    let ai = rpc_client.get_account(&drift_address).await?;
-    let account = load_funding_account(&ai.data).unwrap();
+    assert!(registered_accounts(&mut context, &authority)
+        .await
+        .is_empty());
+    assert_eq!(
+        registered_accounts(&mut context, &new_authority.pubkey()).await,
+        vec![funding_account]
+    );
 
-    assert_eq!(ai.data.len(), FundingAccountLoader::size(20));
-    assert_eq!(account.data_points[0], Some(10_0000_i64));
-    assert!(account.data_points[1..].iter().all(|x| x.is_none()));
+    // the new authority closes the account through its own registry
+    let res = process(
+        &mut context,
+        &[close_ix(new_authority.pubkey(), funding_account)],
+        &new_authority,
+    )
+    .await;
+    assert!(res.is_ok());
 
-    Ok(())
+    assert!(get_account(&mut context, &funding_account).await.is_none());
+    assert!(registered_accounts(&mut context, &new_authority.pubkey())
+        .await
+        .is_empty());
 }
 
-async fn decrease_data_points_count(
-    rpc_client: &Arc<RpcClient>,
-    wallet: &Rc<Wallet>,
-    drift_address: Pubkey,
-) -> Result<(), Error> {
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-    let ixs = [instructions::configure_funding_account(
-        instructions::ConfigureFundingAccountAccounts {
-            authority: wallet.pubkey,
-            funding_account: drift_address,
-        },
-        None,
-        None,
-        None,
-        Some(10),
-    )];
-
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&wallet.pubkey),
-        &[&wallet.keypair],
-        blockhash,
+#[tokio::test]
+async fn initialize_prefunded_registry() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_registry = FundingRegistryLoader::pda(&authority).0;
+
+    // anyone can send lamports to the registry address before it is created
+    context.set_account(
+        &funding_registry,
+        &AccountSharedData::new(1_000, 0, &system_program::id()),
     );
 
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_success());
+    let funding_account = setup(&mut context, 12).await;
+    assert_eq!(
+        registered_accounts(&mut context, &authority).await,
+        vec![funding_account]
+    );
+}
 
-    let ai = rpc_client.get_account(&drift_address).await?;
-    let account = load_funding_account(&ai.data).unwrap();
+#[tokio::test]
+async fn register_and_close_unregistered() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 12).await;
+    let funding_registry = FundingRegistryLoader::pda(&authority).0;
+
+    // funding accounts created before the registry existed
+    context.set_account(&funding_registry, &AccountSharedData::default());
+
+    let register_ix =
+        instructions::register_funding_account(instructions::RegisterFundingAccountAccounts {
+            authority,
+            funding_account,
+            funding_registry,
+        });
+    let res = process_payer(&mut context, &[register_ix.clone()]).await;
+    assert!(res.is_ok());
+    assert_eq!(
+        registered_accounts(&mut context, &authority).await,
+        vec![funding_account]
+    );
 
-    assert_eq!(ai.data.len(), FundingAccountLoader::size(10));
-    dbg!(&account.data_points);
-    assert!(account.data_points.iter().all(|x| x.is_none()));
+    // registering twice keeps a single entry
+    let res = process_payer(&mut context, &[register_ix]).await;
+    assert!(res.is_ok());
+    assert_eq!(
+        registered_accounts(&mut context, &authority).await,
+        vec![funding_account]
+    );
 
-    Ok(())
+    context.set_account(&funding_registry, &AccountSharedData::default());
+    let res = process_payer(&mut context, &[close_ix(authority, funding_account)]).await;
+    assert!(res.is_ok());
+    assert!(get_account(&mut context, &funding_account).await.is_none());
 }
 
-async fn update_err_wrong_authority(
-    rpc_client: &Arc<RpcClient>,
-    drift_address: Pubkey,
-) -> Result<(), Error> {
-    let fake_wallet = mock_wallet(rpc_client).await;
-
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-
-    let ixs = [instructions::update_funding_account(
-        instructions::UpdateFundingAccountAccounts {
-            authority: fake_wallet.pubkey,
-            funding_account: drift_address,
-        },
-        10_0000,
-    )];
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&fake_wallet.pubkey),
-        &[&fake_wallet.keypair],
-        blockhash,
+#[tokio::test]
+async fn update() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 3).await;
+
+    let other = funded_keypair(&mut context);
+    let res = process(
+        &mut context,
+        &[update_ix(other.pubkey(), funding_account, None, 100_000)],
+        &other,
+    )
+    .await;
+    assert_funding_error(res, 0, ErrorCode::MissingOrInvalidAuthority);
+
+    update_range(&mut context, funding_account, None, 0..1).await;
+    let account = get_funding_account(&mut context, &funding_account).await;
+    assert_eq!(account.data_points[0].map(|dp| dp.value), Some(100_000));
+    assert_eq!(account.funding_ema, None);
+    assert_eq!(account.last_updated_ts, START_TS);
+
+    set_unix_timestamp(&mut context, START_TS + UPDATE_FREQUENCY_SECS as i64 - 1).await;
+    let res = process_payer(
+        &mut context,
+        &[update_ix(authority, funding_account, None, 100_000)],
+    )
+    .await;
+    assert_funding_error(res, 0, ErrorCode::UpdateTooSoon);
+
+    // the EMA is computed once the data points window is full and shifts
+    update_range(&mut context, funding_account, None, 1..5).await;
+    let account = get_funding_account(&mut context, &funding_account).await;
+    assert!(account.funding_ema.is_some());
+    assert_eq!(
+        account
+            .ordered_data_points()
+            .iter()
+            .map(|dp| dp.value)
+            .collect::<Vec<i64>>(),
+        vec![100_002, 100_003, 100_004]
     );
+}
 
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_err());
+#[tokio::test]
+async fn update_stale_reset() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 3).await;
+
+    update_range(&mut context, funding_account, None, 0..4).await;
+    let account = get_funding_account(&mut context, &funding_account).await;
+    assert!(account.funding_ema.is_some());
+
+    let stale_ts = account.last_updated_ts + STALENESS_THRESHOLD_SECS as i64 + 1;
+    set_unix_timestamp(&mut context, stale_ts).await;
+    let res = process_payer(
+        &mut context,
+        &[update_ix(authority, funding_account, None, 200_000)],
+    )
+    .await;
+    assert!(res.is_ok());
 
-    Ok(())
+    let account = get_funding_account(&mut context, &funding_account).await;
+    assert_eq!(account.funding_ema, None);
+    assert_eq!(account.last_updated_ts, stale_ts);
+    assert_eq!(account.data_points[0].map(|dp| dp.value), Some(200_000));
+    assert!(account.data_points[1..].iter().all(|x| x.is_none()));
 }
 
-async fn update_success(
-    rpc_client: &Arc<RpcClient>,
-    wallet: &Rc<Wallet>,
-    drift_address: Pubkey,
-    mango_address: Pubkey,
-) -> Result<(), Error> {
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-
-    let ixs = [
-        instructions::update_funding_account(
-            instructions::UpdateFundingAccountAccounts {
-                authority: wallet.pubkey,
-                funding_account: drift_address,
-            },
-            10_0000,
-        ),
-        instructions::update_funding_account(
-            instructions::UpdateFundingAccountAccounts {
-                authority: wallet.pubkey,
-                funding_account: mango_address,
-            },
-            0,
-        ),
-    ];
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&wallet.pubkey),
-        &[&wallet.keypair],
-        blockhash,
-    );
-
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_success());
+#[tokio::test]
+async fn resize() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 12).await;
+    update_range(&mut context, funding_account, None, 0..1).await;
+
+    let res = process_payer(
+        &mut context,
+        &[configure_ix(
+            authority,
+            funding_account,
+            None,
+            None,
+            Some(20),
+        )],
+    )
+    .await;
+    assert!(res.is_ok());
 
-    let ai = rpc_client.get_account(&drift_address).await?;
+    let ai = get_account(&mut context, &funding_account).await.unwrap();
     let account = load_funding_account(&ai.data).unwrap();
+    assert_eq!(ai.data.len(), FundingAccountLoader::size(20));
+    assert_eq!(account.data_points[0].map(|dp| dp.value), Some(100_000));
+    assert!(account.data_points[1..].iter().all(|x| x.is_none()));
 
-    assert_eq!(account.data_points[0], Some(10_0000_i64));
-    assert_eq!(account.funding_ema, None);
-    assert_ne!(account.last_updated_ts, 0);
+    let authority_lamports = get_account(&mut context, &authority)
+        .await
+        .unwrap()
+        .lamports;
+    let res = process_payer(
+        &mut context,
+        &[configure_ix(
+            authority,
+            funding_account,
+            None,
+            None,
+            Some(10),
+        )],
+    )
+    .await;
+    assert!(res.is_ok());
 
-    Ok(())
-}
+    let ai = get_account(&mut context, &funding_account).await.unwrap();
+    let account = load_funding_account(&ai.data).unwrap();
+    assert_eq!(ai.data.len(), FundingAccountLoader::size(10));
+    assert_eq!(account.last_updated_ts, 0);
+    assert!(account.data_points.iter().all(|x| x.is_none()));
 
-async fn update_err_too_soon(
-    rpc_client: &Arc<RpcClient>,
-    wallet: &Rc<Wallet>,
-    drift_address: Pubkey,
-) -> Result<(), Error> {
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-
-    let ixs = [instructions::update_funding_account(
-        instructions::UpdateFundingAccountAccounts {
-            authority: wallet.pubkey,
-            funding_account: drift_address,
-        },
-        10_0000,
-    )];
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&wallet.pubkey),
-        &[&wallet.keypair],
-        blockhash,
-    );
+    // excess rent is refunded to the authority, minus the transaction fee
+    let refunded_lamports = get_account(&mut context, &authority)
+        .await
+        .unwrap()
+        .lamports;
+    assert!(refunded_lamports > authority_lamports - 5000);
+}
 
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_err());
+#[tokio::test]
+async fn history() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 3).await;
+    let funding_history = FundingHistoryLoader::pda(&funding_account).0;
+
+    let res = process_payer(
+        &mut context,
+        &[instructions::initialize_funding_history(
+            instructions::InitializeFundingHistoryAccounts {
+                authority,
+                funding_account,
+                funding_history,
+            },
+        )],
+    )
+    .await;
+    assert!(res.is_ok());
 
-    Ok(())
-}
+    update_range(&mut context, funding_account, Some(funding_history), 0..5).await;
 
-async fn close_account_success(
-    rpc_client: &Arc<RpcClient>,
-    wallet: &Rc<Wallet>,
-    drift_address: Pubkey,
-) -> Result<(), Error> {
-    let blockhash = rpc_client.get_latest_blockhash().await?;
-    let ixs = [instructions::close_funding_account(
-        instructions::CloseFundingAccountAccounts {
-            authority: wallet.pubkey,
-            funding_account: drift_address,
-            receiver: wallet.pubkey,
-        },
-    )];
+    let ai = get_account(&mut context, &funding_history).await.unwrap();
+    let history = load_funding_history(&ai.data).unwrap();
+    let account = get_funding_account(&mut context, &funding_account).await;
 
-    let tx = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&wallet.pubkey),
-        &[&wallet.keypair],
-        blockhash,
+    assert_eq!(history.funding_account, funding_account);
+    assert_eq!(history.hourly.len(), 1);
+    assert_eq!(history.daily.len(), 1);
+    assert_eq!(
+        Some(history.hourly[0].close),
+        account.funding_ema.map(FundingRate::raw)
     );
+}
+
+/// Ed25519 verification of the `signer` signature followed by the signed update
+fn signed_update_ixs(
+    funding_account: Pubkey,
+    observation_ts: i64,
+    signer: &Keypair,
+) -> [Instruction; 2] {
+    let message =
+        instructions::signed_funding_update_message(funding_account, 100_000, None, observation_ts);
+    let signature = signer.sign_message(&message);
+
+    [
+        instructions::ed25519_verify(
+            signer.pubkey(),
+            signature.as_ref().try_into().unwrap(),
+            &message,
+        ),
+        instructions::update_funding_data_signed(
+            instructions::UpdateFundingDataSignedAccounts {
+                funding_account,
+                funding_history: None,
+            },
+            100_000,
+            None,
+            observation_ts,
+        ),
+    ]
+}
 
-    let res = transaction::send_and_confirm_transaction(rpc_client, &tx).await?;
-    assert!(res.is_success());
+#[tokio::test]
+async fn signed_update() {
+    let mut context = start().await;
+    let authority = context.payer.insecure_clone();
+    let funding_account = setup(&mut context, 12).await;
+    let observation_ts = START_TS + 10;
+    set_unix_timestamp(&mut context, observation_ts).await;
+
+    // submitted by another payer
+    let relayer = funded_keypair(&mut context);
+    let res = process(
+        &mut context,
+        &signed_update_ixs(funding_account, observation_ts, &authority),
+        &relayer,
+    )
+    .await;
+    assert!(res.is_ok());
 
-    Ok(())
+    let account = get_funding_account(&mut context, &funding_account).await;
+    assert_eq!(account.data_points[0].map(|dp| dp.value), Some(100_000));
+
+    // replayed observation
+    set_unix_timestamp(&mut context, observation_ts + UPDATE_FREQUENCY_SECS as i64).await;
+    let res = process(
+        &mut context,
+        &signed_update_ixs(funding_account, observation_ts, &authority),
+        &relayer,
+    )
+    .await;
+    assert_funding_error(res, 1, ErrorCode::InvalidObservationTimestamp);
+
+    let res = process(
+        &mut context,
+        &signed_update_ixs(
+            funding_account,
+            observation_ts + UPDATE_FREQUENCY_SECS as i64,
+            &relayer,
+        ),
+        &relayer,
+    )
+    .await;
+    assert_funding_error(res, 1, ErrorCode::MissingOrInvalidAuthority);
+
+    let res = process(
+        &mut context,
+        &signed_update_ixs(
+            funding_account,
+            observation_ts + UPDATE_FREQUENCY_SECS as i64,
+            &authority,
+        )[1..],
+        &relayer,
+    )
+    .await;
+    assert_funding_error(res, 0, ErrorCode::MissingOrInvalidSignature);
 }
 
 #[tokio::test]
-async fn test() {
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(
-        RPC_URL.to_string(),
-        CommitmentConfig::confirmed(),
-    ));
-    let wallet = mock_wallet(&rpc_client).await;
-
-    let init_res = initialize_success(&rpc_client, &wallet).await;
-    assert!(init_res.is_ok());
-    let (drift_address, mango_address) = init_res.unwrap();
-
-    assert!(update_err_wrong_authority(&rpc_client, drift_address)
-        .await
-        .is_ok());
+async fn close() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 12).await;
+    let funding_history = FundingHistoryLoader::pda(&funding_account).0;
+
+    let res = process_payer(
+        &mut context,
+        &[instructions::initialize_funding_history(
+            instructions::InitializeFundingHistoryAccounts {
+                authority,
+                funding_account,
+                funding_history,
+            },
+        )],
+    )
+    .await;
+    assert!(res.is_ok());
 
-    assert!(
-        configure_funding_account(&rpc_client, &wallet, drift_address)
-            .await
-            .is_ok()
-    );
+    let other = funded_keypair(&mut context);
+    let res = process(
+        &mut context,
+        &[close_ix(other.pubkey(), funding_account)],
+        &other,
+    )
+    .await;
+    assert_funding_error(res, 0, ErrorCode::MissingOrInvalidAuthority);
+
+    // the history would be orphaned
+    let mut without_history_ix = close_ix(authority, funding_account);
+    without_history_ix.accounts.pop();
+    let res = process_payer(&mut context, &[without_history_ix]).await;
+    assert_instruction_error(res, InstructionError::NotEnoughAccountKeys);
+
+    let mut other_history_ix = close_ix(authority, funding_account);
+    other_history_ix.accounts[4].pubkey = other.pubkey();
+    let res = process_payer(&mut context, &[other_history_ix]).await;
+    assert_funding_error(res, 0, ErrorCode::InvalidAccount);
+
+    let res = process_payer(&mut context, &[close_ix(authority, funding_account)]).await;
+    assert!(res.is_ok());
 
-    assert!(
-        update_success(&rpc_client, &wallet, drift_address, mango_address)
-            .await
-            .is_ok()
-    );
+    assert!(get_account(&mut context, &funding_account).await.is_none());
+    assert!(get_account(&mut context, &funding_history).await.is_none());
 
-    assert!(update_err_too_soon(&rpc_client, &wallet, drift_address)
+    assert!(!registered_accounts(&mut context, &authority)
         .await
-        .is_ok());
+        .contains(&funding_account));
+}
 
-    assert!(
-        increase_data_points_count(&rpc_client, &wallet, drift_address)
-            .await
-            .is_ok()
-    );
+#[tokio::test]
+async fn signed_update_future_observation() {
+    let mut context = start().await;
+    let authority = context.payer.insecure_clone();
+    let funding_account = setup(&mut context, 12).await;
+    let relayer = funded_keypair(&mut context);
+
+    let now_ts = START_TS + 10;
+    let future_ts = now_ts + UPDATE_FREQUENCY_SECS as i64;
+    let ixs = signed_update_ixs(funding_account, future_ts, &authority);
+
+    set_unix_timestamp(&mut context, now_ts).await;
+    let res = process(&mut context, &ixs, &relayer).await;
+    assert_funding_error(res, 1, ErrorCode::InvalidObservationTimestamp);
+
+    set_unix_timestamp(&mut context, future_ts).await;
+    let res = process(&mut context, &ixs, &relayer).await;
+    assert!(res.is_ok());
 
-    assert!(
-        decrease_data_points_count(&rpc_client, &wallet, drift_address)
-            .await
-            .is_ok()
-    );
+    let account = get_funding_account(&mut context, &funding_account).await;
+    assert_eq!(account.last_observation_ts, future_ts);
 
-    assert!(close_account_success(&rpc_client, &wallet, drift_address)
-        .await
-        .is_ok());
+    // the same data point can not be applied twice
+    set_unix_timestamp(&mut context, future_ts + UPDATE_FREQUENCY_SECS as i64).await;
+    let res = process(&mut context, &ixs, &relayer).await;
+    assert_funding_error(res, 1, ErrorCode::InvalidObservationTimestamp);
+}
+
+#[tokio::test]
+async fn update_previous_layout() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let funding_account = setup(&mut context, 3).await;
+
+    // accounts written before the discriminator was added are rejected instead of misread
+    let mut ai = get_account(&mut context, &funding_account).await.unwrap();
+    ai.data[..8].fill(0);
+    context.set_account(&funding_account, &ai.clone().into());
+    assert!(load_funding_account(&ai.data).is_err());
+
+    let res = process_payer(
+        &mut context,
+        &[update_ix(authority, funding_account, None, 100_000)],
+    )
+    .await;
+    assert_funding_error(res, 0, ErrorCode::InvalidAccountVersion);
 }
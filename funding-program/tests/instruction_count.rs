@@ -0,0 +1,84 @@
+//! Compute units consumed by the funding program instructions
+//!
+//! Run with `cargo test-sbf --test instruction_count -- --nocapture` so the
+//! numbers come from the compiled program instead of the native processor.
+//! Exceeding one of the bounds below fails the test, they are only raised
+//! together with a change that knowingly costs more compute units.
+
+use funding::test_support::{
+    initialize_ix, set_unix_timestamp, start, test_instr_exec_ok, update_ix, FundingAccountParams,
+};
+use solana_sdk::signature::Signer;
+
+/// Creates the funding account and the registry of the authority
+const MAX_INITIALIZE_CU: u64 = 40_000;
+/// Writes the first data point, the EMA is not computed yet
+const MAX_UPDATE_FIRST_CU: u64 = 15_000;
+/// EMA over all data points, scales with `data_points_count`
+const MAX_UPDATE_ROLLING_12_CU: u64 = 20_000;
+const MAX_UPDATE_ROLLING_30_CU: u64 = 30_000;
+const MAX_UPDATE_ROLLING_30_CONFIDENCE_CU: u64 = 40_000;
+
+#[tokio::test]
+async fn test_initialize() {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+
+    let units = test_instr_exec_ok(
+        &mut context,
+        initialize_ix(authority, FundingAccountParams::new(60, 30)),
+    )
+    .await;
+    println!("InitializeFundingAccount: {} CU", units);
+    assert!(units <= MAX_INITIALIZE_CU);
+}
+
+async fn test_update(data_points_count: u16, confidence: Option<u64>, max_rolling_units: u64) {
+    let mut context = start().await;
+    let authority = context.payer.pubkey();
+    let params = FundingAccountParams::new(60, data_points_count);
+    let funding_account = params.address();
+
+    test_instr_exec_ok(&mut context, initialize_ix(authority, params)).await;
+
+    let mut ts = 1_000_000;
+    let mut units = vec![];
+    // fills all data points and performs one more update that shifts the window
+    for i in 0..=data_points_count as i64 {
+        set_unix_timestamp(&mut context, ts).await;
+        units.push(
+            test_instr_exec_ok(
+                &mut context,
+                update_ix(authority, funding_account, None, 1_000_000 + i, confidence),
+            )
+            .await,
+        );
+        ts += 60;
+    }
+
+    println!(
+        "UpdateFundingData ({} data points, confidence: {}): first {} CU, filling {} CU, rolling EMA {} CU",
+        data_points_count,
+        confidence.is_some(),
+        units[0],
+        units[1],
+        units[units.len() - 1],
+    );
+    assert!(units[0] <= MAX_UPDATE_FIRST_CU);
+    assert!(units.iter().all(|units| *units <= max_rolling_units));
+}
+
+#[tokio::test]
+async fn test_update_12_data_points() {
+    test_update(12, None, MAX_UPDATE_ROLLING_12_CU).await;
+}
+
+#[tokio::test]
+async fn test_update_30_data_points() {
+    test_update(30, None, MAX_UPDATE_ROLLING_30_CU).await;
+}
+
+#[tokio::test]
+async fn test_update_30_data_points_with_confidence() {
+    test_update(30, Some(10_000), MAX_UPDATE_ROLLING_30_CONFIDENCE_CU).await;
+}
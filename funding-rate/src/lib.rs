@@ -0,0 +1,219 @@
+//! Funding rates tagged with the period they are paid over, so rates of different
+//! venues can only be compared once converted to the same period
+
+use std::{
+    fmt,
+    iter::Sum,
+    marker::PhantomData,
+    ops::{Add, Neg, Sub},
+};
+
+/// Funding rates are percentages with 6 decimals, ex: 1000000 = 1.000000%
+pub const PRECISION: i64 = 1_000_000;
+
+pub trait FundingPeriod: Copy {
+    const HOURS: i64;
+    const NAME: &'static str;
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PerHour;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Per8Hours;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PerDay;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Apr;
+
+impl FundingPeriod for PerHour {
+    const HOURS: i64 = 1;
+    const NAME: &'static str = "per hour";
+}
+
+impl FundingPeriod for Per8Hours {
+    const HOURS: i64 = 8;
+    const NAME: &'static str = "per 8h";
+}
+
+impl FundingPeriod for PerDay {
+    const HOURS: i64 = 24;
+    const NAME: &'static str = "per day";
+}
+
+impl FundingPeriod for Apr {
+    const HOURS: i64 = 24 * 365;
+    const NAME: &'static str = "APR";
+}
+
+/// Percentage with `PRECISION` decimals paid over `P`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FundingRate<P> {
+    value: i64,
+    period: PhantomData<P>,
+}
+
+impl<P: FundingPeriod> FundingRate<P> {
+    pub const ZERO: Self = Self::from_raw(0);
+
+    pub const fn from_raw(value: i64) -> Self {
+        Self {
+            value,
+            period: PhantomData,
+        }
+    }
+
+    pub const fn raw(self) -> i64 {
+        self.value
+    }
+
+    /// ex: 1.5 = 1.5%
+    pub fn from_percent_f64(percent: f64) -> Self {
+        Self::from_raw((percent * PRECISION as f64).round() as i64)
+    }
+
+    /// ex: 1.5 = 1.5%
+    pub fn as_percent_f64(self) -> f64 {
+        self.value as f64 / PRECISION as f64
+    }
+
+    /// Conversion to a shorter period truncates towards zero, `None` on overflow
+    pub fn checked_to<Q: FundingPeriod>(self) -> Option<FundingRate<Q>> {
+        let value = (self.value as i128)
+            .checked_mul(Q::HOURS as i128)?
+            .checked_div(P::HOURS as i128)?;
+
+        i64::try_from(value).ok().map(FundingRate::from_raw)
+    }
+
+    /// Conversion to a shorter period truncates towards zero, saturates on overflow
+    pub fn to<Q: FundingPeriod>(self) -> FundingRate<Q> {
+        self.checked_to().unwrap_or(if self.value < 0 {
+            FundingRate::from_raw(i64::MIN)
+        } else {
+            FundingRate::from_raw(i64::MAX)
+        })
+    }
+
+    pub fn per_hour(self) -> FundingRate<PerHour> {
+        self.to()
+    }
+
+    pub fn per_8_hours(self) -> FundingRate<Per8Hours> {
+        self.to()
+    }
+
+    pub fn per_day(self) -> FundingRate<PerDay> {
+        self.to()
+    }
+
+    pub fn apr(self) -> FundingRate<Apr> {
+        self.to()
+    }
+
+    pub fn abs(self) -> Self {
+        Self::from_raw(self.value.saturating_abs())
+    }
+}
+
+impl<P: FundingPeriod> Add for FundingRate<P> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::from_raw(self.value + rhs.value)
+    }
+}
+
+impl<P: FundingPeriod> Sub for FundingRate<P> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::from_raw(self.value - rhs.value)
+    }
+}
+
+impl<P: FundingPeriod> Neg for FundingRate<P> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::from_raw(-self.value)
+    }
+}
+
+impl<P: FundingPeriod> Sum for FundingRate<P> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl<P: FundingPeriod> fmt::Display for FundingRate<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.value < 0 { "-" } else { "" };
+        let abs = self.value.unsigned_abs();
+        let precision = PRECISION as u64;
+
+        write!(
+            f,
+            "{}{}.{:06}% {}",
+            sign,
+            abs / precision,
+            abs % precision,
+            P::NAME
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let hourly = FundingRate::<PerHour>::from_raw(1_000);
+
+        assert_eq!(hourly.apr(), FundingRate::<Apr>::from_raw(8_760_000));
+        assert_eq!(hourly.per_8_hours(), FundingRate::<Per8Hours>::from_raw(8_000));
+        assert_eq!(hourly.per_day(), FundingRate::<PerDay>::from_raw(24_000));
+        assert_eq!(
+            FundingRate::<PerDay>::from_raw(24_000).apr(),
+            FundingRate::<Apr>::from_raw(8_760_000)
+        );
+        assert_eq!(hourly.apr().per_hour(), hourly);
+        assert_eq!((-hourly).apr().raw(), -8_760_000);
+
+        // truncated towards zero
+        assert_eq!(FundingRate::<Apr>::from_raw(8_759).per_hour().raw(), 0);
+        assert_eq!(FundingRate::<Apr>::from_raw(-8_761).per_hour().raw(), -1);
+
+        assert_eq!(
+            FundingRate::<PerHour>::from_raw(i64::MAX).checked_to::<Apr>(),
+            None
+        );
+        assert_eq!(FundingRate::<PerHour>::from_raw(i64::MIN).apr().raw(), i64::MIN);
+    }
+
+    #[test]
+    fn percent() {
+        let apr = FundingRate::<Apr>::from_percent_f64(8.76);
+
+        assert_eq!(apr.raw(), 8_760_000);
+        assert_eq!(apr.as_percent_f64(), 8.76);
+        assert_eq!(apr.to_string(), "8.760000% APR");
+        assert_eq!((-apr.per_hour()).to_string(), "-0.001000% per hour");
+    }
+
+    #[test]
+    fn arithmetic() {
+        let drift = FundingRate::<Apr>::from_raw(10_000_000);
+        let mango = FundingRate::<Apr>::from_raw(4_000_000);
+
+        assert_eq!(drift - mango, FundingRate::from_raw(6_000_000));
+        assert_eq!((mango - drift).abs(), FundingRate::from_raw(6_000_000));
+        assert_eq!(
+            [drift, mango].into_iter().sum::<FundingRate<Apr>>(),
+            FundingRate::from_raw(14_000_000)
+        );
+    }
+}
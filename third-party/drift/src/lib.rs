@@ -1,3 +1,4 @@
+use funding_rate::{Apr, FundingRate, PerHour};
 use impl_helpers::{Cast, SafeMath};
 use math::{
     calculate_auction_price, calculate_funding_payment_in_quote_precision,
@@ -439,7 +440,7 @@ impl accounts::PerpMarket {
         oracle_price: i64,
         oracle_confidence: u64,
         now_ts: i64,
-    ) -> DriftResult<i64> {
+    ) -> DriftResult<FundingRate<Apr>> {
         use std::cmp::{max, min};
 
         let reserve_price = self.amm.reserve_price()?;
@@ -510,11 +511,9 @@ impl accounts::PerpMarket {
             .safe_div(oracle_price_twap.cast()?)?
             .unsigned_abs();
 
-        let funding_apr: i64 = funding_rate
-            .safe_mul(100)?
-            .safe_mul(24)?
-            .safe_mul(365)?
-            .cast()?;
+        let funding_apr = FundingRate::<PerHour>::from_raw(funding_rate.safe_mul(100)?.cast()?)
+            .checked_to::<Apr>()
+            .ok_or(DriftError::MathError)?;
 
         Ok(match funding_direction {
             types::PositionDirection::Long => -funding_apr,
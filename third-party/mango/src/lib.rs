@@ -1,5 +1,6 @@
 use anchor_lang::prelude::Pubkey;
 use bytemuck::cast_ref;
+use funding_rate::{Apr, FundingPeriod, FundingRate, PerDay, PRECISION};
 use iter::{BookSideIter, OrderTreeIter};
 
 pub mod iter;
@@ -183,7 +184,7 @@ impl accounts::PerpMarket {
         asks: &accounts::BookSide,
         oracle_price: fixed::types::I80F48,
         now_ts: u64,
-    ) -> Result<i64, ()> {
+    ) -> Result<FundingRate<Apr>, ()> {
         use fixed::types::I80F48;
 
         let oracle_price_lots = self.mango_native_price_to_lot(oracle_price).ok_or(())?;
@@ -207,12 +208,23 @@ impl accounts::PerpMarket {
             (None, None) => I80F48::ZERO,
         };
 
-        // 1e6 precision
-        let fr = funding_rate * 100000000 * 365;
-        Ok(fr.to_num())
+        daily_funding_rate_to_apr(funding_rate).ok_or(())
     }
 }
 
+/// Mango funding rates are daily fractions, they are annualized before being truncated
+/// to `PRECISION` so the rounding error is not multiplied by 365
+fn daily_funding_rate_to_apr(funding_rate: fixed::types::I80F48) -> Option<FundingRate<Apr>> {
+    use fixed::types::I80F48;
+
+    let periods = I80F48::from_num(Apr::HOURS / PerDay::HOURS);
+    funding_rate
+        .checked_mul(I80F48::from_num(100 * PRECISION))?
+        .checked_mul(periods)?
+        .checked_to_num()
+        .map(FundingRate::from_raw)
+}
+
 impl Default for accounts::MangoAccount {
     fn default() -> Self {
         Self {
@@ -430,3 +442,34 @@ pub mod oracle_math {
         fixed::types::I80F48::from_le_bytes(mango_i80f48.val.to_le_bytes())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fixed::types::I80F48;
+    use funding_rate::{Apr, FundingRate};
+
+    use super::daily_funding_rate_to_apr;
+
+    #[test]
+    fn daily_funding_rate_annualized_like_baseline() {
+        for funding_rate in [0.0, 0.000_012_345_678_9, -0.000_012_345_678_9, 0.001, -0.05] {
+            let funding_rate = I80F48::from_num(funding_rate);
+            // computation before the `FundingRate` newtype
+            let baseline: i64 = (funding_rate * 100000000 * 365).to_num();
+
+            assert_eq!(
+                daily_funding_rate_to_apr(funding_rate),
+                Some(FundingRate::<Apr>::from_raw(baseline))
+            );
+        }
+
+        // truncating the daily rate first would give 1234 * 365
+        assert_eq!(
+            daily_funding_rate_to_apr(I80F48::from_num(0.000_012_345_678_9))
+                .unwrap()
+                .raw(),
+            450_617
+        );
+        assert_eq!(daily_funding_rate_to_apr(I80F48::MAX), None);
+    }
+}